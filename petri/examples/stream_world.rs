@@ -0,0 +1,38 @@
+//! Simulate a dish and print a `WorldDelta` as one line of json every second.
+//! `cargo run -p petri --example stream_world -- [dish file] [steps per second]`
+
+use std::{
+	env,
+	io::{self, Write},
+	path::Path,
+	thread,
+	time::Duration,
+};
+
+use petri::{Dish, StepMode, WorldDelta};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+	let mut args = env::args().skip(1);
+	let mut dish = match args.next() {
+		Some(path) => petri::io::load(Path::new(&path))?,
+		None => Dish::new(),
+	};
+	let steps = args
+		.next()
+		.map(|s| s.parse())
+		.transpose()?
+		.unwrap_or(10_000);
+	let mut stdout = io::stdout().lock();
+	let mut revision = 0;
+	loop {
+		let delta = dish.serialize_world_delta(revision);
+		revision = match &delta {
+			WorldDelta::Full { revision, .. } | WorldDelta::Tiles { revision, .. } => *revision,
+		};
+		serde_json::to_writer(&mut stdout, &delta)?;
+		writeln!(stdout)?;
+		stdout.flush()?;
+		thread::sleep(Duration::from_secs(1));
+		dish.step(StepMode::Random, steps);
+	}
+}
@@ -0,0 +1,422 @@
+use crate::{Cell, CellData, CellGroup, Dish, Rule, RuleCellFrom, RuleCellTo, SubRule};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+	pub rule_a: usize,
+	pub rule_b: usize,
+	pub kind: OverlapKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverlapKind {
+	/// both rules have the same from-pattern
+	Identical,
+	/// rule_b matches whenever rule_a matches, so rule_a can be shadowed by rule_b
+	Subsumed,
+	/// both rules can match at the same origin and write different things to these cells
+	ConflictingOutputs(Vec<(usize, usize)>),
+}
+
+/// why a rule can never match, positions are in the base pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+	/// the group has no members and doesn't match outside the world
+	EmptyGroup { x: usize, y: usize, group: usize },
+	/// the group index is past the end of the group list
+	MissingGroup { x: usize, y: usize, group: usize },
+	/// the cell id is past the end of the cell types
+	MissingCell { x: usize, y: usize, cell: Cell },
+}
+
+/// the set of world cells a single rule cell accepts
+#[derive(Debug, Clone, PartialEq)]
+enum Constraint {
+	Any,
+	Set { cells: Vec<Cell>, void: bool },
+}
+
+impl Constraint {
+	fn new(from: &RuleCellFrom, groups: &[CellGroup]) -> Self {
+		match from {
+			RuleCellFrom::Any => Constraint::Any,
+			RuleCellFrom::One(cell) if cell.is_boundary() => Constraint::Set {
+				cells: Vec::new(),
+				void: true,
+			},
+			RuleCellFrom::One(cell) => Constraint::Set {
+				cells: vec![*cell],
+				void: false,
+			},
+			RuleCellFrom::BoundaryVoid => Constraint::Set {
+				cells: Vec::new(),
+				void: true,
+			},
+			RuleCellFrom::Group(group_id) => match groups.get(*group_id) {
+				Some(group) => Constraint::Set {
+					cells: group.placeable_cells().collect(),
+					void: group.contains(None),
+				},
+				None => Constraint::Set {
+					cells: Vec::new(),
+					void: false,
+				},
+			},
+		}
+	}
+
+	/// no position satisfies it
+	fn is_empty(&self) -> bool {
+		matches!(self, Constraint::Set { cells, void: false } if cells.is_empty())
+	}
+
+	fn intersects(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Constraint::Any, _) | (_, Constraint::Any) => true,
+			(
+				Constraint::Set { cells, void },
+				Constraint::Set {
+					cells: other_cells,
+					void: other_void,
+				},
+			) => (*void && *other_void) || cells.iter().any(|c| other_cells.contains(c)),
+		}
+	}
+
+	fn is_subset_of(&self, other: &Self) -> bool {
+		match (self, other) {
+			(_, Constraint::Any) => true,
+			(Constraint::Any, _) => false,
+			(
+				Constraint::Set { cells, void },
+				Constraint::Set {
+					cells: other_cells,
+					void: other_void,
+				},
+			) => (!void || *other_void) && cells.iter().all(|c| other_cells.contains(c)),
+		}
+	}
+}
+
+fn constraint_at(rule: &SubRule, x: usize, y: usize, groups: &[CellGroup]) -> Constraint {
+	Constraint::new(&rule.get(x, y).0, groups)
+}
+
+/// whether both patterns can match with their top-left corners at the same position
+fn can_overlap(a: &SubRule, b: &SubRule, groups: &[CellGroup]) -> bool {
+	for x in 0..a.width.min(b.width) {
+		for y in 0..a.height.min(b.height) {
+			if !constraint_at(a, x, y, groups).intersects(&constraint_at(b, x, y, groups)) {
+				return false;
+			}
+		}
+	}
+	true
+}
+
+/// whether `general` matches everywhere `specific` does
+fn subsumes(general: &SubRule, specific: &SubRule, groups: &[CellGroup]) -> bool {
+	for x in 0..general.width.max(specific.width) {
+		for y in 0..general.height.max(specific.height) {
+			// SubRule::get returns Any outside the rule bounds
+			let g = constraint_at(general, x, y, groups);
+			let s = constraint_at(specific, x, y, groups);
+			if !s.is_subset_of(&g) {
+				return false;
+			}
+		}
+	}
+	true
+}
+
+fn conflicting_outputs(a: &SubRule, b: &SubRule) -> Vec<(usize, usize)> {
+	let mut cells = Vec::new();
+	for x in 0..a.width.min(b.width) {
+		for y in 0..a.height.min(b.height) {
+			let out_a = a.get(x, y).1;
+			let out_b = b.get(x, y).1;
+			if out_a != RuleCellTo::None && out_b != RuleCellTo::None && out_a != out_b {
+				cells.push((x, y));
+			}
+		}
+	}
+	cells
+}
+
+/// compares the base patterns of every pair of rules
+pub fn find_overlaps(dish: &Dish) -> Vec<Overlap> {
+	let groups = &dish.groups;
+	let mut overlaps = Vec::new();
+	for (i, rule_a) in dish.rules.iter().enumerate() {
+		for (j, rule_b) in dish.rules.iter().enumerate().skip(i + 1) {
+			let a = &rule_a.base;
+			let b = &rule_b.base;
+			if !can_overlap(a, b, groups) {
+				continue;
+			}
+			let a_in_b = subsumes(b, a, groups);
+			let b_in_a = subsumes(a, b, groups);
+			if a_in_b && b_in_a {
+				overlaps.push(Overlap {
+					rule_a: i,
+					rule_b: j,
+					kind: OverlapKind::Identical,
+				});
+			} else if a_in_b {
+				overlaps.push(Overlap {
+					rule_a: i,
+					rule_b: j,
+					kind: OverlapKind::Subsumed,
+				});
+			} else if b_in_a {
+				overlaps.push(Overlap {
+					rule_a: j,
+					rule_b: i,
+					kind: OverlapKind::Subsumed,
+				});
+			}
+			let conflicts = conflicting_outputs(a, b);
+			if !conflicts.is_empty() {
+				overlaps.push(Overlap {
+					rule_a: i,
+					rule_b: j,
+					kind: OverlapKind::ConflictingOutputs(conflicts),
+				});
+			}
+		}
+	}
+	overlaps
+}
+
+/// Rules with a from cell that no world position can satisfy, one entry for each such cell.
+/// Disabled rules are included.
+pub fn unsatisfiable_rules(dish: &Dish) -> Vec<(usize, Reason)> {
+	let mut found = Vec::new();
+	for (rule_index, rule) in dish.rules.iter().enumerate() {
+		let base = &rule.base;
+		for y in 0..base.height {
+			for x in 0..base.width {
+				let reason = match base.get(x, y).0 {
+					RuleCellFrom::One(cell)
+						if !cell.is_boundary() && cell.id() >= dish.types.len() =>
+					{
+						Reason::MissingCell { x, y, cell }
+					}
+					RuleCellFrom::Group(group) if group >= dish.groups.len() => {
+						Reason::MissingGroup { x, y, group }
+					}
+					RuleCellFrom::Group(group)
+						if Constraint::new(&RuleCellFrom::Group(group), &dish.groups)
+							.is_empty() =>
+					{
+						Reason::EmptyGroup { x, y, group }
+					}
+					_ => continue,
+				};
+				found.push((rule_index, reason));
+			}
+		}
+	}
+	found
+}
+
+/// Rules that can never change a cell, because every output leaves the cell alone or writes back
+/// what the input already requires there. Conservative: a rule is only listed when this holds for
+/// every cell the input accepts, see `rule_never_changes`. Disabled rules are included.
+pub fn no_op_rules(dish: &Dish) -> Vec<usize> {
+	dish.rules
+		.iter()
+		.enumerate()
+		.filter(|(_, rule)| rule_never_changes(rule, &dish.groups))
+		.map(|(index, _)| index)
+		.collect()
+}
+
+/// True if applying the rule never changes a cell: every output does nothing, copies the cell
+/// from its own position or writes back what the input already requires there, given the groups.
+/// Broader than `Rule::is_identity`, which only looks at the outputs.
+pub fn rule_never_changes(rule: &Rule, groups: &[CellGroup]) -> bool {
+	let base = &rule.base;
+	(0..base.height).all(|y| (0..base.width).all(|x| output_keeps_cell(base, x, y, groups)))
+}
+
+/// whether the output at x, y writes only cells the input at that position already holds
+fn output_keeps_cell(rule: &SubRule, x: usize, y: usize, groups: &[CellGroup]) -> bool {
+	let (from, to) = rule.get(x, y);
+	// the cells that can be here when the rule fires, positions outside the world are never written
+	let accepted = match Constraint::new(&from, groups) {
+		Constraint::Any => None,
+		Constraint::Set { cells, .. } => Some(cells),
+	};
+	// whether writing any of `written` keeps every accepted cell as it is
+	let writes_back = |written: &[Cell]| {
+		written.is_empty()
+			|| accepted
+				.as_ref()
+				.is_some_and(|accepted| accepted.iter().all(|a| written.iter().all(|c| c == a)))
+	};
+	match to {
+		RuleCellTo::None => true,
+		RuleCellTo::One(cell) => cell.is_boundary() || writes_back(&[cell]),
+		RuleCellTo::GroupRandom(g) | RuleCellTo::GroupRandomOther(g) => {
+			let cells: Vec<Cell> = groups
+				.get(g)
+				.map(|group| group.placeable_cells().collect())
+				.unwrap_or_default();
+			writes_back(&cells)
+		}
+		RuleCellTo::Copy(cx, cy) if (cx, cy) == (x, y) => true,
+		// out of bounds copies are reported by `Dish::validate` instead
+		RuleCellTo::Copy(cx, cy) if cx >= rule.width || cy >= rule.height => false,
+		RuleCellTo::Copy(cx, cy) => match Constraint::new(&rule.get(cx, cy).0, groups) {
+			Constraint::Set { cells, .. } => writes_back(&cells),
+			Constraint::Any => false,
+		},
+	}
+}
+
+/// convert an sRGB color to CIELAB (D65)
+fn srgb_to_lab([r, g, b]: [u8; 3]) -> [f32; 3] {
+	fn linear(c: u8) -> f32 {
+		let c = c as f32 / 255.;
+		if c <= 0.04045 {
+			c / 12.92
+		} else {
+			((c + 0.055) / 1.055).powf(2.4)
+		}
+	}
+	fn f(t: f32) -> f32 {
+		if t > 0.008856 {
+			t.cbrt()
+		} else {
+			7.787 * t + 16. / 116.
+		}
+	}
+	let (r, g, b) = (linear(r), linear(g), linear(b));
+	let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+	let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+	let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+	let (fx, fy, fz) = (f(x), f(y), f(z));
+	[116. * fy - 16., 500. * (fx - fy), 200. * (fy - fz)]
+}
+
+/// perceptual distance between two colors (CIE76 delta E, ~2.3 is a just noticeable difference)
+pub fn color_distance(a: [u8; 3], b: [u8; 3]) -> f32 {
+	let a = srgb_to_lab(a);
+	let b = srgb_to_lab(b);
+	a.iter()
+		.zip(b)
+		.map(|(a, b)| (a - b).powi(2))
+		.sum::<f32>()
+		.sqrt()
+}
+
+/// pairs of cell types whose colors are closer than `threshold`
+pub fn color_collisions(types: &[CellData], threshold: f32) -> Vec<(usize, usize)> {
+	let mut collisions = Vec::new();
+	for (i, a) in types.iter().enumerate() {
+		for (j, b) in types.iter().enumerate().skip(i + 1) {
+			if color_distance(a.color, b.color) < threshold {
+				collisions.push((i, j));
+			}
+		}
+	}
+	collisions
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::dsl::parse_rule;
+
+	fn group(cells: &[u16]) -> CellGroup {
+		CellGroup {
+			cells: cells.iter().map(|&id| Cell(id)).collect(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn no_op_rules_follow_groups() {
+		let mut dish = Dish::new();
+		dish.rules.clear();
+		dish.groups = vec![group(&[1]), group(&[1, 2])];
+		for pattern in [
+			// writes back the cell the input requires
+			"1 -> 1\n0 -> c(0,1)",
+			// only the top cell stays the same
+			"1 -> 1\n0 -> 1",
+			// every member of group 0 is 1
+			"g0 -> 1",
+			"1 -> g0",
+			// 2 matches group 1 and would become 1
+			"g1 -> 1",
+			"1 -> g1",
+		] {
+			dish.rules.push(parse_rule(pattern).unwrap());
+		}
+		assert_eq!(no_op_rules(&dish), vec![0, 2, 3]);
+		for (i, rule) in dish.rules.iter().enumerate() {
+			assert_eq!(
+				rule_never_changes(rule, &dish.groups),
+				[0, 2, 3].contains(&i)
+			);
+		}
+	}
+
+	#[test]
+	fn unsatisfiable_rules_by_reason() {
+		let mut dish = Dish::new();
+		dish.rules.clear();
+		dish.groups = vec![
+			group(&[]),
+			group(&[1]),
+			CellGroup {
+				matches_out_of_bounds: true,
+				..Default::default()
+			},
+			group(&[Cell::BOUNDARY.0]),
+		];
+		for pattern in [
+			"1 -> 0\n0 -> 1",
+			"g0 -> 1",
+			// a missing output group doesn't stop the rule from matching
+			"g1 -> 0\n1 -> g5",
+			"* 7 -> _ 0",
+			"g9 -> 0",
+			// only match outside the world, but can still match
+			"g2 -> _\n0 -> 1",
+			"g3 -> _\n0 -> 1",
+		] {
+			dish.rules.push(parse_rule(pattern).unwrap());
+		}
+		assert_eq!(
+			unsatisfiable_rules(&dish),
+			[
+				(
+					1,
+					Reason::EmptyGroup {
+						x: 0,
+						y: 0,
+						group: 0
+					}
+				),
+				(
+					3,
+					Reason::MissingCell {
+						x: 1,
+						y: 0,
+						cell: Cell(7)
+					}
+				),
+				(
+					4,
+					Reason::MissingGroup {
+						x: 0,
+						y: 0,
+						group: 9
+					}
+				),
+			]
+		);
+	}
+}
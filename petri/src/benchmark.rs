@@ -0,0 +1,46 @@
+use std::{fmt::Write, time::Duration};
+
+use crate::Rule;
+
+/// where the time went during `Dish::run_profiled`, per-rule times are indexed by rule
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Profile {
+	/// testing the variants of the rule against the world while updating the cache
+	pub rule_match_time: Vec<Duration>,
+	/// writing the outputs of the rule, including failed attempts
+	pub rule_apply_time: Vec<Duration>,
+	/// all of `Dish::update_cache`, which includes `rule_match_time`
+	pub cache_update_time: Duration,
+	pub total_time: Duration,
+}
+
+impl Profile {
+	pub(crate) fn new(rule_count: usize) -> Self {
+		Self {
+			rule_match_time: vec![Duration::ZERO; rule_count],
+			rule_apply_time: vec![Duration::ZERO; rule_count],
+			..Self::default()
+		}
+	}
+
+	/// a plain text table of the rules, most expensive first
+	pub fn report(&self, rules: &[Rule]) -> String {
+		let mut order: Vec<usize> = (0..self.rule_match_time.len()).collect();
+		order
+			.sort_by_key(|&i| std::cmp::Reverse(self.rule_match_time[i] + self.rule_apply_time[i]));
+		let mut text = format!("{:<24} {:>12} {:>12}\n", "rule", "matching", "applying");
+		for i in order {
+			let name = rules.get(i).map_or("?", |r| &r.name);
+			let _ = writeln!(
+				text,
+				"{:<24} {:>12} {:>12}",
+				name,
+				format!("{:.2?}", self.rule_match_time[i]),
+				format!("{:.2?}", self.rule_apply_time[i]),
+			);
+		}
+		let _ = writeln!(text, "\ncache updates: {:.2?}", self.cache_update_time);
+		let _ = writeln!(text, "total: {:.2?}", self.total_time);
+		text
+	}
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Default, Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct Cell(pub u16);
+
+impl Cell {
+	/// pseudo-cell standing for positions outside the world.
+	/// it can be used in groups and `RuleCellFrom::One`, but is never written to the world
+	pub const BOUNDARY: Cell = Cell(u16::MAX);
+
+	pub fn id(&self) -> usize {
+		self.0 as usize
+	}
+
+	pub fn is_boundary(&self) -> bool {
+		*self == Cell::BOUNDARY
+	}
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CellGroup {
+	pub name: String,
+	/// whether positions outside the world count as members
+	pub matches_out_of_bounds: bool,
+	/// may include `Cell::BOUNDARY`, which only matches outside the world and is never placed
+	pub cells: Vec<Cell>,
+}
+
+impl CellGroup {
+	/// members that can be written to the world
+	pub fn placeable_cells(&self) -> impl Iterator<Item = Cell> + '_ {
+		self.cells.iter().copied().filter(|c| !c.is_boundary())
+	}
+
+	/// whether a world position holding `cell` (None outside the world) is in the group
+	pub fn contains(&self, cell: Option<Cell>) -> bool {
+		match cell {
+			Some(cell) => self.cells.contains(&cell),
+			None => self.matches_out_of_bounds || self.cells.contains(&Cell::BOUNDARY),
+		}
+	}
+}
+
+/// the fields of a group in the self-describing formats,
+/// which also accept the `void` and `match_out_of_bounds` fields of older saves
+#[derive(Deserialize)]
+struct CellGroupCompat {
+	name: String,
+	#[serde(default)]
+	matches_out_of_bounds: bool,
+	#[serde(default)]
+	match_out_of_bounds: Option<bool>,
+	#[serde(default)]
+	void: bool,
+	cells: Vec<Cell>,
+}
+
+/// the exact layout written by `Serialize`, for formats that don't store field names
+#[derive(Deserialize)]
+struct CellGroupFields {
+	name: String,
+	matches_out_of_bounds: bool,
+	cells: Vec<Cell>,
+}
+
+impl<'de> Deserialize<'de> for CellGroup {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		if deserializer.is_human_readable() {
+			let group = CellGroupCompat::deserialize(deserializer)?;
+			Ok(CellGroup {
+				name: group.name,
+				matches_out_of_bounds: group.matches_out_of_bounds
+					|| group.match_out_of_bounds.unwrap_or(group.void),
+				cells: group.cells,
+			})
+		} else {
+			let group = CellGroupFields::deserialize(deserializer)?;
+			Ok(CellGroup {
+				name: group.name,
+				matches_out_of_bounds: group.matches_out_of_bounds,
+				cells: group.cells,
+			})
+		}
+	}
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct CellData {
+	pub name: String,
+	pub color: [u8; 3],
+	#[serde(default)]
+	pub shape: CellShape,
+	/// drawn over the color so types can be told apart without it
+	#[serde(default)]
+	pub pattern: CellPattern,
+	/// pinned to the quick brush row in the editor, has no effect on the simulation
+	#[serde(default)]
+	pub favorite: bool,
+}
+
+/// how a cell type is drawn, has no effect on the simulation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CellShape {
+	#[default]
+	Square,
+	Circle,
+	Diamond,
+	Cross,
+}
+
+/// overlay drawn on top of a cell type's color, has no effect on the simulation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CellPattern {
+	#[default]
+	None,
+	/// two horizontal bands
+	Stripes,
+	/// lines from bottom left to top right
+	Diagonal,
+	/// one dot in each quarter
+	Dots,
+	/// both diagonals
+	Cross,
+}
+
+impl CellPattern {
+	pub const ALL: [CellPattern; 5] = [
+		CellPattern::None,
+		CellPattern::Stripes,
+		CellPattern::Diagonal,
+		CellPattern::Dots,
+		CellPattern::Cross,
+	];
+
+	/// whether pixel (x, y) of a cell `size` pixels wide is drawn in the overlay color
+	pub fn covers(&self, x: u32, y: u32, size: u32) -> bool {
+		let size = size.max(1);
+		let thickness = (size / 8).max(1);
+		match self {
+			CellPattern::None => false,
+			CellPattern::Stripes => (y * 4 / size) % 2 == 1,
+			CellPattern::Diagonal => (x + y) % (size / 2).max(2) < thickness,
+			CellPattern::Dots => {
+				let quarter = size as f32 / 4.;
+				let (dx, dy) = (x as f32 + 0.5, y as f32 + 0.5);
+				let offset = |d: f32| (d % (quarter * 2.)) - quarter;
+				offset(dx).hypot(offset(dy)) <= size as f32 / 8.
+			}
+			CellPattern::Cross => {
+				x.abs_diff(y) < thickness || (x + y).abs_diff(size - 1) < thickness
+			}
+		}
+	}
+}
+
+impl CellData {
+	pub fn new(name: &str, r: u8, g: u8, b: u8) -> Self {
+		Self {
+			name: name.to_owned(),
+			color: [r, g, b],
+			shape: CellShape::Square,
+			pattern: CellPattern::None,
+			favorite: false,
+		}
+	}
+
+	/// euclidean distance in rgb space, see `analyze::color_distance` for a perceptual one
+	pub fn color_distance(&self, other: &CellData) -> f32 {
+		rgb_distance(self.color, other.color)
+	}
+
+	/// perceived brightness of the color, from 0 to 1
+	pub fn luminance(&self) -> f32 {
+		let [r, g, b] = self.color.map(|c| c as f32 / 255.);
+		0.299 * r + 0.587 * g + 0.114 * b
+	}
+
+	pub fn is_dark(&self) -> bool {
+		self.luminance() < 0.5
+	}
+
+	/// the contrasting color blended a third of the way over the cell color, for `pattern`
+	pub fn pattern_color(&self) -> [u8; 3] {
+		let contrast = self.contrasting_text_color();
+		std::array::from_fn(|i| ((self.color[i] as u16 * 2 + contrast[i] as u16) / 3) as u8)
+	}
+
+	/// white on dark colors and black on light ones, for text drawn over the cell
+	pub fn contrasting_text_color(&self) -> [u8; 3] {
+		if self.is_dark() {
+			[255; 3]
+		} else {
+			[0; 3]
+		}
+	}
+}
+
+pub(crate) fn rgb_distance(a: [u8; 3], b: [u8; 3]) -> f32 {
+	a.iter()
+		.zip(b)
+		.map(|(&a, b)| (a as f32 - b as f32).powi(2))
+		.sum::<f32>()
+		.sqrt()
+}
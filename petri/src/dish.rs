@@ -0,0 +1,6028 @@
+use std::{
+	collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+	hash::{Hash, Hasher},
+	ops::{ControlFlow, Not},
+	sync::atomic::{AtomicU64, Ordering},
+	time::{Duration, Instant},
+};
+
+use rand::{distributions::WeightedIndex, prelude::*};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	benchmark::Profile,
+	cell::rgb_distance,
+	dynamic::{DynMatch, DynamicRule, WorldView, WorldWriter},
+	observer::{EarlyExitObserver, Observer},
+	world::{ChunkSummary, World},
+	Anchor, Cell, CellData, CellGroup, Edge, RegionMask, Rule, RuleCellFrom, RuleCellTo, Schedule,
+	SubRule, CHUNK_SIZE, MAX_RULE_NAME_LEN, TEXT_GRID_CHARS,
+};
+
+/// share of steps a cell has to repeat on for `Dish::find_oscillating_patterns`
+const OSCILLATION_THRESHOLD: f64 = 0.9;
+/// steps of unchanged entropy after which `Dish::time_to_fixpoint` gives up
+const FIXPOINT_PLATEAU: u64 = 100;
+/// entropy samples kept in `SimStats::step_entropy`
+const STEP_ENTROPY_HISTORY: usize = 1000;
+/// entropy samples kept in `SimStats::entropy`
+const ENTROPY_HISTORY: usize = 256;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Dish {
+	#[serde(skip)]
+	world: World,
+	pub rules: Vec<Rule>, // todo make read-only to ensure cache is updated
+	#[serde(default)]
+	pub types: Vec<CellData>,
+	pub groups: Vec<CellGroup>, // todo make read-only to ensure cache is updated
+	/// multiplies the success chance of every rule that doesn't ignore it
+	#[serde(default = "default_temperature")]
+	pub temperature: f32,
+	/// how `apply_one_match`, `step_phased` and `step_batch` choose among cached matches
+	#[serde(default)]
+	pub selection: SelectionStrategy,
+	/// cells excluded from simulation, row-major over the world. empty when nothing is frozen
+	#[serde(default)]
+	frozen: Vec<bool>,
+	#[serde(skip)]
+	cache: Vec<RuleCache>,
+	#[serde(skip)]
+	match_cache: Vec<usize>,
+	#[serde(skip)]
+	max_rule_width: usize,
+	#[serde(skip)]
+	max_rule_height: usize,
+	#[serde(skip)]
+	pub stats: SimStats,
+	#[serde(skip, default = "CountingRng::from_entropy")]
+	rng: CountingRng,
+	#[serde(skip)]
+	dynamic_rules: Vec<Box<dyn DynamicRule>>,
+	#[serde(skip)]
+	phase: u8,
+	#[serde(skip)]
+	cache_timing: CacheTiming,
+	#[serde(skip)]
+	counters: Counters,
+	#[serde(skip)]
+	pending_rebuild: Option<PendingRebuild>,
+	#[serde(skip)]
+	observers: Vec<Box<dyn Observer>>,
+	/// collects timings while `run_profiled` runs
+	#[serde(skip)]
+	profile: Option<Profile>,
+	/// steps run through `Dish::step`, which rule schedules are checked against
+	#[serde(default)]
+	step_count: u64,
+	/// upcoming (step, rule) schedule changes, latest first. None when the rules changed since they were collected
+	#[serde(skip)]
+	schedule_transitions: Option<Vec<(u64, usize)>>,
+	/// bumped by every change to what gets saved, see `edit_count`
+	#[serde(skip)]
+	edit_count: u64,
+	/// the world revision is kept by the world itself, see `revisions`
+	#[serde(skip)]
+	revisions: Revisions,
+}
+
+/// smallest cell size in pixels `Dish::render_world_rgb` draws cell patterns at
+const MIN_PATTERN_SCALE: u32 = 4;
+/// cell size in pixels used by `Dish::export_all_rules_as_svg`
+const SVG_CELL_SIZE: u32 = 24;
+
+fn default_temperature() -> f32 {
+	1.
+}
+
+/// Mutable iterator over the rules of a dish, each rule comes with a changed flag.
+/// When the iterator is dropped, every rule whose flag was set gets its variants regenerated
+/// and its cache updated, so the dish is consistent again as soon as the borrow ends.
+/// The items borrow the iterator, so loop with `while let Some(..) = rules.next_rule()`.
+pub struct RuleIterMut<'a> {
+	dish: &'a mut Dish,
+	next_index: usize,
+	changed: Vec<bool>,
+}
+
+impl RuleIterMut<'_> {
+	pub fn types(&self) -> &[CellData] {
+		&self.dish.types
+	}
+
+	pub fn groups(&self) -> &[CellGroup] {
+		&self.dish.groups
+	}
+
+	/// the next rule with its index and changed flag
+	pub fn next_rule(&mut self) -> Option<(usize, &mut Rule, &mut bool)> {
+		let index = self.next_index;
+		let rule = self.dish.rules.get_mut(index)?;
+		self.next_index += 1;
+		Some((index, rule, &mut self.changed[index]))
+	}
+}
+
+impl Drop for RuleIterMut<'_> {
+	fn drop(&mut self) {
+		for (rule_index, &changed) in self.changed.iter().enumerate() {
+			if changed {
+				self.dish.mark_changed(DishPart::Rules);
+				self.dish.rules[rule_index].generate_variants();
+				self.dish.update_cache_single_rule(rule_index);
+			}
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SimStats {
+	/// World entropy after each step of `Dish::step` or `Dish::simulate_with_observer_and_early_exit`,
+	/// oldest first. A whole batch or epoch counts as one step.
+	pub entropy: VecDeque<f64>,
+	/// `Dish::world_hash` at the last snapshot
+	pub world_hash: u64,
+	/// world entropy after each step of the last `Dish::time_to_fixpoint`, oldest first
+	pub step_entropy: VecDeque<f64>,
+	/// ring buffer of per-rule activity, newest last
+	activity: VecDeque<ActivitySample>,
+	/// world revision the last entropy was computed at
+	entropy_revision: u64,
+}
+
+/// how `Dish::time_to_fixpoint` ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FixpointResult {
+	/// nothing could match any more after this many steps
+	Reached { steps: u64 },
+	/// Still changing when entropy stayed the same for a while or the step limit ran out.
+	/// `period_detected` is set if the world was repeating while entropy stayed the same.
+	NotReached {
+		final_entropy: f64,
+		period_detected: Option<u64>,
+	},
+}
+
+/// per-rule counters over a fixed number of simulation steps
+#[derive(Debug, Default, Clone)]
+struct ActivitySample {
+	steps: u64,
+	fired: Vec<u64>,
+	matched: Vec<u64>,
+	/// times the rule fired without writing a single cell
+	fired_empty: Vec<u64>,
+	/// times the rule fired without changing a cell
+	fired_unchanged: Vec<u64>,
+	/// whether the rule had any cached matches when the sample was closed
+	cached: Vec<bool>,
+}
+
+/// cells written by one application of a rule
+#[derive(Debug, Clone, Copy)]
+struct Writes {
+	written: u64,
+	/// writes that replaced a different cell
+	changed: u64,
+}
+
+/// what `Dish::apply_match_traced` read and wrote
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApplyTrace {
+	pub rule: usize,
+	pub variant: usize,
+	/// where the variant origin was placed
+	pub origin: (isize, isize),
+	/// corner x, corner y, width and height, see `Dish::match_footprint`
+	pub footprint: (isize, isize, usize, usize),
+	/// the variant matched the world at `origin`
+	pub matched: bool,
+	/// false if it didn't match, the footprint has frozen cells or the failrate/temperature roll failed
+	pub applied: bool,
+	/// the footprint before applying, row-major, None outside the world
+	pub old_state: Vec<Option<Cell>>,
+	/// the footprint afterwards
+	pub new_state: Vec<Option<Cell>>,
+	/// every write in order as (x, y, old cell, new cell), including ones that wrote the same cell
+	pub writes: Vec<(isize, isize, Cell, Cell)>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RuleActivity {
+	/// number of times the rule was applied
+	pub fired: u64,
+	/// how many of those wrote no cells, for example because every output copied from outside the world
+	pub fired_empty: u64,
+	/// how many of those left every cell as it was, always the case for rules in `analyze::no_op_rules`
+	pub fired_unchanged: u64,
+	/// number of times the rule matched at the chosen location but was not applied
+	pub lost_lottery: u64,
+	/// the rule never had a match during the window
+	pub never_matched: bool,
+}
+
+impl SimStats {
+	const SAMPLE_STEPS: u64 = 1000;
+	const MAX_SAMPLES: usize = 100;
+
+	fn current_sample(&mut self) -> &mut ActivitySample {
+		if self.activity.is_empty() {
+			self.activity.push_back(ActivitySample::default());
+		}
+		self.activity.back_mut().unwrap()
+	}
+
+	/// Count one simulation step. `candidates` are the rules that matched at the chosen location,
+	/// `fired` the rule that was applied and what it wrote.
+	fn record_step(&mut self, candidates: &[usize], fired: Option<(usize, Writes)>) {
+		let sample = self.current_sample();
+		sample.steps += 1;
+		for &rule in candidates {
+			if rule >= sample.matched.len() {
+				sample.matched.resize(rule + 1, 0);
+			}
+			sample.matched[rule] += 1;
+		}
+		if let Some((rule, writes)) = fired {
+			if rule >= sample.fired.len() {
+				sample.fired.resize(rule + 1, 0);
+			}
+			sample.fired[rule] += 1;
+			if writes.written == 0 {
+				if rule >= sample.fired_empty.len() {
+					sample.fired_empty.resize(rule + 1, 0);
+				}
+				sample.fired_empty[rule] += 1;
+			}
+			if writes.changed == 0 {
+				if rule >= sample.fired_unchanged.len() {
+					sample.fired_unchanged.resize(rule + 1, 0);
+				}
+				sample.fired_unchanged[rule] += 1;
+			}
+		}
+	}
+
+	fn sample_full(&self) -> bool {
+		self.activity
+			.back()
+			.is_some_and(|s| s.steps >= Self::SAMPLE_STEPS)
+	}
+
+	fn close_sample(&mut self, cached: Vec<bool>) {
+		self.current_sample().cached = cached;
+		self.activity.push_back(ActivitySample::default());
+		if self.activity.len() > Self::MAX_SAMPLES {
+			self.activity.pop_front();
+		}
+	}
+}
+
+/// an inconsistency in the match cache, see `Dish::verify_cache_integrity`
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheError {
+	/// the same position is cached twice for one variant
+	DuplicateMatch {
+		cache_index: usize,
+		pos: (isize, isize),
+	},
+	/// the entry's rule or variant doesn't exist, or the rule is disabled
+	OrphanRule { cache_index: usize, rule: usize },
+	/// an earlier entry already holds this rule variant
+	DuplicateEntry {
+		cache_index: usize,
+		rule: usize,
+		variant: usize,
+	},
+}
+
+/// summary of the match cache, see `Dish::cache_stats`
+#[derive(Debug, Clone, Default)]
+pub struct CacheStats {
+	pub total_matches: usize,
+	/// (rule index, variant index, match count) for every cached variant
+	pub per_variant: Vec<(usize, usize, usize)>,
+	/// rough heap size of the cache in bytes
+	pub memory_estimate: usize,
+	pub last_rebuild: Option<Instant>,
+	pub last_rebuild_duration: Option<Duration>,
+	/// `update_cache` calls since the last full rebuild
+	pub incremental_updates: u64,
+	/// total time spent in those calls
+	pub incremental_duration: Duration,
+}
+
+impl CacheStats {
+	/// match count for each rule, summed over its variants
+	pub fn per_rule(&self, rule_count: usize) -> Vec<usize> {
+		let mut counts = vec![0; rule_count];
+		for &(rule, _, matches) in &self.per_variant {
+			if let Some(count) = counts.get_mut(rule) {
+				*count += matches;
+			}
+		}
+		counts
+	}
+}
+
+#[derive(Debug, Default)]
+struct CacheTiming {
+	last_rebuild: Option<Instant>,
+	last_rebuild_duration: Option<Duration>,
+	incremental_updates: u64,
+	incremental_duration: Duration,
+}
+
+/// work done by the simulation since the last `Dish::reset_frame_counters`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameCounters {
+	/// rule variants tested against the world at one position
+	pub matches_examined: u64,
+	/// cached matches checked for overlap with a changed area
+	pub cache_entries_scanned: u64,
+	/// cells written by rules
+	pub cells_written: u64,
+	/// values drawn from the dish rng
+	pub rng_draws: u64,
+}
+
+/// atomics so the matching code can count through `&self`
+#[derive(Debug, Default)]
+struct Counters {
+	matches_examined: AtomicU64,
+	cache_entries_scanned: AtomicU64,
+	cells_written: AtomicU64,
+}
+
+/// the dish rng, counting how many values are drawn from it
+#[derive(Debug, Clone)]
+struct CountingRng {
+	rng: StdRng,
+	draws: u64,
+}
+
+impl CountingRng {
+	fn from_entropy() -> Self {
+		Self::new(StdRng::from_entropy())
+	}
+
+	fn new(rng: StdRng) -> Self {
+		Self { rng, draws: 0 }
+	}
+}
+
+impl RngCore for CountingRng {
+	fn next_u32(&mut self) -> u32 {
+		self.draws += 1;
+		self.rng.next_u32()
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		self.draws += 1;
+		self.rng.next_u64()
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		self.draws += 1;
+		self.rng.fill_bytes(dest)
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+		self.draws += 1;
+		self.rng.try_fill_bytes(dest)
+	}
+}
+
+/// a match chosen by `Dish::pick_match`
+enum MatchPick {
+	Cached {
+		rule: usize,
+		variant: usize,
+		x: isize,
+		y: isize,
+	},
+	Dynamic(usize),
+}
+
+#[derive(Debug)]
+struct RuleCache {
+	rule: usize,
+	variant: usize,
+	matches: Vec<(isize, isize)>,
+	/// how many of `matches` are in each of the `Tiles`, for stratified selection
+	tile_counts: Vec<u32>,
+}
+
+impl RuleCache {
+	fn new(rule: usize, variant: usize, matches: Vec<(isize, isize)>, tiles: Tiles) -> Self {
+		let mut cache = Self {
+			rule,
+			variant,
+			matches,
+			tile_counts: Vec::new(),
+		};
+		cache.recount_tiles(tiles);
+		cache
+	}
+
+	fn recount_tiles(&mut self, tiles: Tiles) {
+		self.tile_counts = vec![0; tiles.count()];
+		for &pos in &self.matches {
+			self.tile_counts[tiles.of(pos)] += 1;
+		}
+	}
+
+	fn push(&mut self, pos: (isize, isize), tiles: Tiles) {
+		self.matches.push(pos);
+		self.tile_counts[tiles.of(pos)] += 1;
+	}
+
+	fn swap_remove(&mut self, index: usize, tiles: Tiles) {
+		let pos = self.matches.swap_remove(index);
+		self.tile_counts[tiles.of(pos)] -= 1;
+	}
+}
+
+/// the world split into `CHUNK_SIZE` squares, row-major
+#[derive(Debug, Clone, Copy)]
+struct Tiles {
+	width: usize,
+	height: usize,
+	columns: usize,
+}
+
+impl Tiles {
+	fn new(world: &World) -> Self {
+		let (width, height) = (world.width.max(1), world.height.max(1));
+		Self {
+			width,
+			height,
+			columns: width.div_ceil(CHUNK_SIZE),
+		}
+	}
+
+	fn count(&self) -> usize {
+		self.columns * self.height.div_ceil(CHUNK_SIZE)
+	}
+
+	/// the tile of a match position, matches overhanging the world belong to the tile at the nearest edge
+	fn of(&self, (x, y): (isize, isize)) -> usize {
+		let x = x.clamp(0, self.width as isize - 1) as usize / CHUNK_SIZE;
+		let y = y.clamp(0, self.height as isize - 1) as usize / CHUNK_SIZE;
+		y * self.columns + x
+	}
+}
+
+/// a cache rebuild spread over several `Dish::continue_cache_rebuild` calls
+#[derive(Debug)]
+struct PendingRebuild {
+	/// every (rule, variant) to scan, in order
+	variants: Vec<(usize, usize)>,
+	/// the new cache, one entry for each of `variants` scanned so far
+	cache: Vec<RuleCache>,
+	start: Instant,
+}
+
+/// a part of a dish with its own revision, see `Dish::revisions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DishPart {
+	Rules,
+	Groups,
+	Types,
+	World,
+}
+
+/// Counters that only go up, one per part of a dish.
+/// Keep a copy and compare it with `Dish::revisions` later to find out what changed in between.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Revisions {
+	pub rules: u64,
+	pub groups: u64,
+	pub types: u64,
+	pub world: u64,
+}
+
+impl Revisions {
+	pub fn get(&self, part: DishPart) -> u64 {
+		match part {
+			DishPart::Rules => self.rules,
+			DishPart::Groups => self.groups,
+			DishPart::Types => self.types,
+			DishPart::World => self.world,
+		}
+	}
+
+	fn get_mut(&mut self, part: DishPart) -> &mut u64 {
+		match part {
+			DishPart::Rules => &mut self.rules,
+			DishPart::Groups => &mut self.groups,
+			DishPart::Types => &mut self.types,
+			DishPart::World => &mut self.world,
+		}
+	}
+}
+
+/// how a match is chosen from the cache, see `Dish::selection`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SelectionStrategy {
+	/// A cached rule variant is chosen by its variant weight, however many matches it has,
+	/// then one of its matches with equal chance.
+	#[default]
+	Uniform,
+	/// Every `CHUNK_SIZE` tile holding a match is equally likely, then a rule variant by its weight
+	/// times its matches in that tile, then one of those matches. Sparse areas keep moving next to
+	/// dense ones. Each pick reads the tile counts of every cached variant but only scans the
+	/// matches of the chosen one.
+	Stratified,
+}
+
+/// how `Dish::step` advances the simulation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StepMode {
+	/// `try_one_location` per step
+	#[default]
+	Random,
+	/// `step_phased` per step
+	Phased,
+	/// one `step_batch` of all the steps
+	Batch,
+	/// one `step_epoch` per step
+	Epoch { epoch_size: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationWarning {
+	/// every variant of the rule is (Any, None) everywhere, so it can never change anything
+	TrivialRule { rule_index: usize },
+	/// a Copy output refers to a position outside the rule
+	OutOfBoundsCopy { rule_index: usize },
+	/// no output can ever write a cell, so firing the rule changes nothing.
+	/// Not reported for rules that are already `TrivialRule`
+	NeverWrites { rule_index: usize },
+	/// the rule has the same canonical pattern as an earlier rule, see `Dish::find_duplicate_rules`
+	DuplicateRule { rule_index: usize, original: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportError {
+	/// the world contains cell ids without a character
+	NotEnoughChars { needed: usize, got: usize },
+	/// there is no rule with this index
+	NoSuchRule { index: usize },
+}
+
+/// Changes to the world for a viewer that keeps its own copy, see `Dish::serialize_world_delta`.
+/// Cells are row-major.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WorldDelta {
+	/// the whole world, replacing whatever the viewer had
+	Full {
+		revision: u64,
+		width: usize,
+		height: usize,
+		cells: Vec<Cell>,
+	},
+	/// the chunks with changes, everything else is as it was at the requested revision
+	Tiles {
+		revision: u64,
+		width: usize,
+		height: usize,
+		tiles: Vec<WorldTile>,
+	},
+}
+
+/// a chunk of the world, clipped to the world edges
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldTile {
+	pub x: usize,
+	pub y: usize,
+	pub width: usize,
+	pub height: usize,
+	pub cells: Vec<Cell>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeltaError {
+	/// a `WorldDelta::Tiles` made for a world of another size
+	SizeMismatch {
+		world: (usize, usize),
+		delta: (usize, usize),
+	},
+	/// `tile` is None for the cells of a `WorldDelta::Full`
+	WrongCellCount {
+		tile: Option<usize>,
+		expected: usize,
+		got: usize,
+	},
+	TileOutOfBounds {
+		tile: usize,
+	},
+}
+
+/// The tunable settings of every rule and the temperature, without the patterns.
+/// See `Dish::capture_params`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParamSet {
+	pub temperature: f32,
+	pub rules: Vec<RuleParams>,
+}
+
+/// the settings of one rule in a `ParamSet`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleParams {
+	/// rules are matched by name, several rules with the same name are matched in order
+	pub name: String,
+	pub enabled: bool,
+	pub failrate: u8,
+	pub ignore_temperature: bool,
+	pub phase: u8,
+	pub schedule: Option<Schedule>,
+}
+
+impl RuleParams {
+	fn of(rule: &Rule) -> Self {
+		Self {
+			name: rule.name.clone(),
+			enabled: rule.enabled,
+			failrate: rule.failrate,
+			ignore_temperature: rule.ignore_temperature,
+			phase: rule.phase,
+			schedule: rule.schedule,
+		}
+	}
+
+	fn apply_to(&self, rule: &mut Rule) {
+		rule.enabled = self.enabled;
+		rule.failrate = self.failrate;
+		rule.ignore_temperature = self.ignore_temperature;
+		rule.phase = self.phase;
+		rule.schedule = self.schedule;
+	}
+}
+
+/// rules `Dish::apply_params` could not pair up by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamMismatch {
+	/// names of rules in the set with no rule left to match in the dish
+	pub missing_in_dish: Vec<String>,
+	/// indices of the rules in the dish that the set has no settings for
+	pub missing_in_set: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportError {
+	Empty,
+	/// rows must all be as long as the first one
+	RaggedRow {
+		row: usize,
+		expected: usize,
+		got: usize,
+	},
+	UnknownChar {
+		ch: char,
+		x: usize,
+		y: usize,
+	},
+	/// the text is not valid json, holds the parser message
+	Json(String),
+	/// `rule` is the index within the imported rules
+	InvalidCellRef {
+		rule: usize,
+		cell: Cell,
+	},
+	InvalidGroupRef {
+		rule: usize,
+		group: usize,
+	},
+	/// the rule pattern has no rows or no columns
+	EmptyPattern {
+		rule: usize,
+	},
+	/// the rule pattern holds a different number of cells than width * height
+	WrongCellCount {
+		rule: usize,
+		expected: usize,
+		got: usize,
+	},
+	/// a copy target outside the rule pattern
+	InvalidCopyRef {
+		rule: usize,
+		x: usize,
+		y: usize,
+	},
+	/// the imported world must be the same size as the current one
+	DimensionMismatch {
+		expected: (usize, usize),
+		got: (usize, usize),
+	},
+}
+
+/// the world alone, as written by `Dish::export_world_as_json_string`
+#[derive(Serialize, Deserialize)]
+struct WorldCells {
+	width: usize,
+	height: usize,
+	/// row-major
+	cells: Vec<Cell>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoveGroupError {
+	InvalidIndex,
+	/// the group is still referenced by this rule, see `Dish::remove_group_force`
+	UsedByRule {
+		rule_index: usize,
+	},
+}
+
+/// problems with rule names, see `Dish::validate_rule_names`
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleNameError {
+	EmptyName {
+		rule: usize,
+	},
+	/// longer than `MAX_RULE_NAME_LEN` chars
+	TooLong {
+		rule: usize,
+		len: usize,
+	},
+	/// same name as an earlier rule, ignoring case
+	DuplicateName {
+		rule_a: usize,
+		rule_b: usize,
+	},
+	/// control characters such as newlines, `Rule::sanitize_name` replaces them
+	InvalidChar {
+		rule: usize,
+		ch: char,
+	},
+}
+
+impl RuleNameError {
+	/// the rule with the problem, the later one for duplicates
+	pub fn rule_index(&self) -> usize {
+		match self {
+			RuleNameError::EmptyName { rule }
+			| RuleNameError::TooLong { rule, .. }
+			| RuleNameError::InvalidChar { rule, .. }
+			| RuleNameError::DuplicateName { rule_b: rule, .. } => *rule,
+		}
+	}
+}
+
+impl ValidationWarning {
+	pub fn rule_index(&self) -> usize {
+		match self {
+			ValidationWarning::TrivialRule { rule_index }
+			| ValidationWarning::OutOfBoundsCopy { rule_index }
+			| ValidationWarning::NeverWrites { rule_index }
+			| ValidationWarning::DuplicateRule { rule_index, .. } => *rule_index,
+		}
+	}
+}
+
+impl Default for Dish {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Dish {
+	pub fn new() -> Self {
+		let mut default_rules = vec![
+			Rule {
+				enabled: true,
+				name: "fall".into(),
+				base: SubRule {
+					width: 1,
+					height: 2,
+					origin_x: 0,
+					origin_y: 0,
+					contents: vec![
+						(RuleCellFrom::One(Cell(1)), RuleCellTo::One(Cell(0))),
+						(RuleCellFrom::One(Cell(0)), RuleCellTo::One(Cell(1))),
+					],
+				},
+				..Rule::new()
+			},
+			Rule {
+				enabled: true,
+				name: "slide".into(),
+				base: SubRule {
+					width: 2,
+					height: 2,
+					origin_x: 0,
+					origin_y: 0,
+					contents: vec![
+						(RuleCellFrom::One(Cell(1)), RuleCellTo::One(Cell(0))),
+						(RuleCellFrom::Any, RuleCellTo::None),
+						(RuleCellFrom::One(Cell(1)), RuleCellTo::None),
+						(RuleCellFrom::One(Cell(0)), RuleCellTo::One(Cell(1))),
+					],
+				},
+				flip_x: true,
+				..Rule::new()
+			},
+		];
+
+		for rule in &mut default_rules {
+			rule.generate_variants()
+		}
+
+		let mut new = Self::from_parts(
+			World::new(CHUNK_SIZE, CHUNK_SIZE).with_random_ones(),
+			default_rules,
+			vec![
+				CellData::new("air", 0, 0, 0),
+				CellData::new("pink_sand", 255, 147, 219),
+			],
+			vec![CellGroup {
+				name: "empty".into(),
+				matches_out_of_bounds: true,
+				cells: vec![Cell(0)],
+			}],
+		);
+		new.update_all_rules();
+		new.add_source(Cell(1), Edge::Top, 0.1);
+		new.add_sink(RuleCellFrom::One(Cell(1)), Edge::Bottom);
+		new.edit_count = 0;
+		new
+	}
+
+	/// a dish with default settings and an empty cache, call `update_all_rules` before simulating
+	fn from_parts(
+		world: World,
+		rules: Vec<Rule>,
+		types: Vec<CellData>,
+		groups: Vec<CellGroup>,
+	) -> Self {
+		Self {
+			world,
+			rules,
+			types,
+			groups,
+			temperature: 1.,
+			selection: SelectionStrategy::Uniform,
+			frozen: Vec::new(),
+			cache: Vec::new(),
+			match_cache: Vec::new(),
+			max_rule_height: 1,
+			max_rule_width: 1,
+			stats: SimStats::default(),
+			rng: CountingRng::from_entropy(),
+			dynamic_rules: Vec::new(),
+			phase: 0,
+			cache_timing: CacheTiming::default(),
+			counters: Counters::default(),
+			pending_rebuild: None,
+			observers: Vec::new(),
+			profile: None,
+			step_count: 0,
+			schedule_transitions: None,
+			edit_count: 0,
+			revisions: Revisions::default(),
+		}
+	}
+
+	/// Counts changes to the rules, types, groups and other saved settings made through the dish.
+	/// Compare it with the value at the last save to tell whether there are unsaved changes.
+	/// The world is not saved, so painting and simulating don't count.
+	pub fn edit_count(&self) -> u64 {
+		self.edit_count
+	}
+
+	/// count a change made directly through the public fields, see `edit_count`
+	pub fn mark_edited(&mut self) {
+		self.edit_count += 1;
+	}
+
+	/// The current revision of every part of the dish.
+	///
+	/// - rules: adding, removing, importing or editing rules through the dish, `update_all_rules`,
+	///   and removing a group or permuting cell ids, which rewrite rules
+	/// - groups: adding, editing and removing groups, and permuting cell ids
+	/// - types: adding types, including placeholders, and permuting cell ids
+	/// - world: every cell that changes, whether painted, filled, imported or simulated,
+	///   and resizing or replacing the world
+	///
+	/// Changes made directly through the public fields are only seen after `mark_changed`.
+	pub fn revisions(&self) -> Revisions {
+		Revisions {
+			world: self.world.revision,
+			..self.revisions
+		}
+	}
+
+	/// like `revisions`, for one part
+	pub fn revision(&self, part: DishPart) -> u64 {
+		self.revisions().get(part)
+	}
+
+	/// Count a change to one part made directly through the public fields.
+	/// Also counts as an edit for `edit_count`, except for the world, which is not saved.
+	pub fn mark_changed(&mut self, part: DishPart) {
+		match part {
+			DishPart::World => self.world.revision += 1,
+			part => {
+				*self.revisions.get_mut(part) += 1;
+				self.edit_count += 1;
+			}
+		}
+	}
+
+	/// non-fatal problems with the current rule set
+	pub fn validate(&self) -> Vec<ValidationWarning> {
+		let mut warnings = Vec::new();
+		for (rule_index, rule) in self.rules.iter().enumerate() {
+			if rule.all_variants_trivial() {
+				warnings.push(ValidationWarning::TrivialRule { rule_index });
+			} else if rule.never_writes(&self.groups) {
+				warnings.push(ValidationWarning::NeverWrites { rule_index });
+			}
+			if rule.has_out_of_bounds_copy() {
+				warnings.push(ValidationWarning::OutOfBoundsCopy { rule_index });
+			}
+		}
+		let mut duplicates = self.find_duplicate_rules();
+		// only report the first rule each one duplicates
+		duplicates.dedup_by_key(|&mut (_, rule_index)| rule_index);
+		for (original, rule_index) in duplicates {
+			warnings.push(ValidationWarning::DuplicateRule {
+				rule_index,
+				original,
+			});
+		}
+		warnings
+	}
+
+	/// Empty, too long and duplicate names, and names with control characters.
+	/// Duplicates are only reported against the first earlier rule with the name.
+	pub fn validate_rule_names(&self) -> Vec<RuleNameError> {
+		let mut errors = Vec::new();
+		let lowercase: Vec<String> = self.rules.iter().map(|r| r.name.to_lowercase()).collect();
+		for (rule, name) in self.rules.iter().map(|r| &r.name).enumerate() {
+			if name.trim().is_empty() {
+				errors.push(RuleNameError::EmptyName { rule });
+				continue;
+			}
+			let len = name.chars().count();
+			if len > MAX_RULE_NAME_LEN {
+				errors.push(RuleNameError::TooLong { rule, len });
+			}
+			if let Some(ch) = name.chars().find(|c| c.is_control()) {
+				errors.push(RuleNameError::InvalidChar { rule, ch });
+			}
+			if let Some(rule_a) = lowercase[..rule].iter().position(|n| *n == lowercase[rule]) {
+				errors.push(RuleNameError::DuplicateName {
+					rule_a,
+					rule_b: rule,
+				});
+			}
+		}
+		errors
+	}
+
+	/// see `Rule::sanitize_name`
+	pub fn normalize_rule_name(&mut self, rule_index: usize) {
+		let rule = &mut self.rules[rule_index];
+		let old = rule.name.clone();
+		rule.sanitize_name();
+		if rule.name != old {
+			self.mark_changed(DishPart::Rules);
+		}
+	}
+
+	/// the first rule with this name, ignoring case
+	pub fn find_rule_by_name(&self, name: &str) -> Option<usize> {
+		let name = name.to_lowercase();
+		self.rules
+			.iter()
+			.position(|r| r.name.to_lowercase() == name)
+	}
+
+	/// Pairs (a, b) with a < b of rules whose base patterns are equal after `SubRule::canonicalize`.
+	/// Only the patterns are compared, not symmetry, failrate or other settings.
+	/// Sorted by b, then a.
+	pub fn find_duplicate_rules(&self) -> Vec<(usize, usize)> {
+		let patterns: Vec<SubRule> = self.rules.iter().map(Rule::canonical_base).collect();
+		let mut pairs = Vec::new();
+		for (b, pattern) in patterns.iter().enumerate() {
+			for (a, earlier) in patterns[..b].iter().enumerate() {
+				if earlier == pattern {
+					pairs.push((a, b));
+				}
+			}
+		}
+		pairs
+	}
+
+	/// Remove every rule that duplicates an earlier one, keeping the first occurrence.
+	/// Returns the indices the removed rules had, in ascending order.
+	pub fn remove_duplicate_rules(&mut self) -> Vec<usize> {
+		let mut removed: Vec<usize> = self
+			.find_duplicate_rules()
+			.into_iter()
+			.map(|(_, b)| b)
+			.collect();
+		removed.dedup();
+		if removed.is_empty() {
+			return removed;
+		}
+		self.mark_changed(DishPart::Rules);
+		for &i in removed.iter().rev() {
+			self.rules.remove(i);
+		}
+		self.update_all_rules();
+		removed
+	}
+
+	/// Shannon entropy (in bits) of the cell type distribution in the world
+	pub fn world_entropy(&self) -> f64 {
+		let mut counts = vec![0usize; self.types.len()];
+		let mut chunks = 0;
+		for summary in self.world.chunk_summaries() {
+			chunks += 1;
+			for (cell, n) in summary.present() {
+				if cell.id() >= counts.len() {
+					counts.resize(cell.id() + 1, 0);
+				}
+				counts[cell.id()] += n as usize;
+			}
+		}
+		if let Some(empty) = counts.first_mut() {
+			// the parts of edge chunks hanging over the world count as empty
+			*empty -= chunks * CHUNK_SIZE * CHUNK_SIZE - self.world.width * self.world.height;
+		}
+		let total = (self.world.width * self.world.height) as f64;
+		counts
+			.iter()
+			.filter(|&&c| c > 0)
+			.map(|&c| {
+				let p = c as f64 / total;
+				-p * p.log2()
+			})
+			.sum()
+	}
+
+	/// world entropy scaled to [0, 1] by the maximum possible entropy for the defined types
+	pub fn world_entropy_normalized(&self) -> f64 {
+		if self.types.len() < 2 {
+			return 0.;
+		}
+		(self.world_entropy() / (self.types.len() as f64).log2()).min(1.)
+	}
+
+	/// record the current world hash in `stats`, the entropy is recorded after every step
+	pub fn snapshot_stats(&mut self) {
+		self.stats.world_hash = self.world_hash();
+	}
+
+	/// add the entropy after a step to `stats`, only recomputed if the world changed since the last one
+	fn record_step_entropy(&mut self) {
+		let history = &self.stats.entropy;
+		let entropy = match history.back() {
+			Some(&last) if self.stats.entropy_revision == self.world.revision => last,
+			_ => self.world_entropy(),
+		};
+		self.stats.entropy_revision = self.world.revision;
+		let history = &mut self.stats.entropy;
+		history.push_back(entropy);
+		if history.len() > ENTROPY_HISTORY {
+			history.pop_front();
+		}
+	}
+
+	/// Fast hash of the world size and cells, for noticing changes without comparing worlds.
+	/// Not stable across versions or platforms, so don't save it.
+	pub fn world_hash(&self) -> u64 {
+		self.world.content_hash()
+	}
+
+	/// hash of the rules as they would be saved
+	pub fn rules_hash(&self) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		serde_json::to_string(&self.rules)
+			.unwrap_or_default()
+			.hash(&mut hasher);
+		hasher.finish()
+	}
+
+	/// (`world_hash`, `rules_hash`)
+	pub fn full_hash(&self) -> (u64, u64) {
+		(self.world_hash(), self.rules_hash())
+	}
+
+	/// whether the world differs from when `world_hash` returned `prev_hash`
+	pub fn has_changed_since(&self, prev_hash: u64) -> bool {
+		self.world_hash() != prev_hash
+	}
+
+	/// Simulate every dish for `steps` calls of `apply_one_match` on a thread pool.
+	/// Dish `i` is seeded with `seed_base + i`, so the results match running them one after another.
+	pub fn run_parallel_worlds(worlds: Vec<Dish>, steps: usize, seed_base: u64) -> Vec<Dish> {
+		worlds
+			.into_par_iter()
+			.enumerate()
+			.map(|(i, mut dish)| {
+				dish.seed(seed_base.wrapping_add(i as u64));
+				for _ in 0..steps {
+					dish.apply_one_match();
+				}
+				dish
+			})
+			.collect()
+	}
+
+	/// Like `run_parallel_worlds`, also returning stats after every step.
+	/// Each holds only the entropy and hash of the world after its step, see `current_stats`.
+	/// They are only recomputed after steps that changed a cell.
+	pub fn run_parallel_worlds_with_stats(
+		worlds: Vec<Dish>,
+		steps: usize,
+		seed_base: u64,
+	) -> Vec<(Dish, Vec<SimStats>)> {
+		worlds
+			.into_par_iter()
+			.enumerate()
+			.map(|(i, mut dish)| {
+				dish.seed(seed_base.wrapping_add(i as u64));
+				let mut stats = Vec::with_capacity(steps);
+				let mut current = dish.current_stats();
+				let mut revision = dish.world.revision;
+				for _ in 0..steps {
+					dish.apply_one_match();
+					if dish.world.revision != revision {
+						revision = dish.world.revision;
+						current = dish.current_stats();
+					}
+					stats.push(current.clone());
+				}
+				(dish, stats)
+			})
+			.collect()
+	}
+
+	/// stats of the world as it is now, with no history: one entropy value and the world hash
+	pub fn current_stats(&self) -> SimStats {
+		SimStats {
+			entropy: VecDeque::from([self.world_entropy()]),
+			world_hash: self.world_hash(),
+			..Default::default()
+		}
+	}
+
+	/// Per-rule activity over roughly the last `window` steps, indexed like `rules`.
+	/// The window is rounded up to whole samples and limited to the recorded history.
+	pub fn rule_activity_report(&self, window: u64) -> Vec<RuleActivity> {
+		let mut report = vec![
+			RuleActivity {
+				never_matched: true,
+				..Default::default()
+			};
+			self.rules.len()
+		];
+		let mut matched = vec![0; self.rules.len()];
+		let mut steps = 0;
+		for sample in self.stats.activity.iter().rev() {
+			if steps >= window {
+				break;
+			}
+			steps += sample.steps;
+			for (i, activity) in report.iter_mut().enumerate() {
+				let fired = sample.fired.get(i).copied().unwrap_or_default();
+				let m = sample.matched.get(i).copied().unwrap_or_default();
+				activity.fired += fired;
+				activity.fired_empty += sample.fired_empty.get(i).copied().unwrap_or_default();
+				activity.fired_unchanged +=
+					sample.fired_unchanged.get(i).copied().unwrap_or_default();
+				matched[i] += m;
+				if m > 0 || sample.cached.get(i).copied().unwrap_or_default() {
+					activity.never_matched = false;
+				}
+			}
+		}
+		for (activity, matched) in report.iter_mut().zip(matched) {
+			activity.lost_lottery = matched.saturating_sub(activity.fired);
+		}
+		report
+	}
+
+	fn record_step(&mut self, candidates: &[usize], fired: Option<(usize, Writes)>) {
+		self.stats.record_step(candidates, fired);
+		if self.stats.sample_full() {
+			let mut cached = vec![false; self.rules.len()];
+			for c in &self.cache {
+				if !c.matches.is_empty() {
+					cached[c.rule] = true;
+				}
+			}
+			self.stats.close_sample(cached);
+		}
+	}
+
+	/// difference in entropy between the last two steps
+	pub fn entropy_change_rate(&self) -> f64 {
+		let mut newest = self.stats.entropy.iter().rev();
+		match (newest.next(), newest.next()) {
+			(Some(last), Some(prev)) => last - prev,
+			_ => 0.,
+		}
+	}
+
+	#[deprecated(note = "use cache_stats")]
+	pub fn cache_count(&self) -> usize {
+		self.cache.iter().map(|c| c.matches.len()).sum()
+	}
+
+	#[deprecated(note = "use cache_stats")]
+	pub fn dbg_cache(&self) {
+		dbg!(&self.cache);
+	}
+
+	/// set every cell, only updating the cache where something changed
+	pub fn fill(&mut self, cell: Cell) {
+		if let Some((x, y, width, height)) = self.world.fill(cell) {
+			self.update_cache(x, y, width, height);
+		}
+	}
+
+	pub fn update_all_rules(&mut self) {
+		self.revisions.rules += 1;
+		self.max_rule_height = 1;
+		self.max_rule_width = 1;
+		for rule in &mut self.rules {
+			rule.generate_variants();
+			self.max_rule_height = self.max_rule_height.max(rule.max_height());
+			self.max_rule_width = self.max_rule_width.max(rule.max_width());
+		}
+		if self.frozen.len() != self.world.width * self.world.height {
+			// loaded mask does not fit the current world
+			self.frozen.clear();
+		}
+		self.rebuild_cache();
+	}
+
+	/// restrict where a rule can match, None allows the whole world
+	pub fn set_rule_region(&mut self, rule_index: usize, mask: Option<RegionMask>) {
+		self.mark_changed(DishPart::Rules);
+		self.rules[rule_index].region_mask = mask;
+		self.update_cache_single_rule(rule_index);
+	}
+
+	/// the cell type with the color closest to `color` in rgb space, None if there are no types
+	pub fn nearest_cell_type(&self, color: [u8; 3]) -> Option<Cell> {
+		self.types
+			.iter()
+			.map(|t| rgb_distance(t.color, color))
+			.enumerate()
+			.min_by(|(_, a), (_, b)| a.total_cmp(b))
+			.map(|(i, _)| Cell(i as u16))
+	}
+
+	/// like `nearest_cell_type`, but None if even the closest type is further than `max_dist`
+	pub fn nearest_cell_type_below_distance(&self, color: [u8; 3], max_dist: f32) -> Option<Cell> {
+		self.nearest_cell_type(color)
+			.filter(|cell| rgb_distance(self.types[cell.id()].color, color) <= max_dist)
+	}
+
+	/// Exchange the ids of two cell types, updating the world, rules and groups to match.
+	/// The simulation behaves the same afterwards, only the encoding changes.
+	pub fn swap_cell_ids(&mut self, a: Cell, b: Cell) {
+		let mut new_ids: Vec<usize> = (0..self.types.len()).collect();
+		new_ids.swap(a.id(), b.id());
+		self.permute_cell_ids(&new_ids);
+	}
+
+	/// sort the cell types by name, keeping the dish equivalent like `swap_cell_ids`
+	pub fn normalize_cell_ordering(&mut self) {
+		let mut order: Vec<usize> = (0..self.types.len()).collect();
+		order.sort_by(|&a, &b| self.types[a].name.cmp(&self.types[b].name));
+		let mut new_ids = vec![0; order.len()];
+		for (new_id, &old_id) in order.iter().enumerate() {
+			new_ids[old_id] = new_id;
+		}
+		self.permute_cell_ids(&new_ids);
+	}
+
+	/// move cell type `i` to `new_ids[i]`, which must be a permutation of the type indices
+	fn permute_cell_ids(&mut self, new_ids: &[usize]) {
+		self.mark_changed(DishPart::Types);
+		self.mark_changed(DishPart::Groups);
+		let remap = |cell: Cell| {
+			if cell.is_boundary() {
+				cell
+			} else {
+				new_ids.get(cell.id()).map_or(cell, |&id| Cell(id as u16))
+			}
+		};
+		let mut types = vec![CellData::default(); self.types.len()];
+		for (old_id, data) in self.types.drain(..).enumerate() {
+			types[new_ids[old_id]] = data;
+		}
+		self.types = types;
+		for y in 0..self.world.height {
+			for x in 0..self.world.width {
+				let cell = self.get_cell(x, y).unwrap();
+				self.world.set_cell(x as isize, y as isize, remap(cell));
+			}
+		}
+		for rule in &mut self.rules {
+			for (from, to) in &mut rule.base.contents {
+				if let RuleCellFrom::One(cell) = from {
+					*cell = remap(*cell);
+				}
+				if let RuleCellTo::One(cell) = to {
+					*cell = remap(*cell);
+				}
+			}
+		}
+		for group in &mut self.groups {
+			for cell in &mut group.cells {
+				*cell = remap(*cell);
+			}
+		}
+		self.update_all_rules();
+	}
+
+	/// Add a rule that emits `cell` into empty cells along an edge, see `Rule::source`.
+	/// Returns the index of the new rule.
+	pub fn add_source(&mut self, cell: Cell, edge: Edge, rate: f32) -> usize {
+		let name = self.types.get(cell.id()).map_or("?", |t| &t.name);
+		let rule = Rule {
+			name: format!("{name} source ({edge:?})").to_lowercase(),
+			..Rule::source(cell, edge, rate)
+		};
+		self.push_rule(rule)
+	}
+
+	/// Add a rule that clears cells matching `from` along an edge, see `Rule::sink`.
+	/// Returns the index of the new rule.
+	pub fn add_sink(&mut self, from: RuleCellFrom, edge: Edge) -> usize {
+		let name = match &from {
+			RuleCellFrom::One(cell) => self.types.get(cell.id()).map_or("?", |t| &t.name),
+			RuleCellFrom::Group(group) => self.groups.get(*group).map_or("?", |g| &g.name),
+			RuleCellFrom::Any | RuleCellFrom::BoundaryVoid => "any",
+		};
+		let rule = Rule {
+			name: format!("{name} sink ({edge:?})").to_lowercase(),
+			..Rule::sink(from, edge)
+		};
+		self.push_rule(rule)
+	}
+
+	fn push_rule(&mut self, mut rule: Rule) -> usize {
+		rule.generate_variants();
+		self.max_rule_height = self.max_rule_height.max(rule.max_height());
+		self.max_rule_width = self.max_rule_width.max(rule.max_width());
+		self.rules.push(rule);
+		self.cache_last_added_rule();
+		self.rules.len() - 1
+	}
+
+	/// give rules that are unnamed or still called "new rule" a name from `Rule::auto_name`
+	pub fn auto_name_rules(&mut self) {
+		self.mark_changed(DishPart::Rules);
+		for rule in &mut self.rules {
+			if rule.name.is_empty() || rule.name == "new rule" {
+				rule.name = rule.auto_name(&self.types);
+			}
+		}
+	}
+
+	/// returns the index of the new group
+	pub fn add_group(&mut self, group: CellGroup) -> usize {
+		self.mark_changed(DishPart::Groups);
+		self.groups.push(group);
+		self.groups.len() - 1
+	}
+
+	/// indices of the rules that match or output a group
+	pub fn rules_using_group(&self, group: usize) -> Vec<usize> {
+		(0..self.rules.len())
+			.filter(|&i| self.rules[i].uses_group(group))
+			.collect()
+	}
+
+	/// replace the members of a group and update the cache of the rules using it
+	pub fn set_group_cells(&mut self, group: usize, cells: Vec<Cell>) {
+		self.mark_changed(DishPart::Groups);
+		self.groups[group].cells = cells;
+		self.recache_group_users(group);
+	}
+
+	pub fn set_group_out_of_bounds(&mut self, group: usize, matches: bool) {
+		self.mark_changed(DishPart::Groups);
+		self.groups[group].matches_out_of_bounds = matches;
+		self.recache_group_users(group);
+	}
+
+	fn recache_group_users(&mut self, group: usize) {
+		for rule_index in self.rules_using_group(group) {
+			self.update_cache_single_rule(rule_index);
+		}
+	}
+
+	/// remove a group that no rule refers to
+	pub fn remove_group(&mut self, index: usize) -> Result<(), RemoveGroupError> {
+		if index >= self.groups.len() {
+			return Err(RemoveGroupError::InvalidIndex);
+		}
+		if let Some(rule_index) = self.rules.iter().position(|r| r.uses_group(index)) {
+			return Err(RemoveGroupError::UsedByRule { rule_index });
+		}
+		self.remove_group_force(index);
+		Ok(())
+	}
+
+	/// Remove a group even if rules use it.
+	/// Rule inputs matching the group become Any and random outputs from it become no change.
+	pub fn remove_group_force(&mut self, index: usize) {
+		if index >= self.groups.len() {
+			return;
+		}
+		self.mark_changed(DishPart::Groups);
+		self.groups.remove(index);
+		for rule in &mut self.rules {
+			for (from, to) in &mut rule.base.contents {
+				if let RuleCellFrom::Group(g) = from {
+					if *g == index {
+						*from = RuleCellFrom::Any;
+					} else if *g > index {
+						*g -= 1;
+					}
+				}
+				if let RuleCellTo::GroupRandom(g) | RuleCellTo::GroupRandomOther(g) = to {
+					if *g == index {
+						*to = RuleCellTo::None;
+					} else if *g > index {
+						*g -= 1;
+					}
+				}
+			}
+		}
+		self.update_all_rules();
+	}
+
+	/// add a group containing the cells of both groups, returns its index
+	/// or `InvalidIndex` if either group doesn't exist
+	pub fn merge_groups(
+		&mut self,
+		a: usize,
+		b: usize,
+		name: &str,
+	) -> Result<usize, RemoveGroupError> {
+		let (Some(group_a), Some(group_b)) = (self.groups.get(a), self.groups.get(b)) else {
+			return Err(RemoveGroupError::InvalidIndex);
+		};
+		let mut cells = group_a.cells.clone();
+		for &cell in &group_b.cells {
+			if !cells.contains(&cell) {
+				cells.push(cell);
+			}
+		}
+		let matches_out_of_bounds = group_a.matches_out_of_bounds || group_b.matches_out_of_bounds;
+		Ok(self.add_group(CellGroup {
+			name: name.into(),
+			matches_out_of_bounds,
+			cells,
+		}))
+	}
+
+	/// iterate over the rules with a changed flag each, see `RuleIterMut`
+	pub fn iter_rules_mut(&mut self) -> RuleIterMut<'_> {
+		let changed = vec![false; self.rules.len()];
+		RuleIterMut {
+			dish: self,
+			next_index: 0,
+			changed,
+		}
+	}
+
+	/// Modify one rule and update its cache if the variants changed.
+	/// Returns whether anything relevant to matching changed.
+	pub fn with_rule_mut(&mut self, index: usize, f: impl FnOnce(&mut Rule)) -> bool {
+		let Some(rule) = self.rules.get_mut(index) else {
+			return false;
+		};
+		self.revisions.rules += 1;
+		self.edit_count += 1;
+		let before = rule.variant_hash();
+		f(rule);
+		rule.generate_variants();
+		let changed = rule.variant_hash() != before;
+		if changed {
+			self.update_cache_single_rule(index);
+		}
+		changed
+	}
+
+	/// Modify several rules at once, then update the cache in one pass for those whose matching changed.
+	/// Indices that are out of range or repeated are skipped.
+	pub fn edit_rules(&mut self, indices: &[usize], f: impl Fn(&mut Rule)) {
+		let mut indices = indices.to_vec();
+		indices.sort_unstable();
+		indices.dedup();
+		let mut changed = Vec::new();
+		for index in indices {
+			let Some(rule) = self.rules.get_mut(index) else {
+				continue;
+			};
+			self.revisions.rules += 1;
+			self.edit_count += 1;
+			let before = rule.variant_hash();
+			f(rule);
+			rule.generate_variants();
+			if rule.variant_hash() != before {
+				changed.push(index);
+			}
+		}
+		self.recache_rules(&changed);
+	}
+
+	/// the tunable settings of every rule, see `apply_params`
+	pub fn capture_params(&self) -> ParamSet {
+		ParamSet {
+			temperature: self.temperature,
+			rules: self.rules.iter().map(RuleParams::of).collect(),
+		}
+	}
+
+	/// Set the temperature and the settings of every rule the set has a rule of the same name for.
+	/// The patterns are left alone, so only rules that were switched on or off or got a new schedule are rescanned.
+	/// Rules that could not be paired up are reported, the others are still applied.
+	pub fn apply_params(&mut self, params: &ParamSet) -> Result<(), ParamMismatch> {
+		let mut by_name: HashMap<&str, VecDeque<&RuleParams>> = HashMap::new();
+		for rule in &params.rules {
+			by_name.entry(&rule.name).or_default().push_back(rule);
+		}
+		let mut missing_in_set = Vec::new();
+		let mut changed = Vec::new();
+		for (index, rule) in self.rules.iter_mut().enumerate() {
+			let Some(rule_params) = by_name
+				.get_mut(rule.name.as_str())
+				.and_then(|q| q.pop_front())
+			else {
+				missing_in_set.push(index);
+				continue;
+			};
+			let before = (rule.enabled, rule.schedule);
+			rule_params.apply_to(rule);
+			if (rule.enabled, rule.schedule) != before {
+				changed.push(index);
+			}
+		}
+		let mut missing_in_dish: Vec<String> = by_name
+			.into_values()
+			.flatten()
+			.map(|rule| rule.name.clone())
+			.collect();
+		missing_in_dish.sort();
+		self.temperature = params.temperature;
+		self.mark_changed(DishPart::Rules);
+		self.recache_rules(&changed);
+		if missing_in_dish.is_empty() && missing_in_set.is_empty() {
+			Ok(())
+		} else {
+			Err(ParamMismatch {
+				missing_in_dish,
+				missing_in_set,
+			})
+		}
+	}
+
+	/// replace the cache of several rules in one pass, for changes that don't need the variants regenerated
+	fn recache_rules(&mut self, changed: &[usize]) {
+		if changed.is_empty() {
+			return;
+		}
+		self.restart_pending_rebuild();
+		self.schedule_transitions = None;
+		let mut is_changed = vec![false; self.rules.len()];
+		for &index in changed {
+			is_changed[index] = true;
+		}
+		self.cache
+			.retain(|c| !is_changed.get(c.rule).is_some_and(|&b| b));
+		for &index in changed {
+			self.add_cache_single_rule(index);
+		}
+		self.update_match_cache();
+	}
+
+	/// run after any rule modification
+	pub fn update_cache_single_rule(&mut self, rule_index: usize) {
+		self.restart_pending_rebuild();
+		self.schedule_transitions = None;
+		// remove old cache for this rule, since the variants may have changed
+		self.cache.retain(|c| c.rule != rule_index);
+		self.add_cache_single_rule(rule_index);
+		self.update_match_cache();
+	}
+
+	/// world rect (corner x, corner y, width, height) a cached match of this variant at `at` covers
+	pub fn match_footprint(
+		&self,
+		rule: usize,
+		variant: usize,
+		at: (isize, isize),
+	) -> (isize, isize, usize, usize) {
+		self.rules[rule].variants[variant].footprint(at.0, at.1)
+	}
+
+	/// run after adding a rule
+	pub fn cache_last_added_rule(&mut self) {
+		if self.rules.is_empty() {
+			return;
+		}
+		self.mark_changed(DishPart::Rules);
+		let index = self.rules.len() - 1;
+		self.update_cache_single_rule(index);
+	}
+
+	/// remove a rule and rebuild the cache, since the indices of the rules after it change
+	pub fn remove_rule(&mut self, index: usize) -> Rule {
+		self.mark_changed(DishPart::Rules);
+		let rule = self.rules.remove(index);
+		self.rebuild_cache();
+		rule
+	}
+
+	/// add a cell type, returns its cell
+	pub fn add_type(&mut self, data: CellData) -> Cell {
+		self.mark_changed(DishPart::Types);
+		self.types.push(data);
+		Cell((self.types.len() - 1) as u16)
+	}
+
+	pub fn types_len(&self) -> usize {
+		self.types.len()
+	}
+
+	/// add air and sand when there are no types at all, so the world has something to draw with.
+	/// returns the added cells, empty if any types existed
+	pub fn add_default_cell_types(&mut self) -> Vec<Cell> {
+		if !self.types.is_empty() {
+			return Vec::new();
+		}
+		vec![
+			self.add_type(CellData::new("air", 0, 0, 0)),
+			self.add_type(CellData::new("sand", 255, 200, 0)),
+		]
+	}
+
+	/// add unnamed gray types until there are at least `n`. returns how many were added
+	pub fn ensure_minimum_types(&mut self, n: usize) -> usize {
+		let added = n.saturating_sub(self.types.len());
+		for _ in 0..added {
+			self.add_type(CellData::new("", 128, 128, 128));
+		}
+		added
+	}
+
+	/// remove every type without touching rules, groups or the world, leaving their ids dangling
+	pub fn remove_all_types(&mut self) {
+		if !self.types.is_empty() {
+			self.types.clear();
+			self.mark_changed(DishPart::Types);
+		}
+	}
+
+	fn add_cache_single_rule(&mut self, rule_index: usize) {
+		let full_rule = &self.rules[rule_index];
+		if !full_rule.is_active_at(self.step_count) {
+			return;
+		}
+		for variant_index in 0..full_rule.variants.len() {
+			let cache = self.scan_variant(rule_index, variant_index);
+			self.cache.push(cache);
+		}
+	}
+
+	/// every match of one variant in the world
+	fn scan_variant(&self, rule_index: usize, variant_index: usize) -> RuleCache {
+		let mut matches = Vec::new();
+
+		let rule = &self.rules[rule_index].variants[variant_index];
+		if let Some((dx, dy, cells)) = rule.required_cells(&self.groups) {
+			// every match has one of `cells` at (dx, dy) from its corner, inside the world,
+			// so only positions with one of them there, in chunks holding any, need to be tested
+			let (origin_x, origin_y) = rule.origin();
+			let (width, height) = (self.world.width, self.world.height);
+			for chunk_x in (0..width).step_by(CHUNK_SIZE) {
+				for chunk_y in (0..height).step_by(CHUNK_SIZE) {
+					let summary = self.world.summary_at(chunk_x, chunk_y);
+					if !cells.iter().any(|&c| summary.contains(c)) {
+						continue;
+					}
+					for x in chunk_x..(chunk_x + CHUNK_SIZE).min(width) {
+						for y in chunk_y..(chunk_y + CHUNK_SIZE).min(height) {
+							let cell = self.world.get_cell(x as isize, y as isize);
+							if !cell.is_some_and(|c| cells.contains(&c)) {
+								continue;
+							}
+							let px = (x + origin_x) as isize - dx as isize;
+							let py = (y + origin_y) as isize - dy as isize;
+							if self.variant_matches_at(rule_index, variant_index, px, py) {
+								matches.push((px, py));
+							}
+						}
+					}
+				}
+			}
+		} else {
+			let border_x = rule.width as isize - 1;
+			let border_y = rule.height as isize - 1;
+			// scan positions where the rule overhangs the world, so BoundaryVoid and void groups can match
+			for px in -border_x..(self.world.width as isize + border_x) {
+				for py in -border_y..(self.world.height as isize + border_y) {
+					if self.variant_matches_at(rule_index, variant_index, px, py) {
+						matches.push((px, py));
+					}
+				}
+			}
+		}
+		RuleCache::new(rule_index, variant_index, matches, Tiles::new(&self.world))
+	}
+
+	/// the (rule, variant) pairs a full rebuild scans
+	fn cached_variants(&self) -> Vec<(usize, usize)> {
+		self.rules
+			.iter()
+			.enumerate()
+			.filter(|(_, rule)| rule.is_active_at(self.step_count))
+			.flat_map(|(i, rule)| (0..rule.variants.len()).map(move |v| (i, v)))
+			.collect()
+	}
+
+	/// whether a variant could be applied with its origin at this position right now
+	fn variant_matches_at(
+		&self,
+		rule_index: usize,
+		variant_index: usize,
+		x: isize,
+		y: isize,
+	) -> bool {
+		let full_rule = &self.rules[rule_index];
+		if !full_rule.allows_position(x, y) {
+			return false;
+		}
+		let rule = &full_rule.variants[variant_index];
+		let (corner_x, corner_y, width, height) = rule.footprint(x, y);
+		self.counters
+			.matches_examined
+			.fetch_add(1, Ordering::Relaxed);
+		self.world
+			.subrule_matches(corner_x, corner_y, rule, &self.groups)
+			&& !self
+				.world
+				.rect_in_mask(&self.frozen, corner_x, corner_y, width, height)
+	}
+
+	pub fn frame_counters(&self) -> FrameCounters {
+		FrameCounters {
+			matches_examined: self.counters.matches_examined.load(Ordering::Relaxed),
+			cache_entries_scanned: self.counters.cache_entries_scanned.load(Ordering::Relaxed),
+			cells_written: self.counters.cells_written.load(Ordering::Relaxed),
+			rng_draws: self.rng.draws,
+		}
+	}
+
+	/// start counting from zero, hosts call this once per frame
+	pub fn reset_frame_counters(&mut self) {
+		self.counters = Counters::default();
+		self.rng.draws = 0;
+	}
+
+	/// whether a cache entry belongs to an existing variant of an enabled rule
+	fn cache_entry_live(&self, entry: &RuleCache) -> bool {
+		self.rules.get(entry.rule).is_some_and(|rule| {
+			rule.is_active_at(self.step_count) && entry.variant < rule.variants.len()
+		})
+	}
+
+	/// Drop entries of missing or disabled rules, merge entries for the same rule variant and free spare capacity.
+	/// Empty entries of live variants are kept, since `update_cache` only adds matches to existing entries.
+	pub fn compact_cache(&mut self) {
+		let mut cache: Vec<RuleCache> = Vec::new();
+		for entry in std::mem::take(&mut self.cache) {
+			if !self.cache_entry_live(&entry) {
+				continue;
+			}
+			match cache
+				.iter_mut()
+				.find(|c| (c.rule, c.variant) == (entry.rule, entry.variant))
+			{
+				Some(existing) => existing.matches.extend(entry.matches),
+				None => cache.push(entry),
+			}
+		}
+		let tiles = Tiles::new(&self.world);
+		for entry in &mut cache {
+			entry.matches.sort_unstable();
+			entry.matches.dedup();
+			entry.matches.shrink_to_fit();
+			entry.recount_tiles(tiles);
+		}
+		cache.shrink_to_fit();
+		self.cache = cache;
+		self.update_match_cache();
+	}
+
+	/// every inconsistency in the match cache, empty when it is sound
+	pub fn verify_cache_integrity(&self) -> Vec<CacheError> {
+		let mut errors = Vec::new();
+		let mut seen_variants = HashSet::new();
+		for (cache_index, entry) in self.cache.iter().enumerate() {
+			if !self.cache_entry_live(entry) {
+				errors.push(CacheError::OrphanRule {
+					cache_index,
+					rule: entry.rule,
+				});
+			}
+			if !seen_variants.insert((entry.rule, entry.variant)) {
+				errors.push(CacheError::DuplicateEntry {
+					cache_index,
+					rule: entry.rule,
+					variant: entry.variant,
+				});
+			}
+			let mut seen_positions = HashSet::new();
+			for &pos in &entry.matches {
+				if !seen_positions.insert(pos) {
+					errors.push(CacheError::DuplicateMatch { cache_index, pos });
+				}
+			}
+		}
+		errors
+	}
+
+	pub fn cache_stats(&self) -> CacheStats {
+		let per_variant: Vec<_> = self
+			.cache
+			.iter()
+			.map(|c| (c.rule, c.variant, c.matches.len()))
+			.collect();
+		let memory_estimate = self.cache.capacity() * std::mem::size_of::<RuleCache>()
+			+ self
+				.cache
+				.iter()
+				.map(|c| {
+					c.matches.capacity() * std::mem::size_of::<(isize, isize)>()
+						+ c.tile_counts.capacity() * std::mem::size_of::<u32>()
+				})
+				.sum::<usize>()
+			+ self.match_cache.capacity() * std::mem::size_of::<usize>();
+		CacheStats {
+			total_matches: per_variant.iter().map(|&(_, _, n)| n).sum(),
+			per_variant,
+			memory_estimate,
+			last_rebuild: self.cache_timing.last_rebuild,
+			last_rebuild_duration: self.cache_timing.last_rebuild_duration,
+			incremental_updates: self.cache_timing.incremental_updates,
+			incremental_duration: self.cache_timing.incremental_duration,
+		}
+	}
+
+	/// the cells in a rect as rows, with `Cell(0)` where it is outside the world
+	pub fn get_region(&self, x: usize, y: usize, w: usize, h: usize) -> Vec<Vec<Cell>> {
+		(y..y + h)
+			.map(|py| {
+				(x..x + w)
+					.map(|px| self.get_cell(px, py).unwrap_or(Cell(0)))
+					.collect()
+			})
+			.collect()
+	}
+
+	/// `get_region` as a single row-major vec
+	pub fn get_region_flat(&self, x: usize, y: usize, w: usize, h: usize) -> Vec<Cell> {
+		self.get_region(x, y, w, h).concat()
+	}
+
+	/// Write rows of cells starting at (x, y), such as ones from `get_region`.
+	/// Parts outside the world are skipped, and the cache is updated once for the whole rect.
+	pub fn set_region(&mut self, x: usize, y: usize, cells: &[Vec<Cell>]) {
+		let width = cells.iter().map(Vec::len).max().unwrap_or(0);
+		for (dy, row) in cells.iter().enumerate() {
+			for (dx, &cell) in row.iter().enumerate() {
+				if x + dx < self.world.width && y + dy < self.world.height {
+					self.set_cell(x + dx, y + dy, cell);
+				}
+			}
+		}
+		self.update_cache(x as isize, y as isize, width, cells.len());
+	}
+
+	/// whether the pattern matches the world with its top-left corner at (x, y)
+	pub fn region_matches_pattern(&self, x: usize, y: usize, pattern: &SubRule) -> bool {
+		pattern.matches_at(x as isize, y as isize, &self.groups, |x, y| {
+			self.world.get_cell(x, y)
+		})
+	}
+
+	/// print every rule with `Rule::grid_display`
+	pub fn print_all_rules(&self) {
+		for rule in &self.rules {
+			println!("{}\n{}", rule.name, rule.grid_display(&self.types));
+		}
+	}
+
+	/// World cells with an id past the end of `types` and how many there are of each, sorted by id.
+	/// Happens when a world is loaded with fewer types than it was saved with.
+	pub fn undefined_cells(&self) -> Vec<(Cell, usize)> {
+		let defined = self.types.len();
+		let mut counts: Vec<usize> = Vec::new();
+		for summary in self.world.chunk_summaries() {
+			for (cell, n) in summary.present() {
+				if let Some(i) = cell.id().checked_sub(defined) {
+					if counts.len() <= i {
+						counts.resize(i + 1, 0);
+					}
+					counts[i] += n as usize;
+				}
+			}
+		}
+		counts
+			.into_iter()
+			.enumerate()
+			.filter(|&(_, n)| n > 0)
+			.map(|(i, n)| (Cell((defined + i) as u16), n))
+			.collect()
+	}
+
+	/// for each rule, the fraction of positions where it currently matches, see `Rule::coverage_fraction`
+	pub fn rule_coverage_fractions(&self) -> Vec<f64> {
+		self.rules
+			.iter()
+			.map(|rule| rule.coverage_fraction(&self.world_view(), &self.groups))
+			.collect()
+	}
+
+	/// rough number of applications per step of a rule, None if the index is out of range
+	pub fn expected_applications_per_step(&self, rule: usize) -> Option<f64> {
+		let rule = self.rules.get(rule)?;
+		Some(rule.expected_applications_per_step(&self.world_view(), &self.groups))
+	}
+
+	/// read-only access to the world, for `Rule::coverage_fraction` and similar
+	pub fn world_view(&self) -> WorldView<'_> {
+		WorldView { world: &self.world }
+	}
+
+	/// how many cells of this type the world holds, counted from the chunk summaries
+	pub fn count_cells(&self, cell: Cell) -> usize {
+		let mut chunks = 0;
+		let mut count = 0;
+		for summary in self.world.chunk_summaries() {
+			chunks += 1;
+			count += summary.count(cell) as usize;
+		}
+		if cell == Cell(0) {
+			// the parts of edge chunks hanging over the world count as empty
+			count -= chunks * CHUNK_SIZE * CHUNK_SIZE - self.world.width * self.world.height;
+		}
+		count
+	}
+
+	/// turn every `from` in the world into `to`, returns how many cells changed
+	pub fn replace_all(&mut self, from: Cell, to: Cell) -> usize {
+		self.conditional_fill(&RuleCellFrom::One(from), to)
+	}
+
+	/// Replace every cell that fits `condition` with `replacement`, returns how many were replaced.
+	pub fn conditional_fill(&mut self, condition: &RuleCellFrom, replacement: Cell) -> usize {
+		let (width, height) = (self.world.width, self.world.height);
+		self.conditional_fill_region(condition, replacement, 0, 0, width, height)
+	}
+
+	/// like `conditional_fill`, only within the rect, which is clipped to the world
+	pub fn conditional_fill_region(
+		&mut self,
+		condition: &RuleCellFrom,
+		replacement: Cell,
+		x: usize,
+		y: usize,
+		w: usize,
+		h: usize,
+	) -> usize {
+		let mut replaced = 0;
+		for y in y..(y + h).min(self.world.height) {
+			for x in x..(x + w).min(self.world.width) {
+				let cell = self.get_cell(x, y);
+				if cell != Some(replacement) && condition.matches(cell, &self.groups) {
+					self.set_cell(x, y, replacement);
+					replaced += 1;
+				}
+			}
+		}
+		if replaced > 0 {
+			self.rebuild_cache();
+		}
+		replaced
+	}
+
+	/// Add magenta "undefined" types until `cell` has one, so undefined cells can be seen and edited.
+	/// Returns how many types were added.
+	pub fn add_placeholder_types(&mut self, cell: Cell) -> usize {
+		let old_len = self.types.len();
+		while !cell.is_boundary() && self.types.len() <= cell.id() {
+			let name = format!("undefined #{}", self.types.len());
+			self.types.push(CellData::new(&name, 255, 0, 255));
+		}
+		if self.types.len() > old_len {
+			self.mark_changed(DishPart::Types);
+		}
+		self.types.len() - old_len
+	}
+
+	/// number of cells of each type inside the rect, indexed by cell id. parts outside the world are ignored
+	pub fn census_region(&self, x: usize, y: usize, w: usize, h: usize) -> Vec<u64> {
+		let mut counts = vec![0; self.types.len()];
+		for py in y..(y + h).min(self.world.height) {
+			for px in x..(x + w).min(self.world.width) {
+				let cell = self.get_cell(px, py).unwrap();
+				if let Some(count) = counts.get_mut(cell.id()) {
+					*count += 1;
+				}
+			}
+		}
+		counts
+	}
+
+	/// cached matches whose footprint overlaps the rect
+	pub fn match_count_region(&self, rect: RegionMask) -> usize {
+		self.region_matches(rect).count()
+	}
+
+	/// cached matches whose footprint overlaps the rect, as (rule, variant, origin)
+	pub fn matches_in_region(&self, rect: RegionMask) -> Vec<(usize, usize, (isize, isize))> {
+		self.region_matches(rect).collect()
+	}
+
+	fn region_matches(
+		&self,
+		rect: RegionMask,
+	) -> impl Iterator<Item = (usize, usize, (isize, isize))> + '_ {
+		self.cache.iter().flat_map(move |c| {
+			let v = &self.rules[c.rule].variants[c.variant];
+			c.matches.iter().filter_map(move |&(x, y)| {
+				let (cx, cy, width, height) = v.footprint(x, y);
+				let overlaps = cx < (rect.x + rect.w) as isize
+					&& cy < (rect.y + rect.h) as isize
+					&& cx.saturating_add_unsigned(width) > rect.x as isize
+					&& cy.saturating_add_unsigned(height) > rect.y as isize;
+				overlaps.then_some((c.rule, c.variant, (x, y)))
+			})
+		})
+	}
+
+	/// Apply the rule at every position where its footprint fits inside the rect, in random order.
+	/// Each match is checked again just before applying, since earlier ones may have changed the world,
+	/// and failrate and temperature apply like in a step. Disabled rules are applied too.
+	/// Returns how many matches were applied.
+	pub fn apply_rule_on_region(
+		&mut self,
+		rule_index: usize,
+		x: usize,
+		y: usize,
+		w: usize,
+		h: usize,
+		rng: &mut impl Rng,
+	) -> usize {
+		let applied = self.apply_rule_in_rect(rule_index, x, y, w, h, rng);
+		self.update_cache(x as isize, y as isize, w, h);
+		applied
+	}
+
+	/// `apply_rule_on_region` for every enabled rule that is active at the current step, in order
+	pub fn apply_all_rules_on_region(
+		&mut self,
+		x: usize,
+		y: usize,
+		w: usize,
+		h: usize,
+		rng: &mut impl Rng,
+	) -> usize {
+		let mut applied = 0;
+		for rule_index in 0..self.rules.len() {
+			if self.rules[rule_index].is_active_at(self.step_count) {
+				applied += self.apply_rule_in_rect(rule_index, x, y, w, h, rng);
+			}
+		}
+		self.update_cache(x as isize, y as isize, w, h);
+		applied
+	}
+
+	/// `apply_rule_on_region` without updating the cache
+	fn apply_rule_in_rect(
+		&mut self,
+		rule_index: usize,
+		x: usize,
+		y: usize,
+		w: usize,
+		h: usize,
+		rng: &mut impl Rng,
+	) -> usize {
+		let right = x.saturating_add(w).min(self.world.width);
+		let bottom = y.saturating_add(h).min(self.world.height);
+		let mut candidates = Vec::new();
+		for (variant_index, variant) in self.rules[rule_index].variants.iter().enumerate() {
+			for corner_y in y..(bottom + 1).saturating_sub(variant.height) {
+				for corner_x in x..(right + 1).saturating_sub(variant.width) {
+					let origin_x = (corner_x + variant.origin_x) as isize;
+					let origin_y = (corner_y + variant.origin_y) as isize;
+					candidates.push((variant_index, origin_x, origin_y));
+				}
+			}
+		}
+		candidates.shuffle(rng);
+		let mut applied = 0;
+		for (variant_index, origin_x, origin_y) in candidates {
+			if self.variant_matches_at(rule_index, variant_index, origin_x, origin_y)
+				&& self
+					.apply_rule(origin_x, origin_y, rule_index, variant_index)
+					.is_some()
+			{
+				applied += 1;
+			}
+		}
+		applied
+	}
+
+	pub fn rebuild_cache(&mut self) {
+		self.rebuild_cache_with_progress(|_, _| ());
+	}
+
+	/// Like `rebuild_cache`, calling `progress(done, total)` after each rule variant is scanned.
+	/// Cancels a rebuild started by `start_cache_rebuild`.
+	pub fn rebuild_cache_with_progress(&mut self, mut progress: impl FnMut(usize, usize)) {
+		let start = Instant::now();
+		self.pending_rebuild = None;
+		self.schedule_transitions = None;
+		self.cache.clear();
+		let variants = self.cached_variants();
+		for (done, &(rule_index, variant_index)) in variants.iter().enumerate() {
+			let cache = self.scan_variant(rule_index, variant_index);
+			self.cache.push(cache);
+			progress(done + 1, variants.len());
+		}
+		self.finish_rebuild(start);
+	}
+
+	/// Begin rebuilding the cache in parts with `continue_cache_rebuild`.
+	/// The old cache stays in place until the new one is done, and is restarted by cache updates
+	/// in the meantime, so the simulation should not be stepped until it finishes.
+	pub fn start_cache_rebuild(&mut self) {
+		self.schedule_transitions = None;
+		self.pending_rebuild = Some(PendingRebuild {
+			variants: self.cached_variants(),
+			cache: Vec::new(),
+			start: Instant::now(),
+		});
+	}
+
+	/// Scan rule variants for a started rebuild until `budget` runs out, at least one per call.
+	/// Returns `(done, total)` variants while it is unfinished, and None once the cache is replaced.
+	pub fn continue_cache_rebuild(&mut self, budget: Duration) -> Option<(usize, usize)> {
+		let call_start = Instant::now();
+		let mut pending = self.pending_rebuild.take()?;
+		while let Some(&(rule_index, variant_index)) = pending.variants.get(pending.cache.len()) {
+			pending
+				.cache
+				.push(self.scan_variant(rule_index, variant_index));
+			if call_start.elapsed() >= budget && pending.cache.len() < pending.variants.len() {
+				let progress = (pending.cache.len(), pending.variants.len());
+				self.pending_rebuild = Some(pending);
+				return Some(progress);
+			}
+		}
+		self.cache = pending.cache;
+		self.finish_rebuild(pending.start);
+		None
+	}
+
+	/// the world or rules changed, so a started rebuild has to scan everything again
+	fn restart_pending_rebuild(&mut self) {
+		if self.pending_rebuild.is_none() {
+			return;
+		}
+		let variants = self.cached_variants();
+		if let Some(pending) = &mut self.pending_rebuild {
+			pending.variants = variants;
+			pending.cache.clear();
+		}
+	}
+
+	fn finish_rebuild(&mut self, start: Instant) {
+		self.update_match_cache();
+		self.cache_timing = CacheTiming {
+			last_rebuild: Some(start),
+			last_rebuild_duration: Some(start.elapsed()),
+			..Default::default()
+		};
+	}
+
+	pub fn update_cache(&mut self, cx: isize, cy: isize, width: usize, height: usize) {
+		self.restart_pending_rebuild();
+		let start = Instant::now();
+		fn overlap(
+			(x1, y1, w1, h1): (isize, isize, usize, usize),
+			(x2, y2, w2, h2): (isize, isize, usize, usize),
+		) -> bool {
+			x2 < x1.saturating_add_unsigned(w1)
+				&& x1 < x2.saturating_add_unsigned(w2)
+				&& y2 < y1.saturating_add_unsigned(h1)
+				&& y1 < y2.saturating_add_unsigned(h2)
+		}
+		let edited_rect = (cx, cy, width, height);
+		let tiles = Tiles::new(&self.world);
+
+		for cache in &mut self.cache {
+			let full_rule = &self.rules[cache.rule];
+			let rule = &full_rule.variants[cache.variant];
+			let rule_width = rule.width;
+			let rule_height = rule.height;
+
+			// discard all overlapping matches
+			*self.counters.cache_entries_scanned.get_mut() += cache.matches.len() as u64;
+			let mut i = 0;
+			while i < cache.matches.len() {
+				let (x, y) = cache.matches[i];
+				let match_rect = rule.footprint(x, y);
+				if overlap(edited_rect, match_rect) {
+					cache.swap_remove(i, tiles);
+				} else {
+					i += 1;
+				}
+			}
+			// check entire changed area and add matches
+			let border_x = rule_width - 1;
+			let border_y = rule_height - 1;
+
+			let x_min = cx.wrapping_sub_unsigned(border_x);
+			let y_min = cy.wrapping_sub_unsigned(border_y);
+			let x_max = cx.wrapping_add_unsigned(width);
+			let y_max = cy.wrapping_add_unsigned(height);
+
+			let scan_start = self.profile.is_some().then(Instant::now);
+			// Scan every corner whose footprint overlaps the edit, the same matches that were
+			// discarded above, and convert back with the same helper so the two can't drift apart.
+			for cx in x_min..x_max {
+				for cy in y_min..y_max {
+					let (px, py) = rule.match_at_corner(cx, cy);
+					if !full_rule.allows_position(px, py) {
+						continue;
+					}
+					*self.counters.matches_examined.get_mut() += 1;
+					if self.world.subrule_matches(cx, cy, rule, &self.groups)
+						&& !self
+							.world
+							.rect_in_mask(&self.frozen, cx, cy, rule_width, rule_height)
+					{
+						cache.push((px, py), tiles);
+					}
+				}
+			}
+			if let (Some(profile), Some(scan_start)) = (&mut self.profile, scan_start) {
+				profile.rule_match_time[cache.rule] += scan_start.elapsed();
+			}
+		}
+		self.update_match_cache();
+		self.cache_timing.incremental_updates += 1;
+		self.cache_timing.incremental_duration += start.elapsed();
+		if let Some(profile) = &mut self.profile {
+			profile.cache_update_time += start.elapsed();
+		}
+	}
+
+	fn update_match_cache(&mut self) {
+		self.match_cache = self
+			.cache
+			.iter()
+			.enumerate()
+			.filter_map(|(i, c)| c.matches.is_empty().not().then_some(i))
+			.collect();
+	}
+
+	/// picks a random match from any rule with at least one match, or a dynamic rule
+	pub fn apply_one_match(&mut self) {
+		self.apply_match_in_phase(None);
+	}
+
+	/// Apply one match from the rules of the current phase, then move on to the next phase.
+	/// Cycles through phases 0 up to the highest phase of any enabled rule. Dynamic rules belong to phase 0.
+	pub fn step_phased(&mut self) {
+		let phase = self.phase;
+		self.apply_match_in_phase(Some(phase));
+		let max_phase = self
+			.rules
+			.iter()
+			.filter(|r| r.is_active_at(self.step_count))
+			.map(|r| r.phase)
+			.max()
+			.unwrap_or(0);
+		self.phase = if phase >= max_phase { 0 } else { phase + 1 };
+	}
+
+	/// the phase the next `step_phased` call will run
+	pub fn current_phase(&self) -> u8 {
+		self.phase
+	}
+
+	fn apply_match_in_phase(&mut self, phase: Option<u8>) {
+		match self.pick_match(phase) {
+			Some(MatchPick::Cached {
+				rule,
+				variant,
+				x,
+				y,
+			}) => {
+				self.apply_cached_match(rule, variant, x, y);
+			}
+			Some(MatchPick::Dynamic(index)) => {
+				self.apply_dynamic_rule(index);
+			}
+			None => (),
+		}
+	}
+
+	/// Apply up to `n` matches, skipping any whose footprint overlaps cells already changed in this batch.
+	/// Dynamic rules are checked with `DynamicRule::footprint`. Returns how many matches were applied.
+	pub fn step_batch(&mut self, n: usize) -> usize {
+		let mut touched = vec![false; self.world.width * self.world.height];
+		let mut applied = 0;
+		for _ in 0..n {
+			let changed = match self.pick_match(None) {
+				Some(MatchPick::Cached {
+					rule,
+					variant,
+					x,
+					y,
+				}) => {
+					let (cx, cy, width, height) = self.match_footprint(rule, variant, (x, y));
+					if self.world.rect_in_mask(&touched, cx, cy, width, height) {
+						continue;
+					}
+					self.apply_cached_match(rule, variant, x, y)
+						.then_some((cx, cy, width, height))
+				}
+				Some(MatchPick::Dynamic(index)) => {
+					let Some(m) = self.pick_dynamic_match(index) else {
+						continue;
+					};
+					let view = WorldView { world: &self.world };
+					let (cx, cy, width, height) = self.dynamic_rules[index].footprint(&view, &m);
+					if self.world.rect_in_mask(&touched, cx, cy, width, height) {
+						continue;
+					}
+					self.apply_dynamic_match(index, &m)
+				}
+				None => break,
+			};
+			if let Some((x, y, width, height)) = changed {
+				self.world.mark_rect(&mut touched, x, y, width, height);
+				applied += 1;
+			}
+		}
+		applied
+	}
+
+	/// Give world positions one update chance each, in random order.
+	/// Visits `epoch_size` distinct positions, or every position if the world (including the border where rules overhang) is smaller.
+	/// Matches are checked against the world as it is when each position is visited, and the cache is rebuilt once at the end.
+	/// Dynamic rules are not run.
+	pub fn step_epoch(&mut self, epoch_size: usize, rng: &mut impl Rng) {
+		let border_x = self.max_rule_width - 1;
+		let border_y = self.max_rule_height - 1;
+		let width = self.world.width + border_x * 2;
+		let height = self.world.height + border_y * 2;
+		let mut positions: Vec<usize> = (0..width * height).collect();
+		let count = epoch_size.min(positions.len());
+		let (positions, _) = positions.partial_shuffle(rng, count);
+		for &i in positions.iter() {
+			let x = ((i % width) as isize).wrapping_sub_unsigned(border_x);
+			let y = ((i / width) as isize).wrapping_sub_unsigned(border_y);
+			let matches = self.live_matches_at_point(x, y);
+			if matches.is_empty() {
+				self.record_step(&[], None);
+				continue;
+			}
+			let rules = &self.rules;
+			let &(rule_index, variant_index) = matches
+				.choose_weighted(rng, |&(rule, variant)| rules[rule].variant_weight(variant))
+				.unwrap();
+			let applied = self.apply_rule(x, y, rule_index, variant_index);
+			let mut candidates: Vec<usize> = matches.iter().map(|&(rule, _)| rule).collect();
+			candidates.sort_unstable();
+			candidates.dedup();
+			self.record_step(&candidates, applied.map(|writes| (rule_index, writes)));
+		}
+		self.rebuild_cache();
+	}
+
+	/// Advance the simulation by `steps` in the given mode.
+	/// Epochs draw their order from a generator seeded by the dish rng.
+	pub fn step(&mut self, mode: StepMode, steps: usize) {
+		let mut left = steps as u64;
+		while left > 0 {
+			self.run_schedule_transitions();
+			// stop at the next transition so scheduled rules switch on the exact step
+			let n = left.min(self.steps_until_transition());
+			match mode {
+				StepMode::Random => (0..n).for_each(|_| {
+					self.try_one_location();
+					self.record_step_entropy();
+				}),
+				StepMode::Phased => (0..n).for_each(|_| {
+					self.step_phased();
+					self.record_step_entropy();
+				}),
+				StepMode::Batch => {
+					self.step_batch(n as usize);
+					self.record_step_entropy();
+				}
+				StepMode::Epoch { epoch_size } => {
+					let mut rng = StdRng::from_rng(&mut self.rng).unwrap();
+					for _ in 0..n {
+						self.step_epoch(epoch_size, &mut rng);
+						self.record_step_entropy();
+					}
+				}
+			}
+			self.step_count += n;
+			left -= n;
+		}
+		// so the cache matches the schedules between calls too
+		self.run_schedule_transitions();
+		if !self.observers.is_empty() {
+			self.snapshot_stats();
+			let mut observers = std::mem::take(&mut self.observers);
+			for observer in &mut observers {
+				observer.on_step_complete(self, &self.stats);
+			}
+			self.observers = observers;
+		}
+	}
+
+	/// number of steps run through `step`, which rule schedules are checked against
+	pub fn step_count(&self) -> u64 {
+		self.step_count
+	}
+
+	/// set the step counter, for example to restart a scheduled scenario, and rebuild the cache to match
+	pub fn set_step_count(&mut self, step_count: u64) {
+		self.step_count = step_count;
+		self.rebuild_cache();
+	}
+
+	/// update the cache of rules whose schedule changes at the current step
+	fn run_schedule_transitions(&mut self) {
+		let step = self.step_count;
+		let transitions = self.upcoming_transitions();
+		let mut due = Vec::new();
+		while let Some(&(at, rule)) = transitions.last() {
+			if at > step {
+				break;
+			}
+			transitions.pop();
+			due.push(rule);
+		}
+		for rule in due {
+			self.update_cache_single_rule(rule);
+		}
+		// collect them again if the cache updates cleared them
+		self.upcoming_transitions();
+	}
+
+	fn upcoming_transitions(&mut self) -> &mut Vec<(u64, usize)> {
+		let step = self.step_count;
+		let rules = &self.rules;
+		self.schedule_transitions.get_or_insert_with(|| {
+			let mut transitions: Vec<(u64, usize)> = rules
+				.iter()
+				.enumerate()
+				.filter_map(|(i, rule)| Some((rule.schedule?.next_transition(step)?, i)))
+				.collect();
+			transitions.sort_unstable_by(|a, b| b.cmp(a));
+			transitions
+		})
+	}
+
+	fn steps_until_transition(&self) -> u64 {
+		self.schedule_transitions
+			.as_ref()
+			.and_then(|t| t.last())
+			.map_or(u64::MAX, |&(at, _)| at - self.step_count)
+	}
+
+	/// weighted random choice between all cached matches and dynamic rules
+	fn pick_match(&mut self, phase: Option<u8>) -> Option<MatchPick> {
+		let cache = &self.cache;
+		let rules = &self.rules;
+		let in_phase = |p: u8| phase.is_none_or(|phase| phase == p);
+		// match_cache is only refreshed by update_match_cache, so entries that emptied
+		// or no longer fit the rules since then are given no weight instead of trusted
+		let static_weights: Vec<usize> = self
+			.match_cache
+			.iter()
+			.map(|&i| {
+				let Some(entry) = cache.get(i).filter(|c| !c.matches.is_empty()) else {
+					return 0;
+				};
+				match rules.get(entry.rule) {
+					Some(rule) if entry.variant < rule.variants.len() && in_phase(rule.phase) => {
+						rule.variant_weight(entry.variant)
+					}
+					_ => 0,
+				}
+			})
+			.collect();
+		let dynamic_weights =
+			self.dynamic_rules
+				.iter()
+				.map(|r| if in_phase(0) { r.weight() } else { 0 });
+		let dist =
+			WeightedIndex::new(static_weights.iter().copied().chain(dynamic_weights)).ok()?;
+		let pick = dist.sample(&mut self.rng);
+		if pick >= self.match_cache.len() {
+			return Some(MatchPick::Dynamic(pick - self.match_cache.len()));
+		}
+		if self.selection == SelectionStrategy::Stratified {
+			// the draw above only chose between cached and dynamic rules
+			return self.pick_stratified(&static_weights);
+		}
+		let rule_cache = &self.cache[self.match_cache[pick]];
+		let match_pos_index = self.rng.gen::<usize>() % rule_cache.matches.len();
+		let (x, y) = rule_cache.matches[match_pos_index];
+		Some(MatchPick::Cached {
+			rule: rule_cache.rule,
+			variant: rule_cache.variant,
+			x,
+			y,
+		})
+	}
+
+	/// Pick a tile uniformly from those holding a match with weight, then a match in it by weight.
+	/// `weights` is indexed like `match_cache`.
+	/// Uses the per-tile counts of the cache, so only the matches of the picked entry are scanned.
+	fn pick_stratified(&mut self, weights: &[usize]) -> Option<MatchPick> {
+		let tiles = Tiles::new(&self.world);
+		let count_in = |entry: &RuleCache, tile: usize| {
+			entry.tile_counts.get(tile).copied().unwrap_or(0) as usize
+		};
+		let entries: Vec<(&RuleCache, usize)> = self
+			.match_cache
+			.iter()
+			.zip(weights)
+			.filter(|(_, &weight)| weight > 0)
+			.map(|(&i, &weight)| (&self.cache[i], weight))
+			.collect();
+		let occupied: Vec<usize> = (0..tiles.count())
+			.filter(|&t| entries.iter().any(|&(entry, _)| count_in(entry, t) > 0))
+			.collect();
+		let tile = *occupied.choose(&mut self.rng)?;
+		let in_tile = entries
+			.iter()
+			.map(|&(entry, weight)| weight * count_in(entry, tile));
+		let (entry, _) = entries[WeightedIndex::new(in_tile).ok()?.sample(&mut self.rng)];
+		let nth = self.rng.gen_range(0..count_in(entry, tile));
+		let &(x, y) = entry
+			.matches
+			.iter()
+			.filter(|&&pos| tiles.of(pos) == tile)
+			.nth(nth)?;
+		Some(MatchPick::Cached {
+			rule: entry.rule,
+			variant: entry.variant,
+			x,
+			y,
+		})
+	}
+
+	/// returns whether the rule was applied
+	fn apply_cached_match(
+		&mut self,
+		rule_index: usize,
+		variant_index: usize,
+		x: isize,
+		y: isize,
+	) -> bool {
+		let (cx, cy, width, height) = self.match_footprint(rule_index, variant_index, (x, y));
+
+		let applied = self.apply_rule(x, y, rule_index, variant_index);
+		self.record_step(&[rule_index], applied.map(|writes| (rule_index, writes)));
+		self.update_cache(cx, cy, width, height);
+		applied.is_some()
+	}
+
+	/// Apply one variant with its origin at `at` like a step would, and report what it read and wrote.
+	/// Nothing is written if the variant doesn't match there, whether or not the rule is enabled.
+	pub fn apply_match_traced(
+		&mut self,
+		rule: usize,
+		variant: usize,
+		at: (isize, isize),
+	) -> ApplyTrace {
+		let footprint = self.match_footprint(rule, variant, at);
+		let (cx, cy, width, height) = footprint;
+		let read = |dish: &Self| {
+			dish.rules[rule].variants[variant]
+				.read_footprint(cx, cy, |x, y| dish.world.get_cell(x, y))
+		};
+		let old_state = read(self);
+		let matched = self.variant_matches_at(rule, variant, at.0, at.1);
+		let mut writes = Vec::new();
+		let mut applied = false;
+		if matched {
+			let written = self.write_rule(at.0, at.1, rule, variant, Some(&mut writes));
+			applied = written.is_some();
+			self.record_step(&[rule], written.map(|writes| (rule, writes)));
+			self.update_cache(cx, cy, width, height);
+		}
+		ApplyTrace {
+			rule,
+			variant,
+			origin: at,
+			footprint,
+			matched,
+			applied,
+			old_state,
+			new_state: read(self),
+			writes,
+		}
+	}
+
+	pub fn add_dynamic_rule(&mut self, rule: Box<dyn DynamicRule>) {
+		self.dynamic_rules.push(rule);
+	}
+
+	pub fn clear_dynamic_rules(&mut self) {
+		self.dynamic_rules.clear();
+	}
+
+	pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+		self.observers.push(observer);
+	}
+
+	pub fn clear_observers(&mut self) {
+		self.observers.clear();
+	}
+
+	pub fn observers(&self) -> &[Box<dyn Observer>] {
+		&self.observers
+	}
+
+	pub fn dynamic_rule_count(&self) -> usize {
+		self.dynamic_rules.len()
+	}
+
+	/// Apply a random match of a dynamic rule and update the cache where it wrote.
+	/// Returns the changed rect.
+	fn apply_dynamic_rule(&mut self, index: usize) -> Option<(isize, isize, usize, usize)> {
+		let m = self.pick_dynamic_match(index)?;
+		self.apply_dynamic_match(index, &m)
+	}
+
+	/// a random current match of a dynamic rule, None if it has none
+	fn pick_dynamic_match(&mut self, index: usize) -> Option<DynMatch> {
+		let matches = self.dynamic_rules[index].find_matches(&WorldView { world: &self.world });
+		if matches.is_empty() {
+			return None;
+		}
+		Some(matches[self.rng.gen::<usize>() % matches.len()])
+	}
+
+	/// apply one match of a dynamic rule and update the cache where it wrote, returns the changed rect
+	fn apply_dynamic_match(
+		&mut self,
+		index: usize,
+		m: &DynMatch,
+	) -> Option<(isize, isize, usize, usize)> {
+		let mut writer = WorldWriter {
+			world: &mut self.world,
+			frozen: &self.frozen,
+			changed: None,
+		};
+		self.dynamic_rules[index].apply(&mut writer, m);
+		let changed = writer.changed_rect();
+		if let Some((x, y, width, height)) = changed {
+			self.update_cache(x, y, width, height);
+		}
+		changed
+	}
+
+	/// Picks a random point and applies a random match at that position, if any exist.
+	/// The random point can be outside the world bounds, to catch cases where the origin of a match is outside the bounds.
+	/// TODO make sure max_rule_[width/height] is up to date after each rule.generate_variants
+	pub fn try_one_location(&mut self) {
+		let border_x = self.max_rule_width - 1;
+		let border_y = self.max_rule_height - 1;
+		let origin_x = ((self.rng.gen::<usize>() % (self.world.width + border_x * 2)) as isize)
+			.wrapping_sub_unsigned(border_x);
+		let origin_y = ((self.rng.gen::<usize>() % (self.world.height + border_y * 2)) as isize)
+			.wrapping_sub_unsigned(border_y);
+
+		let matches = self.get_matches_at_point(origin_x, origin_y);
+		if matches.is_empty() {
+			self.record_step(&[], None);
+			return;
+		}
+		let rules = &self.rules;
+		let &(rule_index, variant_index) = matches
+			.choose_weighted(&mut self.rng, |&(rule, variant)| {
+				rules[rule].variant_weight(variant)
+			})
+			.unwrap();
+		let applied = self.apply_rule(origin_x, origin_y, rule_index, variant_index);
+		let mut candidates: Vec<usize> = matches.iter().map(|&(rule, _)| rule).collect();
+		candidates.sort_unstable();
+		candidates.dedup();
+		self.record_step(&candidates, applied.map(|writes| (rule_index, writes)));
+		let (cx, cy, width, height) =
+			self.match_footprint(rule_index, variant_index, (origin_x, origin_y));
+		self.update_cache(cx, cy, width, height);
+	}
+
+	/// like `get_matches_at_point`, but checked against the world instead of the cache
+	fn live_matches_at_point(&self, x: isize, y: isize) -> Vec<(usize, usize)> {
+		let mut matches = Vec::new();
+		for (rule_index, rule) in self.rules.iter().enumerate() {
+			if !rule.is_active_at(self.step_count) {
+				continue;
+			}
+			for variant_index in 0..rule.variants.len() {
+				if self.variant_matches_at(rule_index, variant_index, x, y) {
+					matches.push((rule_index, variant_index));
+				}
+			}
+		}
+		matches
+	}
+
+	fn get_matches_at_point(&self, x: isize, y: isize) -> Vec<(usize, usize)> {
+		self.cache
+			.iter()
+			.flat_map(|rule| {
+				rule.matches.iter().filter_map(|&(mx, my)| {
+					(mx == x && my == y).then_some((rule.rule, rule.variant))
+				})
+			})
+			.collect()
+	}
+
+	/// Returns what was written, None if the rule failed to apply because of frozen cells,
+	/// its failrate or the temperature. Zero writes means it applied but every output was skipped.
+	fn apply_rule(
+		&mut self,
+		x: isize,
+		y: isize,
+		rule_index: usize,
+		variant_index: usize,
+	) -> Option<Writes> {
+		let start = self.profile.is_some().then(Instant::now);
+		let applied = self.write_rule(x, y, rule_index, variant_index, None);
+		if let (Some(profile), Some(start)) = (&mut self.profile, start) {
+			profile.rule_apply_time[rule_index] += start.elapsed();
+		}
+		applied
+	}
+
+	/// apply a rule, recording each write into `writes` when given
+	fn write_rule(
+		&mut self,
+		x: isize,
+		y: isize,
+		rule_index: usize,
+		variant_index: usize,
+		mut writes: Option<&mut Vec<(isize, isize, Cell, Cell)>>,
+	) -> Option<Writes> {
+		let rule = &self.rules[rule_index];
+		let variant = &rule.variants[variant_index].clone();
+
+		let (corner_x, corner_y, width, height) = variant.footprint(x, y);
+		if self
+			.world
+			.rect_in_mask(&self.frozen, corner_x, corner_y, width, height)
+		{
+			return None;
+		}
+
+		let mut chance = 1. - rule.failrate as f32 / 256.;
+		if !rule.ignore_temperature {
+			chance = (chance * self.temperature).clamp(0., 1.);
+		}
+		if chance < 1. && self.rng.gen::<f32>() >= chance {
+			// TODO don't update cache after this
+			return None;
+		}
+
+		let old_state =
+			variant.read_footprint(corner_x, corner_y, |x, y| self.world.get_cell(x, y));
+		let world = &mut self.world;
+		let observers = &mut self.observers;
+		let mut written = 0;
+		let mut changed = 0;
+		variant.write_outputs(
+			corner_x,
+			corner_y,
+			&old_state,
+			&self.groups,
+			&mut self.rng,
+			|x, y, cell| {
+				if world.is_in_bounds(x, y) {
+					if let Some(writes) = writes.as_deref_mut() {
+						writes.push((x, y, world.get_cell(x, y).unwrap(), cell));
+					}
+					if !observers.is_empty() {
+						let old = world.get_cell(x, y).unwrap();
+						if old != cell {
+							for observer in observers.iter_mut() {
+								observer.on_cell_changed(x as usize, y as usize, old, cell);
+							}
+						}
+					}
+					if world.set_cell(x, y, cell) {
+						changed += 1;
+					}
+					written += 1;
+				}
+			},
+		);
+		*self.counters.cells_written.get_mut() += written;
+		for observer in &mut self.observers {
+			observer.on_rule_fired(rule_index, variant_index, x, y);
+		}
+		Some(Writes { written, changed })
+	}
+
+	pub fn get_cell(&self, x: usize, y: usize) -> Option<Cell> {
+		self.world.get_cell(x as isize, y as isize)
+	}
+
+	pub fn set_cell(&mut self, x: usize, y: usize, cell: Cell) {
+		if !self.observers.is_empty() {
+			if let Some(old) = self.get_cell(x, y).filter(|&old| old != cell) {
+				for observer in &mut self.observers {
+					observer.on_cell_changed(x, y, old, cell);
+				}
+			}
+		}
+		self.world.set_cell(x as isize, y as isize, cell);
+	}
+
+	pub fn is_in_bounds(&self, x: isize, y: isize) -> bool {
+		self.world.is_in_bounds(x, y)
+	}
+
+	pub fn world_width(&self) -> usize {
+		self.world.width
+	}
+
+	pub fn world_height(&self) -> usize {
+		self.world.height
+	}
+
+	pub fn resize_world(&mut self, new_width: usize, new_height: usize, anchor: Anchor) {
+		self.resize_world_with(new_width, new_height, anchor, Cell(0));
+	}
+
+	/// Resize the world, keeping the old contents in place relative to `anchor`.
+	/// New space is filled with `fill` and cells that end up outside the world are discarded.
+	pub fn resize_world_with(
+		&mut self,
+		new_width: usize,
+		new_height: usize,
+		anchor: Anchor,
+		fill: Cell,
+	) {
+		let mut new_world = World::new(new_width, new_height);
+		new_world.fill(fill);
+		let (dx, dy) = anchor.offset(
+			(self.world.width, self.world.height),
+			(new_world.width, new_world.height),
+		);
+		let mut new_frozen = Vec::new();
+		if !self.frozen.is_empty() {
+			new_frozen.resize(new_world.width * new_world.height, false);
+		}
+		for x in 0..self.world.width {
+			for y in 0..self.world.height {
+				if let Some(cell) = self.get_cell(x, y) {
+					new_world.set_cell(x as isize + dx, y as isize + dy, cell);
+				}
+				let (nx, ny) = (x as isize + dx, y as isize + dy);
+				if self.is_frozen(x, y) && new_world.is_in_bounds(nx, ny) {
+					new_frozen[nx as usize + ny as usize * new_world.width] = true;
+				}
+			}
+		}
+		self.world.replace(new_world);
+		self.frozen = new_frozen;
+		self.rebuild_cache();
+	}
+
+	/// cell counts of every chunk, row-major in units of `CHUNK_SIZE`
+	pub fn chunk_summaries(&self) -> impl Iterator<Item = &ChunkSummary> {
+		self.world.chunk_summaries()
+	}
+
+	/// Chunks with cells changed since the last call, as (x, y) in units of `CHUNK_SIZE`.
+	/// For renderers that only redraw what changed. A new or resized world is all dirty.
+	pub fn take_dirty_chunks(&mut self) -> Vec<(usize, usize)> {
+		self.world.take_dirty_chunks()
+	}
+
+	pub fn is_frozen(&self, x: usize, y: usize) -> bool {
+		x < self.world.width
+			&& self
+				.frozen
+				.get(x + y * self.world.width)
+				.copied()
+				.unwrap_or(false)
+	}
+
+	/// Freeze or unfreeze a cell. Matches touching a frozen cell are never cached or applied.
+	pub fn set_frozen(&mut self, x: usize, y: usize, frozen: bool) {
+		if !self.is_in_bounds(x as isize, y as isize) || self.is_frozen(x, y) == frozen {
+			return;
+		}
+		if self.frozen.is_empty() {
+			self.frozen = vec![false; self.world.width * self.world.height];
+		}
+		self.mark_edited();
+		self.frozen[x + y * self.world.width] = frozen;
+		if !self.frozen.contains(&true) {
+			self.frozen.clear();
+		}
+		self.update_cache(x as isize, y as isize, 1, 1);
+	}
+
+	pub fn clear_frozen(&mut self) {
+		if !self.frozen.is_empty() {
+			self.mark_edited();
+			self.frozen.clear();
+			self.rebuild_cache();
+		}
+	}
+
+	/// Set every cell where the row-major `mask` is false to `fill`.
+	/// Positions past the end of a mask that is too short are left as they are.
+	pub fn apply_mask(&mut self, mask: &[bool], fill: Cell) {
+		let width = self.world.width;
+		for y in 0..self.world.height {
+			for x in 0..width {
+				if !mask.get(x + y * width).copied().unwrap_or(true) {
+					self.set_cell(x, y, fill);
+				}
+			}
+		}
+		self.rebuild_cache();
+	}
+
+	/// row-major mask over the world, true where the cell is `cell`
+	pub fn mask_from_cell_type(&self, cell: Cell) -> Vec<bool> {
+		let width = self.world.width;
+		(0..width * self.world.height)
+			.map(|i| self.get_cell(i % width, i / width) == Some(cell))
+			.collect()
+	}
+
+	/// row-major mask over the world, true inside the rectangle. Parts outside the world are ignored
+	pub fn mask_from_region(&self, x: usize, y: usize, width: usize, height: usize) -> Vec<bool> {
+		let world_width = self.world.width;
+		(0..world_width * self.world.height)
+			.map(|i| {
+				let (px, py) = (i % world_width, i / world_width);
+				(x..x.saturating_add(width)).contains(&px)
+					&& (y..y.saturating_add(height)).contains(&py)
+			})
+			.collect()
+	}
+
+	/// true where either mask is, as long as the shorter one
+	pub fn mask_union(a: &[bool], b: &[bool]) -> Vec<bool> {
+		a.iter().zip(b).map(|(a, b)| a | b).collect()
+	}
+
+	/// true where both masks are, as long as the shorter one
+	pub fn mask_intersection(a: &[bool], b: &[bool]) -> Vec<bool> {
+		a.iter().zip(b).map(|(a, b)| a & b).collect()
+	}
+
+	pub fn mask_invert(mask: &[bool]) -> Vec<bool> {
+		mask.iter().map(|m| !m).collect()
+	}
+
+	/// Replaces the 4-connected region of cells equal to the one at (x, y), painting at most `max_cells`.
+	/// Returns the number of cells painted.
+	pub fn paint_fill_bucket(
+		&mut self,
+		x: usize,
+		y: usize,
+		new_cell: Cell,
+		max_cells: usize,
+	) -> usize {
+		self.flood_fill(x, y, max_cells, false, |_| Some(new_cell))
+	}
+
+	/// like `paint_fill_bucket`, but the region is 8-connected
+	pub fn paint_fill_bucket_8(
+		&mut self,
+		x: usize,
+		y: usize,
+		new_cell: Cell,
+		max_cells: usize,
+	) -> usize {
+		self.flood_fill(x, y, max_cells, true, |_| Some(new_cell))
+	}
+
+	/// like `paint_fill_bucket`, but each cell gets a random member of the group
+	pub fn paint_fill_bucket_group(
+		&mut self,
+		x: usize,
+		y: usize,
+		group_index: usize,
+		max_cells: usize,
+	) -> usize {
+		let Some(group) = self.groups.get(group_index) else {
+			return 0;
+		};
+		let cells: Vec<Cell> = group.placeable_cells().collect();
+		self.flood_fill(x, y, max_cells, false, |rng| cells.choose(rng).copied())
+	}
+
+	fn flood_fill(
+		&mut self,
+		x: usize,
+		y: usize,
+		max_cells: usize,
+		diagonal: bool,
+		mut new_cell: impl FnMut(&mut CountingRng) -> Option<Cell>,
+	) -> usize {
+		let Some(target) = self.get_cell(x, y) else {
+			return 0;
+		};
+		let width = self.world.width;
+		let mut visited = vec![false; width * self.world.height];
+		let mut queue = VecDeque::new();
+		queue.push_back((x, y));
+		visited[x + y * width] = true;
+		let (mut min_x, mut min_y, mut max_x, mut max_y) = (x, y, x, y);
+		let mut painted = 0;
+
+		while let Some((x, y)) = queue.pop_front() {
+			if painted >= max_cells {
+				break;
+			}
+			let Some(cell) = new_cell(&mut self.rng) else {
+				break;
+			};
+			self.set_cell(x, y, cell);
+			painted += 1;
+			(min_x, min_y) = (min_x.min(x), min_y.min(y));
+			(max_x, max_y) = (max_x.max(x), max_y.max(y));
+
+			for dx in -1..=1isize {
+				for dy in -1..=1isize {
+					if (dx == 0 && dy == 0) || (!diagonal && dx != 0 && dy != 0) {
+						continue;
+					}
+					let nx = x as isize + dx;
+					let ny = y as isize + dy;
+					if !self.is_in_bounds(nx, ny) {
+						continue;
+					}
+					let (nx, ny) = (nx as usize, ny as usize);
+					if !visited[nx + ny * width] && self.get_cell(nx, ny) == Some(target) {
+						visited[nx + ny * width] = true;
+						queue.push_back((nx, ny));
+					}
+				}
+			}
+		}
+		if painted > 0 {
+			self.update_cache(
+				min_x as isize,
+				min_y as isize,
+				max_x - min_x + 1,
+				max_y - min_y + 1,
+			);
+		}
+		painted
+	}
+
+	/// Fill a rectangle with cells picked at random, weighted by the second element of each pair.
+	/// Does nothing if no weight is positive.
+	pub fn randomize_region(
+		&mut self,
+		x: usize,
+		y: usize,
+		w: usize,
+		h: usize,
+		cells: &[(Cell, f32)],
+		rng: &mut impl Rng,
+	) {
+		let weights = cells.iter().map(|&(_, weight)| weight.max(0.));
+		let Ok(distribution) = WeightedIndex::new(weights) else {
+			return;
+		};
+		for px in x..x.saturating_add(w).min(self.world.width) {
+			for py in y..y.saturating_add(h).min(self.world.height) {
+				let cell = cells[distribution.sample(rng)].0;
+				self.set_cell(px, py, cell);
+			}
+		}
+		self.update_cache(x as isize, y as isize, w, h);
+	}
+
+	pub fn randomize_world(&mut self, cells: &[(Cell, f32)], rng: &mut impl Rng) {
+		let (w, h) = (self.world.width, self.world.height);
+		self.randomize_region(0, 0, w, h, cells, rng);
+	}
+
+	/// Fill the world from a noise function, called with x and y scaled to 0..1.
+	/// `cell_thresholds` is sorted by threshold, and each position gets the cell of the highest threshold
+	/// at or below the noise value. Values below every threshold get the first cell.
+	pub fn generate_noise_world(
+		&mut self,
+		noise_fn: &dyn Fn(f64, f64) -> f64,
+		cell_thresholds: &[(f64, Cell)],
+	) {
+		if cell_thresholds.is_empty() {
+			return;
+		}
+		let (width, height) = (self.world.width as f64, self.world.height as f64);
+		self.map_cells(|x, y, _| {
+			let value = noise_fn(x as f64 / width, y as f64 / height);
+			let above = cell_thresholds.partition_point(|&(threshold, _)| threshold <= value);
+			cell_thresholds[above.saturating_sub(1)].1
+		});
+	}
+
+	/// fill the world with equal bands of `cells`, left to right if `horizontal` and top to bottom otherwise
+	pub fn generate_gradient_world(&mut self, horizontal: bool, cells: &[Cell]) {
+		if cells.is_empty() {
+			return;
+		}
+		let (width, height) = (self.world.width, self.world.height);
+		self.map_cells(|x, y, _| {
+			let (pos, len) = if horizontal { (x, width) } else { (y, height) };
+			cells[pos * cells.len() / len]
+		});
+	}
+
+	/// replace every cell with `f(x, y, cell)`
+	pub fn map_cells(&mut self, f: impl Fn(usize, usize, Cell) -> Cell) {
+		for y in 0..self.world.height {
+			for x in 0..self.world.width {
+				let cell = self.get_cell(x, y).unwrap();
+				self.set_cell(x, y, f(x, y, cell));
+			}
+		}
+		self.rebuild_cache();
+	}
+
+	/// replace the cells where `f(x, y, cell)` is false with `replacement`
+	pub fn filter_cells(&mut self, f: impl Fn(usize, usize, Cell) -> bool, replacement: Cell) {
+		self.map_cells(|x, y, cell| if f(x, y, cell) { cell } else { replacement });
+	}
+
+	/// A new dish with the rules, types and groups of this one,
+	/// and a world made from `f(x, y, cell, other_cell)` where the two worlds overlap.
+	pub fn zip_cells(&self, other: &Dish, f: impl Fn(usize, usize, Cell, Cell) -> Cell) -> Dish {
+		let width = self.world.width.min(other.world.width);
+		let height = self.world.height.min(other.world.height);
+		let mut world = World::new(width, height);
+		for y in 0..height {
+			for x in 0..width {
+				let cell = f(
+					x,
+					y,
+					self.get_cell(x, y).unwrap(),
+					other.get_cell(x, y).unwrap(),
+				);
+				world.set_cell(x as isize, y as isize, cell);
+			}
+		}
+		let mut dish = Dish::from_parts(
+			world,
+			self.rules.clone(),
+			self.types.clone(),
+			self.groups.clone(),
+		);
+		dish.temperature = self.temperature;
+		dish.selection = self.selection;
+		dish.update_all_rules();
+		dish
+	}
+
+	/// One line per row and one character per cell, where the nth char of `cell_chars` stands for cell id n.
+	pub fn export_world_matrix_as_text(&self, cell_chars: &str) -> Result<String, ExportError> {
+		let chars: Vec<char> = cell_chars.chars().collect();
+		let mut text = String::with_capacity((self.world.width + 1) * self.world.height);
+		let mut needed = 0;
+		for y in 0..self.world.height {
+			if y > 0 {
+				text.push('\n');
+			}
+			for x in 0..self.world.width {
+				let id = self.get_cell(x, y).unwrap().id();
+				needed = needed.max(id + 1);
+				text.push(chars.get(id).copied().unwrap_or('?'));
+			}
+		}
+		if needed > chars.len() {
+			return Err(ExportError::NotEnoughChars {
+				needed,
+				got: chars.len(),
+			});
+		}
+		Ok(text)
+	}
+
+	/// The world as row-major rgb bytes with `scale` pixels per cell, for saving as an image.
+	/// With `patterns`, cell patterns are baked in once cells are at least `MIN_PATTERN_SCALE` pixels wide.
+	/// Shapes are not drawn and undefined cells are magenta. Returns (width, height, pixels).
+	pub fn render_world_rgb(&self, scale: u32, patterns: bool) -> (usize, usize, Vec<u8>) {
+		let scale = scale.max(1);
+		let patterns = patterns && scale >= MIN_PATTERN_SCALE;
+		let width = self.world.width * scale as usize;
+		let height = self.world.height * scale as usize;
+		let mut pixels = Vec::with_capacity(width * height * 3);
+		for py in 0..height as u32 {
+			for px in 0..width as u32 {
+				let cell = self
+					.world
+					.get_cell((px / scale) as isize, (py / scale) as isize)
+					.unwrap_or_default();
+				let color = match self.types.get(cell.id()) {
+					Some(data)
+						if patterns && data.pattern.covers(px % scale, py % scale, scale) =>
+					{
+						data.pattern_color()
+					}
+					Some(data) => data.color,
+					None => [255, 0, 255],
+				};
+				pixels.extend_from_slice(&color);
+			}
+		}
+		(width, height, pixels)
+	}
+
+	/// cell ids separated by commas, one line per row
+	pub fn export_world_matrix_csv_ids(&self) -> String {
+		(0..self.world.height)
+			.map(|y| {
+				(0..self.world.width)
+					.map(|x| self.get_cell(x, y).unwrap().0.to_string())
+					.collect::<Vec<_>>()
+					.join(",")
+			})
+			.collect::<Vec<_>>()
+			.join("\n")
+	}
+
+	/// Human-readable overview of every rule in markdown.
+	/// Patterns are drawn with `SubRule::as_text_grid`, followed by a legend of the cell names.
+	pub fn export_rules_markdown(&self) -> String {
+		let chars: Vec<char> = TEXT_GRID_CHARS.chars().collect();
+		let mut text = String::from("# Rules\n");
+		for rule in &self.rules {
+			let name = if rule.name.is_empty() {
+				"unnamed rule"
+			} else {
+				&rule.name
+			};
+			text += &format!("\n## {name}\n\n");
+			if !rule.enabled {
+				text += "*disabled*\n\n";
+			}
+			if !rule.description.is_empty() {
+				text += &format!("{}\n\n", rule.description.trim_end());
+			}
+			let symmetry: Vec<&str> = [
+				(rule.flip_x, "flip x"),
+				(rule.flip_y, "flip y"),
+				(rule.rotate, "rotate"),
+			]
+			.into_iter()
+			.filter_map(|(on, name)| on.then_some(name))
+			.collect();
+			let symmetry = if symmetry.is_empty() {
+				"none".to_owned()
+			} else {
+				symmetry.join(", ")
+			};
+			text += &format!("- symmetry: {symmetry}\n");
+			let chance = 100. * (1. - rule.failrate as f32 / 256.);
+			text += &format!("- chance: {chance:.1}%\n\n");
+			let grid = rule.base.text_grid(TEXT_GRID_CHARS, TEXT_GRID_CHARS).0;
+			text += &format!("```\n{grid}\n```\n\n");
+			let mut used = self.cells_in_pattern(&rule.base);
+			used.sort_by_key(|c| c.0);
+			for cell in used {
+				let ch = chars.get(cell.id()).copied().unwrap_or('?');
+				let name = self.types.get(cell.id()).map_or("?", |t| &t.name);
+				text += &format!("- `{ch}`: {name}\n");
+			}
+			let groups: Vec<usize> = rule
+				.base
+				.contents
+				.iter()
+				.filter_map(|(from, _)| match from {
+					RuleCellFrom::Group(g) => Some(*g),
+					_ => None,
+				})
+				.collect();
+			for (id, group) in self.groups.iter().enumerate() {
+				if groups.contains(&id) {
+					text += &format!("- `g{id}`: group {}\n", group.name);
+				}
+			}
+		}
+		text
+	}
+
+	/// An svg image of a rule's base pattern, the input grid then an arrow then the output grid.
+	/// `Copy` outputs are lines back to the input cell they copy.
+	pub fn export_rule_as_svg(
+		&self,
+		rule_index: usize,
+		cell_size_px: u32,
+	) -> Result<String, ExportError> {
+		let rule = self
+			.rules
+			.get(rule_index)
+			.ok_or(ExportError::NoSuchRule { index: rule_index })?;
+		let (width, height) = svg_rule_size(rule, cell_size_px);
+		let mut svg = svg_header(width, height);
+		self.svg_rule(&mut svg, rule, cell_size_px, 0);
+		svg += "</svg>\n";
+		Ok(svg)
+	}
+
+	/// every rule like `export_rule_as_svg`, stacked top to bottom with a cell of space between them
+	pub fn export_all_rules_as_svg(&self) -> Result<String, ExportError> {
+		let size = SVG_CELL_SIZE;
+		let sizes: Vec<(u32, u32)> = self
+			.rules
+			.iter()
+			.map(|rule| svg_rule_size(rule, size))
+			.collect();
+		let width = sizes.iter().map(|s| s.0).max().unwrap_or(0);
+		let height = sizes
+			.iter()
+			.map(|s| s.1 + size)
+			.sum::<u32>()
+			.saturating_sub(size);
+		let mut svg = svg_header(width, height);
+		let mut y = 0;
+		for (rule, (_, rule_height)) in self.rules.iter().zip(sizes) {
+			self.svg_rule(&mut svg, rule, size, y);
+			y += rule_height + size;
+		}
+		svg += "</svg>\n";
+		Ok(svg)
+	}
+
+	/// append the elements of one rule, as a group moved down by `y`
+	fn svg_rule(&self, svg: &mut String, rule: &Rule, size: u32, y: u32) {
+		let pattern = &rule.base;
+		let name = if rule.name.is_empty() {
+			"unnamed rule"
+		} else {
+			&rule.name
+		};
+		*svg += &format!(
+			"<g transform=\"translate(0 {y})\">\n<title>{}</title>\n",
+			xml_escape(name)
+		);
+		let type_name = |cell: Cell| {
+			if cell.is_boundary() {
+				"outside the world".to_owned()
+			} else {
+				self.types.get(cell.id()).map_or_else(
+					|| format!("undefined type {}", cell.id()),
+					|t| t.name.clone(),
+				)
+			}
+		};
+		let group_name = |g: usize| self.groups.get(g).map_or("?", |g| &g.name).to_owned();
+		let color = |cell: Cell| {
+			self.types
+				.get(cell.id())
+				.filter(|_| !cell.is_boundary())
+				.map_or(
+					"fill=\"none\" stroke=\"black\" stroke-dasharray=\"2\"".to_owned(),
+					|t| {
+						let [r, g, b] = t.color;
+						format!("fill=\"#{r:02x}{g:02x}{b:02x}\"")
+					},
+				)
+		};
+		let empty = "fill=\"none\" stroke=\"gray\"".to_owned();
+		let to_x = (pattern.width as u32 + 1) * size;
+		for py in 0..pattern.height {
+			for px in 0..pattern.width {
+				let (from, to) = pattern.get(px, py);
+				let (style, title) = match from {
+					RuleCellFrom::Any => (empty.clone(), "any".to_owned()),
+					RuleCellFrom::One(cell) => (color(cell), type_name(cell)),
+					RuleCellFrom::Group(g) => (
+						"fill=\"none\" stroke=\"black\"".to_owned(),
+						format!("group {}", group_name(g)),
+					),
+					RuleCellFrom::BoundaryVoid => (
+						"fill=\"none\" stroke=\"black\" stroke-dasharray=\"2\"".to_owned(),
+						"outside the world".to_owned(),
+					),
+				};
+				let (x, y) = (px as u32 * size, py as u32 * size);
+				svg_cell(svg, x, y, size, &style, &title);
+				let (style, title) = match to {
+					RuleCellTo::None => (empty.clone(), "unchanged".to_owned()),
+					RuleCellTo::One(cell) => (color(cell), type_name(cell)),
+					RuleCellTo::GroupRandom(g) => (
+						"fill=\"none\" stroke=\"black\"".to_owned(),
+						format!("random from group {}", group_name(g)),
+					),
+					RuleCellTo::GroupRandomOther(g) => (
+						"fill=\"none\" stroke=\"black\"".to_owned(),
+						format!("random other from group {}", group_name(g)),
+					),
+					RuleCellTo::Copy(cx, cy) => (empty.clone(), format!("copy of ({cx}, {cy})")),
+				};
+				svg_cell(svg, to_x + x, y, size, &style, &title);
+			}
+		}
+		let half = size / 2;
+		for py in 0..pattern.height {
+			for px in 0..pattern.width {
+				if let (_, RuleCellTo::Copy(cx, cy)) = pattern.get(px, py) {
+					if cx < pattern.width && cy < pattern.height {
+						*svg += &format!(
+							"<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\"/>\n",
+							cx as u32 * size + half,
+							cy as u32 * size + half,
+							to_x + px as u32 * size + half,
+							py as u32 * size + half,
+						);
+					}
+				}
+			}
+		}
+		let (start, end) = (pattern.width as u32 * size + 2, to_x.saturating_sub(2));
+		let mid = pattern.height as u32 * size / 2;
+		let head = size / 4;
+		*svg += &format!(
+			"<path d=\"M {start} {mid} H {end} M {} {} L {end} {mid} L {} {}\" fill=\"none\" stroke=\"black\"/>\n",
+			end - head,
+			mid - head,
+			end - head,
+			mid + head,
+		);
+		*svg += "</g>\n";
+	}
+
+	/// distinct cell types a pattern reads or writes, excluding `Cell::BOUNDARY`
+	fn cells_in_pattern(&self, pattern: &SubRule) -> Vec<Cell> {
+		let mut cells = Vec::new();
+		for (from, to) in &pattern.contents {
+			for cell in [
+				match from {
+					RuleCellFrom::One(c) => Some(*c),
+					_ => None,
+				},
+				match to {
+					RuleCellTo::One(c) => Some(*c),
+					_ => None,
+				},
+			]
+			.into_iter()
+			.flatten()
+			{
+				if !cell.is_boundary() && !cells.contains(&cell) {
+					cells.push(cell);
+				}
+			}
+		}
+		cells
+	}
+
+	/// the rules alone as a json array, without the rest of the dish
+	pub fn export_rules_as_json_array(&self) -> Result<String, serde_json::Error> {
+		serde_json::to_string(&self.rules)
+	}
+
+	/// Append rules written by `export_rules_as_json_array`, possibly from another dish.
+	/// Nothing is added unless every pattern is well-formed and every cell and group they refer to exists in this dish.
+	pub fn import_rules_from_json_array(&mut self, json: &str) -> Result<(), ImportError> {
+		let rules: Vec<Rule> =
+			serde_json::from_str(json).map_err(|e| ImportError::Json(e.to_string()))?;
+		self.import_rules(rules)
+	}
+
+	/// Append rules from another dish, such as ones resolved by `io::rule_from_json_value`.
+	/// Nothing is added unless every pattern is well-formed and every cell and group they refer to exists in this dish.
+	pub fn import_rules(&mut self, rules: Vec<Rule>) -> Result<(), ImportError> {
+		for (rule, r) in rules.iter().enumerate() {
+			let (width, height) = (r.base.width, r.base.height);
+			if width == 0 || height == 0 {
+				return Err(ImportError::EmptyPattern { rule });
+			}
+			if r.base.contents.len() != width * height {
+				return Err(ImportError::WrongCellCount {
+					rule,
+					expected: width * height,
+					got: r.base.contents.len(),
+				});
+			}
+			for (from, to) in &r.base.contents {
+				if let &RuleCellTo::Copy(x, y) = to {
+					if x >= width || y >= height {
+						return Err(ImportError::InvalidCopyRef { rule, x, y });
+					}
+				}
+				let cells = [
+					match from {
+						RuleCellFrom::One(cell) if !cell.is_boundary() => Some(*cell),
+						_ => None,
+					},
+					match to {
+						RuleCellTo::One(cell) => Some(*cell),
+						_ => None,
+					},
+				];
+				if let Some(cell) = cells
+					.into_iter()
+					.flatten()
+					.find(|c| c.id() >= self.types.len())
+				{
+					return Err(ImportError::InvalidCellRef { rule, cell });
+				}
+				let groups = [
+					match from {
+						RuleCellFrom::Group(group) => Some(*group),
+						_ => None,
+					},
+					match to {
+						RuleCellTo::GroupRandom(group) | RuleCellTo::GroupRandomOther(group) => {
+							Some(*group)
+						}
+						_ => None,
+					},
+				];
+				if let Some(group) = groups
+					.into_iter()
+					.flatten()
+					.find(|&g| g >= self.groups.len())
+				{
+					return Err(ImportError::InvalidGroupRef { rule, group });
+				}
+			}
+		}
+		self.mark_changed(DishPart::Rules);
+		self.rules.extend(rules);
+		self.update_all_rules();
+		Ok(())
+	}
+
+	/// Replace the world with text written by `export_world_matrix_as_text`.
+	/// The world is resized to the size of the text.
+	pub fn import_world_matrix_from_text(
+		&mut self,
+		s: &str,
+		cell_chars: &str,
+	) -> Result<(), ImportError> {
+		let rows: Vec<Vec<char>> = s.lines().map(|line| line.chars().collect()).collect();
+		let width = rows.first().map_or(0, Vec::len);
+		if width == 0 {
+			return Err(ImportError::Empty);
+		}
+		let mut world = World::new(width, rows.len());
+		for (y, row) in rows.iter().enumerate() {
+			if row.len() != width {
+				return Err(ImportError::RaggedRow {
+					row: y,
+					expected: width,
+					got: row.len(),
+				});
+			}
+			for (x, &ch) in row.iter().enumerate() {
+				let id = cell_chars
+					.chars()
+					.position(|c| c == ch)
+					.ok_or(ImportError::UnknownChar { ch, x, y })?;
+				world.set_cell(x as isize, y as isize, Cell(id as u16));
+			}
+		}
+		if (world.width, world.height) != (self.world.width, self.world.height) {
+			self.frozen.clear();
+		}
+		self.world.replace(world);
+		self.rebuild_cache();
+		Ok(())
+	}
+
+	/// The changes to the world after `since_revision`, for keeping a copy of it in sync with
+	/// `apply_world_delta`. Pass the revision of the last delta to get the next one.
+	/// Gives the whole world for revision 0, an unknown revision, or when most of it changed.
+	pub fn serialize_world_delta(&self, since_revision: u64) -> WorldDelta {
+		let (width, height) = (self.world.width, self.world.height);
+		let revision = self.world.revision;
+		let changed = self.world.chunks_changed_since(since_revision);
+		if since_revision == 0
+			|| since_revision > revision
+			|| changed.len() * 2 > self.world.chunk_count()
+		{
+			return WorldDelta::Full {
+				revision,
+				width,
+				height,
+				cells: self.read_world_rect(0, 0, width, height),
+			};
+		}
+		let tiles = changed
+			.into_iter()
+			.map(|(cx, cy)| {
+				let (x, y) = (cx * CHUNK_SIZE, cy * CHUNK_SIZE);
+				let tile_width = CHUNK_SIZE.min(width - x);
+				let tile_height = CHUNK_SIZE.min(height - y);
+				WorldTile {
+					x,
+					y,
+					width: tile_width,
+					height: tile_height,
+					cells: self.read_world_rect(x, y, tile_width, tile_height),
+				}
+			})
+			.collect();
+		WorldDelta::Tiles {
+			revision,
+			width,
+			height,
+			tiles,
+		}
+	}
+
+	/// Update the world from a `serialize_world_delta` of another dish.
+	/// Nothing is changed if the delta doesn't fit.
+	pub fn apply_world_delta(&mut self, delta: &WorldDelta) -> Result<(), DeltaError> {
+		match delta {
+			&WorldDelta::Full {
+				width,
+				height,
+				ref cells,
+				..
+			} => {
+				if cells.len() != width * height {
+					return Err(DeltaError::WrongCellCount {
+						tile: None,
+						expected: width * height,
+						got: cells.len(),
+					});
+				}
+				let mut world = World::new(width, height);
+				for (i, &cell) in cells.iter().enumerate() {
+					world.set_cell((i % width) as isize, (i / width) as isize, cell);
+				}
+				if (world.width, world.height) != (self.world.width, self.world.height) {
+					self.frozen.clear();
+				}
+				self.world.replace(world);
+				self.rebuild_cache();
+			}
+			WorldDelta::Tiles {
+				width,
+				height,
+				tiles,
+				..
+			} => {
+				let size = (self.world.width, self.world.height);
+				if size != (*width, *height) {
+					return Err(DeltaError::SizeMismatch {
+						world: size,
+						delta: (*width, *height),
+					});
+				}
+				for (index, tile) in tiles.iter().enumerate() {
+					if tile.x + tile.width > size.0 || tile.y + tile.height > size.1 {
+						return Err(DeltaError::TileOutOfBounds { tile: index });
+					}
+					if tile.cells.len() != tile.width * tile.height {
+						return Err(DeltaError::WrongCellCount {
+							tile: Some(index),
+							expected: tile.width * tile.height,
+							got: tile.cells.len(),
+						});
+					}
+				}
+				for tile in tiles {
+					for (i, &cell) in tile.cells.iter().enumerate() {
+						let x = tile.x + i % tile.width;
+						let y = tile.y + i / tile.width;
+						self.world.set_cell(x as isize, y as isize, cell);
+					}
+					self.update_cache(tile.x as isize, tile.y as isize, tile.width, tile.height);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// row-major cells of a rect inside the world
+	fn read_world_rect(&self, x: usize, y: usize, width: usize, height: usize) -> Vec<Cell> {
+		(y..y + height)
+			.flat_map(|y| (x..x + width).map(move |x| (x, y)))
+			.map(|(x, y)| self.get_cell(x, y).unwrap())
+			.collect()
+	}
+
+	/// the cells of the world as json, without rules, types or groups
+	pub fn export_world_as_json_string(&self) -> Result<String, serde_json::Error> {
+		let (width, height) = (self.world.width, self.world.height);
+		let cells = (0..height)
+			.flat_map(|y| (0..width).map(move |x| (x, y)))
+			.map(|(x, y)| self.get_cell(x, y).unwrap())
+			.collect();
+		serde_json::to_string(&WorldCells {
+			width,
+			height,
+			cells,
+		})
+	}
+
+	/// Replace the cells of the world with json written by `export_world_as_json_string`,
+	/// keeping the current rules, types and groups. The world must be the same size.
+	pub fn import_world_from_json_string(&mut self, json: &str) -> Result<(), ImportError> {
+		let world: WorldCells =
+			serde_json::from_str(json).map_err(|e| ImportError::Json(e.to_string()))?;
+		self.check_world_size(world.width, world.height)?;
+		if world.cells.len() != world.width * world.height {
+			return Err(ImportError::Json(format!(
+				"expected {} cells, got {}",
+				world.width * world.height,
+				world.cells.len()
+			)));
+		}
+		for (i, &cell) in world.cells.iter().enumerate() {
+			let (x, y) = (i % world.width, i / world.width);
+			self.world.set_cell(x as isize, y as isize, cell);
+		}
+		self.rebuild_cache();
+		Ok(())
+	}
+
+	/// Copy the cells of the world from `other`, keeping the current rules, types and groups.
+	/// The worlds must be the same size.
+	pub fn import_world_from_dish(&mut self, other: &Dish) -> Result<(), ImportError> {
+		self.check_world_size(other.world.width, other.world.height)?;
+		for y in 0..self.world.height {
+			for x in 0..self.world.width {
+				let cell = other.get_cell(x, y).unwrap();
+				self.world.set_cell(x as isize, y as isize, cell);
+			}
+		}
+		self.rebuild_cache();
+		Ok(())
+	}
+
+	fn check_world_size(&self, width: usize, height: usize) -> Result<(), ImportError> {
+		let expected = (self.world.width, self.world.height);
+		if (width, height) != expected {
+			return Err(ImportError::DimensionMismatch {
+				expected,
+				got: (width, height),
+			});
+		}
+		Ok(())
+	}
+
+	/// make all following random choices deterministic
+	pub fn seed(&mut self, seed: u64) {
+		self.rng = CountingRng::new(StdRng::seed_from_u64(seed));
+	}
+
+	/// Seed the dish from `rng` and run `steps` calls of `apply_one_match`,
+	/// timing the cache updates and each rule's matching and applying.
+	pub fn run_profiled(&mut self, steps: usize, rng: &mut impl Rng) -> Profile {
+		self.seed(rng.gen());
+		self.profile = Some(Profile::new(self.rules.len()));
+		let start = Instant::now();
+		for _ in 0..steps {
+			self.apply_one_match();
+		}
+		let mut profile = self.profile.take().unwrap();
+		profile.total_time = start.elapsed();
+		profile
+	}
+
+	/// true when no rule variant has a cached match and no dynamic rule can apply, so stepping changes nothing
+	pub fn is_quiescent(&self) -> bool {
+		self.cache.iter().all(|c| c.matches.is_empty())
+			&& self.dynamic_rules.iter().all(|rule| {
+				rule.find_matches(&WorldView { world: &self.world })
+					.is_empty()
+			})
+	}
+
+	/// Seed the dish and apply matches until it is quiescent, for at most `max_steps` calls of `apply_one_match`.
+	/// Returns the number of steps taken, or None if it was still changing.
+	pub fn run_until_stable(&mut self, max_steps: usize, seed: u64) -> Option<usize> {
+		self.run_to_quiescence(max_steps as u64, seed, |_| ControlFlow::<()>::Continue(()))
+			.ok()
+			.map(|steps| steps as usize)
+	}
+
+	/// How many steps the world takes to settle from this seed, for at most `max_steps` steps.
+	/// Unlike `run_until_stable` it gives up once the world entropy has not changed for `FIXPOINT_PLATEAU` steps,
+	/// so rules that only move cells around are not reported as reaching a fixpoint unless they settle by then.
+	/// The entropy after each step is kept in `stats.step_entropy`, see `convergence_speed`.
+	pub fn time_to_fixpoint(&mut self, max_steps: u64, seed: u64) -> FixpointResult {
+		self.stats.step_entropy.clear();
+		// world hashes since entropy last changed
+		let mut plateau = Vec::new();
+		let result = self.run_to_quiescence(max_steps, seed, |dish| {
+			let entropy = dish.world_entropy();
+			let history = &mut dish.stats.step_entropy;
+			if history.back() == Some(&entropy) {
+				plateau.push(dish.world.content_hash());
+			} else {
+				plateau.clear();
+			}
+			history.push_back(entropy);
+			if history.len() > STEP_ENTROPY_HISTORY {
+				history.pop_front();
+			}
+			if plateau.len() as u64 >= FIXPOINT_PLATEAU {
+				return ControlFlow::Break(FixpointResult::NotReached {
+					final_entropy: entropy,
+					period_detected: period_of(&plateau),
+				});
+			}
+			ControlFlow::Continue(())
+		});
+		match result {
+			Ok(steps) => FixpointResult::Reached { steps },
+			Err(Some(stopped)) => stopped,
+			Err(None) => FixpointResult::NotReached {
+				final_entropy: self.world_entropy(),
+				period_detected: None,
+			},
+		}
+	}
+
+	/// Seed the dish and call `apply_one_match` until it is quiescent, for at most `max_steps` steps,
+	/// calling `after_step` after each one. Returns the steps taken once quiescent,
+	/// or the value `after_step` stopped with, or None if the steps ran out first.
+	fn run_to_quiescence<B>(
+		&mut self,
+		max_steps: u64,
+		seed: u64,
+		mut after_step: impl FnMut(&mut Self) -> ControlFlow<B>,
+	) -> Result<u64, Option<B>> {
+		self.seed(seed);
+		for step in 0..max_steps {
+			if self.is_quiescent() {
+				return Ok(step);
+			}
+			self.apply_one_match();
+			if let ControlFlow::Break(stopped) = after_step(self) {
+				return Err(Some(stopped));
+			}
+		}
+		if self.is_quiescent() {
+			Ok(max_steps)
+		} else {
+			Err(None)
+		}
+	}
+
+	/// Change in entropy per step over the last `window` steps of `time_to_fixpoint`.
+	/// Negative while converging, zero once stable and positive while diverging.
+	pub fn convergence_speed(&self, window: usize) -> f64 {
+		let series = &self.stats.step_entropy;
+		let window = window.min(series.len().saturating_sub(1));
+		if window == 0 {
+			return 0.;
+		}
+		let last = series.len() - 1;
+		(series[last] - series[last - window]) / window as f64
+	}
+
+	/// Seed the dish from `rng` and run up to `max_steps` calls of `apply_one_match`,
+	/// passing each to `observer.on_step_complete` and stopping once it asks to.
+	/// The observer is not attached, so it gets no cell or rule events.
+	/// Returns the number of steps run.
+	pub fn simulate_with_observer_and_early_exit<O: EarlyExitObserver>(
+		&mut self,
+		observer: &mut O,
+		max_steps: usize,
+		rng: &mut impl Rng,
+	) -> u64 {
+		self.seed(rng.gen());
+		let mut steps = 0;
+		while steps < max_steps as u64 {
+			self.apply_one_match();
+			steps += 1;
+			self.record_step_entropy();
+			self.snapshot_stats();
+			observer.on_step_complete(self, &self.stats);
+			if observer.should_stop() {
+				break;
+			}
+		}
+		steps
+	}
+
+	/// Seed the dish, simulate `max_steps` steps and return `(x, y, period)` for every cell that repeats
+	/// with `period` or one of its divisors over the second half of the run, allowing for a few missed updates.
+	/// Constant cells are left out.
+	/// Each step is a `step_batch` of one match per world cell, roughly one generation.
+	/// Slow, the UI runs it on a copy of the dish in another thread.
+	pub fn find_oscillating_patterns(
+		&mut self,
+		period: u64,
+		max_steps: u64,
+		seed: u64,
+	) -> Vec<(usize, usize, u64)> {
+		self.seed(seed);
+		let (width, height) = (self.world.width, self.world.height);
+		// the first half is not recorded, so patterns have time to settle
+		let settle_steps = max_steps / 2;
+		let mut settled = Vec::with_capacity((max_steps - settle_steps) as usize);
+		for step in 0..max_steps {
+			self.step_batch(width * height);
+			if step >= settle_steps {
+				settled.push(self.get_region_flat(0, 0, width, height));
+			}
+		}
+		let divisors: Vec<u64> = (2..=period).filter(|&d| period.is_multiple_of(d)).collect();
+		let mut found = Vec::new();
+		let mut series = Vec::with_capacity(settled.len());
+		for y in 0..height {
+			for x in 0..width {
+				series.clear();
+				series.extend(settled.iter().map(|cells| cells[x + y * width]));
+				if series.iter().all(|&c| c == series[0]) {
+					continue;
+				}
+				let detected = divisors
+					.iter()
+					.find(|&&lag| autocorrelation(&series, lag as usize) >= OSCILLATION_THRESHOLD);
+				if let Some(&lag) = detected {
+					found.push((x, y, lag));
+				}
+			}
+		}
+		found
+	}
+
+	/// a random member of the group, None if the group is empty or does not exist
+	pub fn random_from_group(&mut self, group_index: usize) -> Option<Cell> {
+		let group = self.groups.get(group_index)?;
+		let cells: Vec<Cell> = group.placeable_cells().collect();
+		cells.choose(&mut self.rng).copied()
+	}
+
+	/// returns None for positions outside the world
+	pub fn get_cell_isize(&self, x: isize, y: isize) -> Option<Cell> {
+		self.world.get_cell(x, y)
+	}
+
+	/// writes outside the world are ignored
+	pub fn set_cell_isize(&mut self, x: isize, y: isize, cell: Cell) {
+		if self.is_in_bounds(x, y) {
+			self.set_cell(x as usize, y as usize, cell);
+		}
+	}
+}
+
+/// width and height in pixels of a rule drawn by `Dish::svg_rule`
+fn svg_rule_size(rule: &Rule, size: u32) -> (u32, u32) {
+	(
+		(rule.base.width as u32 * 2 + 1) * size,
+		rule.base.height as u32 * size,
+	)
+}
+
+fn svg_header(width: u32, height: u32) -> String {
+	format!(
+		"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+	)
+}
+
+fn svg_cell(svg: &mut String, x: u32, y: u32, size: u32, style: &str, title: &str) {
+	*svg += &format!(
+		"<rect x=\"{x}\" y=\"{y}\" width=\"{size}\" height=\"{size}\" {style}><title>{}</title></rect>\n",
+		xml_escape(title)
+	);
+}
+
+fn xml_escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+/// the shortest period the whole series repeats with, if it is at most half its length
+fn period_of(series: &[u64]) -> Option<u64> {
+	(1..=series.len() / 2)
+		.find(|&p| (p..series.len()).all(|i| series[i] == series[i - p]))
+		.map(|p| p as u64)
+}
+
+/// fraction of positions where the series equals itself `lag` steps later, 0 if it is too short to tell
+fn autocorrelation(series: &[Cell], lag: usize) -> f64 {
+	if lag == 0 || series.len() <= lag {
+		return 0.;
+	}
+	let same = series
+		.iter()
+		.zip(&series[lag..])
+		.filter(|(a, b)| a == b)
+		.count();
+	same as f64 / (series.len() - lag) as f64
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		collections::HashMap,
+		sync::{atomic::AtomicUsize, Arc},
+		time::Duration,
+	};
+
+	use quick_xml::events::Event;
+
+	use super::*;
+	use crate::dynamic::TeleportToTop;
+
+	fn rule_json(width: usize, height: usize, contents: &str) -> String {
+		format!(
+			r#"[{{"name":"r","base":{{"width":{width},"height":{height},"contents":{contents}}},"enabled":true,"flip_x":false,"flip_y":false,"rotate":false}}]"#
+		)
+	}
+
+	#[test]
+	fn import_rejects_malformed_patterns() {
+		let mut dish = Dish::new();
+		let rule_count = dish.rules.len();
+		let cell = r#"[{"One":0},{"One":1}]"#;
+
+		let truncated = rule_json(1, 2, &format!("[{cell}]"));
+		assert_eq!(
+			dish.import_rules_from_json_array(&truncated),
+			Err(ImportError::WrongCellCount {
+				rule: 0,
+				expected: 2,
+				got: 1
+			})
+		);
+		assert_eq!(
+			dish.import_rules_from_json_array(&rule_json(0, 1, "[]")),
+			Err(ImportError::EmptyPattern { rule: 0 })
+		);
+		let copy = rule_json(1, 1, r#"[["Any",{"Copy":[0,3]}]]"#);
+		assert_eq!(
+			dish.import_rules_from_json_array(&copy),
+			Err(ImportError::InvalidCopyRef {
+				rule: 0,
+				x: 0,
+				y: 3
+			})
+		);
+		assert_eq!(dish.rules.len(), rule_count);
+
+		let valid = rule_json(1, 2, &format!("[{cell},{cell}]"));
+		assert_eq!(dish.import_rules_from_json_array(&valid), Ok(()));
+		assert_eq!(dish.rules.len(), rule_count + 1);
+	}
+
+	#[test]
+	fn resize_to_zero_keeps_frozen_cells_in_bounds() {
+		let mut dish = Dish::new();
+		dish.set_frozen(0, 0, true);
+		dish.resize_world(0, 8, Anchor::TopLeft);
+		assert_eq!((dish.world_width(), dish.world_height()), (1, 8));
+		assert!(dish.is_frozen(0, 0));
+		assert!(!dish.is_frozen(0, 7));
+	}
+
+	/// an enabled rule from `dsl::parse_rule` text
+	fn enabled_rule(pattern: &str) -> Rule {
+		let mut rule = crate::dsl::parse_rule(pattern).unwrap();
+		rule.enabled = true;
+		rule
+	}
+
+	/// a default dish with random sand, the same for equal seeds
+	fn sand_dish(seed: u64) -> Dish {
+		let mut dish = Dish::new();
+		let cells = [(Cell(0), 1.), (Cell(1), 1.)];
+		dish.randomize_world(&cells, &mut StdRng::seed_from_u64(seed));
+		dish
+	}
+
+	#[test]
+	fn parallel_worlds_match_sequential_runs() {
+		let steps = 300;
+		let worlds = (0..4).map(sand_dish).collect();
+		let parallel = Dish::run_parallel_worlds_with_stats(worlds, steps, u64::MAX - 1);
+		for (i, (dish, stats)) in parallel.iter().enumerate() {
+			let mut sequential = sand_dish(i as u64);
+			sequential.seed((u64::MAX - 1).wrapping_add(i as u64));
+			assert_eq!(stats.len(), steps);
+			for step_stats in stats {
+				sequential.apply_one_match();
+				assert_eq!(step_stats.world_hash, sequential.world_hash());
+				assert_eq!(step_stats.entropy, [sequential.world_entropy()]);
+			}
+			assert_eq!(dish.world_hash(), sequential.world_hash());
+		}
+	}
+
+	#[test]
+	fn fixpoint_of_settling_and_moving_worlds() {
+		// every step removes a cell, so entropy keeps changing until the world is empty
+		let settling = || {
+			let mut dish = sand_dish(5);
+			dish.rules = vec![enabled_rule("1 -> 0")];
+			dish.update_all_rules();
+			dish
+		};
+		let reached = settling().time_to_fixpoint(100_000, 7);
+		let FixpointResult::Reached { steps } = reached else {
+			panic!("{reached:?}");
+		};
+		assert_eq!(
+			settling().run_until_stable(100_000, 7),
+			Some(steps as usize)
+		);
+		assert_eq!(settling().run_until_stable(steps as usize - 1, 7), None);
+
+		let mut moving = Dish::new();
+		let mut rule = enabled_rule("1 0 -> 0 1");
+		rule.flip_x = true;
+		moving.rules = vec![rule];
+		moving.update_all_rules();
+		moving.set_cell(3, 3, Cell(1));
+		let result = moving.time_to_fixpoint(10_000, 7);
+		assert!(
+			matches!(result, FixpointResult::NotReached { .. }),
+			"{result:?}"
+		);
+		assert_eq!(moving.convergence_speed(50), 0.);
+		assert_eq!(moving.run_until_stable(1000, 7), None);
+	}
+
+	fn assert_tile_counts(dish: &Dish) {
+		let tiles = Tiles::new(&dish.world);
+		for entry in &dish.cache {
+			let mut expected = vec![0; tiles.count()];
+			for &pos in &entry.matches {
+				expected[tiles.of(pos)] += 1;
+			}
+			assert_eq!(entry.tile_counts, expected);
+		}
+	}
+
+	#[test]
+	fn stratified_selection_keeps_tile_counts() {
+		let mut dish = Dish::new();
+		dish.resize_world(80, 70, Anchor::TopLeft);
+		let cells = [(Cell(0), 3.), (Cell(1), 1.)];
+		dish.randomize_world(&cells, &mut StdRng::seed_from_u64(1));
+		dish.seed(2);
+		dish.selection = SelectionStrategy::Stratified;
+		assert_tile_counts(&dish);
+		for _ in 0..5000 {
+			dish.apply_one_match();
+		}
+		assert_tile_counts(&dish);
+		assert!(dish.verify_cache_integrity().is_empty());
+		dish.compact_cache();
+		assert_tile_counts(&dish);
+	}
+
+	#[test]
+	fn merge_and_remove_groups() {
+		let mut dish = Dish::new();
+		let first = dish.add_group(CellGroup {
+			cells: vec![Cell(0)],
+			..Default::default()
+		});
+		let second = dish.add_group(CellGroup {
+			matches_out_of_bounds: true,
+			cells: vec![Cell(0), Cell(1)],
+			..Default::default()
+		});
+		assert_eq!(
+			dish.merge_groups(first, 99, "bad"),
+			Err(RemoveGroupError::InvalidIndex)
+		);
+		let merged = dish.merge_groups(first, second, "both").unwrap();
+		assert_eq!(dish.groups[merged].cells, vec![Cell(0), Cell(1)]);
+		assert!(dish.groups[merged].matches_out_of_bounds);
+
+		dish.rules
+			.push(enabled_rule(&format!("1 -> g{first}\ng{second} -> 1")));
+		let rule_index = dish.rules.len() - 1;
+		dish.update_all_rules();
+		assert_eq!(
+			dish.remove_group(second),
+			Err(RemoveGroupError::UsedByRule { rule_index })
+		);
+		dish.remove_group_force(second);
+		let rule = &dish.rules[rule_index];
+		assert_eq!(
+			rule.get(0, 0),
+			(RuleCellFrom::One(Cell(1)), RuleCellTo::GroupRandom(first))
+		);
+		assert_eq!(
+			rule.get(0, 1),
+			(RuleCellFrom::Any, RuleCellTo::One(Cell(1)))
+		);
+		// the merged group moved down into the removed one's place
+		assert_eq!(dish.groups[second].name, "both");
+		for _ in 0..100 {
+			dish.apply_one_match();
+		}
+	}
+
+	#[test]
+	fn entropy_of_uniform_and_split_worlds() {
+		let mut dish = Dish::new();
+		// not a multiple of the chunk size, so edge chunks hang over the world
+		dish.resize_world(CHUNK_SIZE + 3, 20, Anchor::TopLeft);
+		dish.fill(Cell(1));
+		assert_eq!(dish.world_entropy(), 0.);
+		dish.fill(Cell(0));
+		assert_eq!(dish.world_entropy(), 0.);
+
+		let half = dish.world_width() / 2;
+		dish.resize_world(half * 2, 20, Anchor::TopLeft);
+		dish.map_cells(|x, _, _| Cell((x < half) as u16));
+		assert_eq!(dish.world_entropy(), 1.);
+		assert_eq!(dish.world_entropy_normalized(), 1.);
+	}
+
+	#[test]
+	fn entropy_is_recorded_every_step() {
+		let mut dish = Dish::new();
+		dish.rules = vec![enabled_rule("0 -> 1")];
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		dish.seed(653);
+		for step in 1..=10 {
+			dish.step(StepMode::Phased, 1);
+			assert_eq!(dish.stats.entropy.len(), step);
+			assert_eq!(dish.stats.entropy.back(), Some(&dish.world_entropy()));
+		}
+		// each step turns one more cell, which raises the entropy while they are few
+		assert!(dish.entropy_change_rate() > 0.);
+		dish.step(StepMode::Random, ENTROPY_HISTORY);
+		assert_eq!(dish.stats.entropy.len(), ENTROPY_HISTORY);
+		assert_eq!(dish.stats.entropy.back(), Some(&dish.world_entropy()));
+	}
+
+	#[test]
+	fn boundary_void_builds_a_floor() {
+		let mut dish = Dish::new();
+		let wall = dish.add_type(CellData::new("wall", 80, 80, 80));
+		// air right above the bottom edge turns into wall
+		dish.rules = vec![enabled_rule(&format!("0 -> {}\n# -> _", wall.0))];
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		assert!(dish.run_until_stable(100_000, 3).is_some());
+
+		let (width, height) = (dish.world_width(), dish.world_height());
+		for y in 0..height {
+			for x in 0..width {
+				let expected = if y == height - 1 { wall } else { Cell(0) };
+				assert_eq!(dish.get_cell(x, y), Some(expected), "{x}, {y}");
+			}
+		}
+	}
+
+	#[test]
+	fn isize_cell_access_outside_the_world() {
+		let mut dish = Dish::new();
+		dish.fill(Cell(0));
+		let (w, h) = (dish.world_width() as isize, dish.world_height() as isize);
+		for (x, y) in [(-1, 0), (0, -1), (-1, -1), (isize::MIN, 3), (w, 0), (0, h)] {
+			assert_eq!(dish.get_cell_isize(x, y), None, "{x}, {y}");
+			dish.set_cell_isize(x, y, Cell(1));
+		}
+		assert_eq!(dish.count_cells(Cell(1)), 0);
+
+		dish.set_cell_isize(w - 1, h - 1, Cell(1));
+		assert_eq!(dish.get_cell_isize(w - 1, h - 1), Some(Cell(1)));
+		assert_eq!(dish.get_cell(w as usize - 1, h as usize - 1), Some(Cell(1)));
+		assert!(dish.verify_cache_integrity().is_empty());
+	}
+
+	#[test]
+	fn cell_shapes_do_not_change_the_simulation() {
+		let run = |shape| {
+			let mut dish = sand_dish(4);
+			dish.types[1].shape = shape;
+			dish.seed(9);
+			for _ in 0..500 {
+				dish.apply_one_match();
+			}
+			dish.world_hash()
+		};
+		assert_eq!(run(crate::CellShape::Square), run(crate::CellShape::Circle));
+	}
+
+	#[test]
+	fn fill_bucket_stops_at_max_cells() {
+		let mut dish = Dish::new();
+		dish.fill(Cell(0));
+		assert_eq!(dish.paint_fill_bucket(10, 10, Cell(1), 5), 5);
+		assert_eq!(dish.count_cells(Cell(1)), 5);
+		// the closest cells are painted first
+		for (x, y) in [(10, 10), (9, 10), (11, 10), (10, 9), (10, 11)] {
+			assert_eq!(dish.get_cell(x, y), Some(Cell(1)), "{x}, {y}");
+		}
+		assert!(dish.verify_cache_integrity().is_empty());
+
+		// a wall splits the world, so the region is smaller than the limit
+		dish.fill(Cell(0));
+		let height = dish.world_height();
+		for y in 0..height {
+			dish.set_cell(4, y, Cell(1));
+		}
+		assert_eq!(
+			dish.paint_fill_bucket(0, 0, Cell(1), usize::MAX),
+			4 * height
+		);
+		assert_eq!(dish.get_cell(5, 0), Some(Cell(0)));
+		assert_eq!(dish.paint_fill_bucket(0, 0, Cell(0), 0), 0);
+	}
+
+	#[test]
+	fn resize_keeps_contents_at_the_anchor() {
+		const ANCHORS: [Anchor; 9] = [
+			Anchor::TopLeft,
+			Anchor::Top,
+			Anchor::TopRight,
+			Anchor::Left,
+			Anchor::Center,
+			Anchor::Right,
+			Anchor::BottomLeft,
+			Anchor::Bottom,
+			Anchor::BottomRight,
+		];
+		assert_eq!(Anchor::Center.offset((4, 4), (8, 6)), (2, 1));
+		assert_eq!(Anchor::BottomRight.offset((4, 4), (2, 3)), (-2, -1));
+		assert_eq!(Anchor::Top.offset((4, 4), (1, 1)), (-1, 0));
+
+		let fill = Cell(20);
+		// grow, shrink, and grow one side while shrinking the other
+		for (new_width, new_height) in [(8, 6), (2, 3), (7, 1)] {
+			for anchor in ANCHORS {
+				let mut dish = Dish::new();
+				dish.rules.clear();
+				dish.resize_world(4, 4, Anchor::TopLeft);
+				dish.map_cells(|x, y, _| Cell((x + y * 4) as u16));
+				dish.resize_world_with(new_width, new_height, anchor, fill);
+				assert_eq!(
+					(dish.world_width(), dish.world_height()),
+					(new_width, new_height)
+				);
+
+				let (dx, dy) = anchor.offset((4, 4), (new_width, new_height));
+				for x in 0..new_width {
+					for y in 0..new_height {
+						let (ox, oy) = (x as isize - dx, y as isize - dy);
+						let expected = if (0..4).contains(&ox) && (0..4).contains(&oy) {
+							Cell((ox + oy * 4) as u16)
+						} else {
+							fill
+						};
+						assert_eq!(dish.get_cell(x, y), Some(expected), "{anchor:?} {x}, {y}");
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn randomize_region_follows_weights() {
+		let mut dish = Dish::new();
+		dish.ensure_minimum_types(5);
+		dish.fill(Cell(0));
+		let height = dish.world_height();
+		let cells = [(Cell(1), 1.), (Cell(2), 3.), (Cell(3), 0.), (Cell(4), -2.)];
+		let mut rng = StdRng::seed_from_u64(11);
+		let mut counts = [0usize; 5];
+		for _ in 0..100 {
+			dish.randomize_region(4, 2, 10, 20, &cells, &mut rng);
+			for (id, count) in counts.iter_mut().enumerate() {
+				*count += dish.census_region(4, 2, 10, 20)[id] as usize;
+			}
+			// nothing outside the region is touched
+			assert_eq!(dish.census_region(0, 0, 4, height)[0], 4 * height as u64);
+			let right = dish.world_width() - 14;
+			assert_eq!(
+				dish.census_region(14, 0, right, height)[0],
+				(right * height) as u64
+			);
+		}
+		let total = (100 * 10 * 20) as f64;
+		assert!((counts[1] as f64 / total - 0.25).abs() < 0.05, "{counts:?}");
+		assert!((counts[2] as f64 / total - 0.75).abs() < 0.05, "{counts:?}");
+		assert_eq!(counts[3] + counts[4], 0);
+		assert!(dish.verify_cache_integrity().is_empty());
+	}
+
+	#[test]
+	fn variant_priority_biases_selection() {
+		// share of steps a lone cell moves right, the direction of variant 0
+		let moves_right = |priority: Option<Vec<usize>>| {
+			let mut dish = Dish::new();
+			let mut rule = enabled_rule("1 0 -> 0 1");
+			rule.rotate = true;
+			rule.generate_variants();
+			assert_eq!(rule.variants().len(), 4);
+			dish.rules = vec![rule];
+			dish.update_all_rules();
+			// regenerating the variants resets the priority, so set it afterwards
+			if let Some(order) = priority {
+				dish.rules[0].set_variant_priority(order).unwrap();
+			}
+			dish.fill(Cell(0));
+			dish.seed(5);
+			let mut right = 0;
+			for _ in 0..4000 {
+				dish.set_cell(10, 10, Cell(1));
+				dish.update_cache(9, 9, 3, 3);
+				dish.apply_one_match();
+				right += (dish.get_cell(11, 10) == Some(Cell(1))) as usize;
+				dish.fill(Cell(0));
+			}
+			right as f64 / 4000.
+		};
+		assert!((moves_right(None) - 0.25).abs() < 0.03);
+		// weights 4, 1, 2, 3 for the variants 0, 1, 2, 3
+		let order = vec![0, 3, 2, 1];
+		assert!((moves_right(Some(order)) - 0.4).abs() < 0.03);
+		let order = vec![1, 2, 3, 0];
+		assert!((moves_right(Some(order)) - 0.1).abs() < 0.03);
+	}
+
+	/// `TeleportToTop` that counts how often it was applied
+	#[derive(Debug)]
+	struct CountedTeleport(TeleportToTop, Arc<AtomicUsize>);
+
+	impl DynamicRule for CountedTeleport {
+		fn find_matches(&self, world: &WorldView) -> Vec<DynMatch> {
+			self.0.find_matches(world)
+		}
+
+		fn apply(&self, world: &mut WorldWriter, m: &DynMatch) {
+			self.1.fetch_add(1, Ordering::Relaxed);
+			self.0.apply(world, m);
+		}
+
+		fn footprint(&self, world: &WorldView, m: &DynMatch) -> (isize, isize, usize, usize) {
+			self.0.footprint(world, m)
+		}
+
+		fn weight(&self) -> usize {
+			20
+		}
+	}
+
+	#[test]
+	fn dynamic_and_static_rules_keep_the_cache_valid() {
+		let mut dish = sand_dish(8);
+		dish.rules.retain(|r| r.name == "fall" || r.name == "slide");
+		dish.update_all_rules();
+		let applied = Arc::new(AtomicUsize::new(0));
+		let teleport = TeleportToTop {
+			cell: Cell(1),
+			empty: Cell(0),
+		};
+		dish.add_dynamic_rule(Box::new(CountedTeleport(teleport, applied.clone())));
+		dish.set_frozen(5, 0, true);
+		let frozen_cell = dish.get_cell(5, 0);
+		dish.set_cell(6, 20, Cell(1));
+		dish.set_frozen(6, 20, true);
+		let sand = dish.count_cells(Cell(1));
+		dish.seed(1);
+		for _ in 0..2000 {
+			dish.apply_one_match();
+			assert_eq!(dish.verify_cache_integrity(), vec![]);
+		}
+		assert!(applied.load(Ordering::Relaxed) > 0);
+		// both kinds of rule only move sand around
+		assert_eq!(dish.count_cells(Cell(1)), sand);
+		assert_eq!(dish.get_cell(5, 0), frozen_cell);
+		assert_eq!(dish.get_cell(6, 20), Some(Cell(1)));
+	}
+
+	#[test]
+	fn phases_take_turns() {
+		let mut dish = Dish::new();
+		dish.ensure_minimum_types(3);
+		let mut to_two = enabled_rule("1 -> 2");
+		let mut to_one = enabled_rule("2 -> 1");
+		to_two.phase = 0;
+		to_one.phase = 1;
+		dish.rules = vec![to_one, to_two];
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		for x in 0..10 {
+			dish.set_cell(x, 3, Cell(1));
+		}
+		dish.update_cache(0, 3, 10, 1);
+		dish.seed(4);
+		for step in 0..100 {
+			assert_eq!(dish.current_phase() as usize, step % 2);
+			dish.step_phased();
+			// phase 0 turns one 1 into a 2, phase 1 turns it back
+			assert_eq!(dish.count_cells(Cell(2)), 1 - step % 2, "step {step}");
+		}
+
+		let json = dish.export_rules_as_json_array().unwrap();
+		let rules: Vec<Rule> = serde_json::from_str(&json).unwrap();
+		assert_eq!(rules.iter().map(|r| r.phase).collect::<Vec<_>>(), [1, 0]);
+	}
+
+	#[test]
+	fn region_mask_limits_where_a_rule_fires() {
+		let mut dish = Dish::new();
+		dish.rules = vec![enabled_rule("0 -> 1")];
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		let mask = RegionMask {
+			x: 4,
+			y: 2,
+			w: 6,
+			h: 9,
+		};
+		dish.set_rule_region(0, Some(mask));
+		assert!(dish.run_until_stable(10_000, 1).is_some());
+		for x in 0..dish.world_width() {
+			for y in 0..dish.world_height() {
+				let inside = (4..10).contains(&x) && (2..11).contains(&y);
+				assert_eq!(dish.get_cell(x, y), Some(Cell(inside as u16)), "{x}, {y}");
+			}
+		}
+
+		dish.set_rule_region(0, None);
+		assert!(dish.run_until_stable(10_000, 1).is_some());
+		assert_eq!(dish.count_cells(Cell(0)), 0);
+	}
+
+	#[test]
+	fn buffer_matches_equal_cached_matches() {
+		let dish = sand_dish(3);
+		let (width, height) = (dish.world_width(), dish.world_height());
+		let buf = dish.get_region_flat(0, 0, width, height);
+		let mut cached = dish.matches_in_region(RegionMask {
+			x: 0,
+			y: 0,
+			w: width,
+			h: height,
+		});
+		cached.sort();
+		let mut found = Vec::new();
+		for (rule_index, rule) in dish.rules.iter().enumerate() {
+			if !rule.enabled {
+				continue;
+			}
+			for (variant_index, variant) in rule.variants().iter().enumerate() {
+				let (ox, oy) = variant.origin();
+				for (x, y) in variant.find_matches_in_buffer(&buf, width, height, &dish.groups) {
+					let origin = (x + ox as isize, y + oy as isize);
+					found.push((rule_index, variant_index, origin));
+				}
+			}
+		}
+		found.sort();
+		assert!(!found.is_empty());
+		assert_eq!(found, cached);
+
+		// with a single possible match both paths have to do the same thing
+		let mut dish = Dish::new();
+		dish.rules.retain(|r| r.name == "fall");
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		dish.set_cell(3, 3, Cell(1));
+		dish.update_cache(3, 3, 1, 1);
+		let from = dish.get_region_flat(0, 0, width, height);
+		let mut to = from.clone();
+		let applied = dish.rules[0].apply_to_buffer(
+			&from,
+			&mut to,
+			width,
+			height,
+			&dish.groups,
+			&mut StdRng::seed_from_u64(0),
+		);
+		assert!(applied);
+		dish.apply_one_match();
+		assert_eq!(to, dish.get_region_flat(0, 0, width, height));
+		assert_eq!(dish.get_cell(3, 4), Some(Cell(1)));
+	}
+
+	#[test]
+	fn text_matrix_round_trip() {
+		let mut dish = Dish::new();
+		dish.ensure_minimum_types(3);
+		dish.resize_world(7, 5, Anchor::TopLeft);
+		let cells = [(Cell(0), 1.), (Cell(1), 1.), (Cell(2), 1.)];
+		dish.randomize_world(&cells, &mut StdRng::seed_from_u64(2));
+		let chars = ".#~";
+		let text = dish.export_world_matrix_as_text(chars).unwrap();
+		assert_eq!(text.lines().count(), 5);
+		assert!(text.lines().all(|line| line.chars().count() == 7));
+		for ch in chars.chars() {
+			assert!(text.contains(ch), "{text}");
+		}
+
+		let mut copy = Dish::new();
+		copy.import_world_matrix_from_text(&text, chars).unwrap();
+		assert_eq!(copy.world_hash(), dish.world_hash());
+		assert_eq!(
+			dish.export_world_matrix_as_text(".#"),
+			Err(ExportError::NotEnoughChars { needed: 3, got: 2 })
+		);
+		assert_eq!(
+			copy.import_world_matrix_from_text("..\n.x", chars),
+			Err(ImportError::UnknownChar {
+				ch: 'x',
+				x: 1,
+				y: 1
+			})
+		);
+	}
+
+	/// Whether a rule with group 0 past `edge` and air inside fires on an empty world.
+	/// Checks that it then turns exactly the cells along that edge into sand.
+	fn group_fires_at_edge(group: CellGroup, edge: Edge) -> bool {
+		let pattern = match edge {
+			Edge::Top => "g0 -> _\n0 -> 1",
+			Edge::Bottom => "0 -> 1\ng0 -> _",
+			Edge::Left => "g0 0 -> _ 1",
+			Edge::Right => "0 g0 -> 1 _",
+		};
+		let mut dish = Dish::new();
+		dish.groups = vec![group];
+		dish.rules = vec![enabled_rule(pattern)];
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		assert!(dish.run_until_stable(100_000, 0).is_some());
+
+		let (w, h) = (dish.world_width(), dish.world_height());
+		let on_edge = |x: usize, y: usize| match edge {
+			Edge::Top => y == 0,
+			Edge::Bottom => y == h - 1,
+			Edge::Left => x == 0,
+			Edge::Right => x == w - 1,
+		};
+		let fired = dish.count_cells(Cell(1)) > 0;
+		for x in 0..w {
+			for y in 0..h {
+				let expected = Cell((fired && on_edge(x, y)) as u16);
+				assert_eq!(dish.get_cell(x, y), Some(expected), "{edge:?} {x}, {y}");
+			}
+		}
+		fired
+	}
+
+	#[test]
+	fn legacy_void_fields_at_world_edges() {
+		// (void, match_out_of_bounds) of older saves, and whether the group then matches outside the world
+		for (void, match_out_of_bounds, expected) in [
+			(false, "false", false),
+			(true, "false", false),
+			(false, "true", true),
+			(true, "true", true),
+			(false, "null", false),
+			(true, "null", true),
+		] {
+			let json = format!(
+				r#"{{"name":"g","void":{void},"match_out_of_bounds":{match_out_of_bounds},"cells":[]}}"#
+			);
+			let group: CellGroup = serde_json::from_str(&json).unwrap();
+			assert_eq!(group.matches_out_of_bounds, expected, "{json}");
+			for edge in [Edge::Top, Edge::Bottom, Edge::Left, Edge::Right] {
+				assert_eq!(
+					group_fires_at_edge(group.clone(), edge),
+					expected,
+					"{json} {edge:?}"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn out_of_bounds_membership_at_world_edges() {
+		for matches_out_of_bounds in [false, true] {
+			for boundary_member in [false, true] {
+				// a member that is never in the world, so only the outside can match
+				let mut cells = vec![Cell(2)];
+				if boundary_member {
+					cells.push(Cell::BOUNDARY);
+				}
+				let group = CellGroup {
+					matches_out_of_bounds,
+					cells,
+					..Default::default()
+				};
+				assert!(!group.placeable_cells().any(|c| c.is_boundary()));
+				let expected = matches_out_of_bounds || boundary_member;
+				for edge in [Edge::Top, Edge::Bottom, Edge::Left, Edge::Right] {
+					assert_eq!(
+						group_fires_at_edge(group.clone(), edge),
+						expected,
+						"{matches_out_of_bounds} {boundary_member} {edge:?}"
+					);
+				}
+			}
+		}
+
+		// the boundary pseudo-cell on its own only matches outside the world
+		let mut rule = enabled_rule("0 -> _\n0 -> 1");
+		rule.set_cell(0, 0, RuleCellFrom::One(Cell::BOUNDARY), RuleCellTo::None);
+		rule.rotate = true;
+		let mut dish = Dish::new();
+		dish.rules = vec![rule];
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		assert!(dish.run_until_stable(100_000, 0).is_some());
+		let (w, h) = (dish.world_width(), dish.world_height());
+		for x in 0..w {
+			for y in 0..h {
+				let border = x == 0 || y == 0 || x == w - 1 || y == h - 1;
+				assert_eq!(dish.get_cell(x, y), Some(Cell(border as u16)), "{x}, {y}");
+			}
+		}
+	}
+
+	#[test]
+	fn epochs_move_particles_more_evenly() {
+		// one sand cell at the top of every column, with room to fall
+		let falling = || {
+			let mut dish = Dish::new();
+			dish.rules.retain(|r| r.name == "fall");
+			dish.update_all_rules();
+			dish.resize_world(64, 400, Anchor::TopLeft);
+			dish.fill(Cell(0));
+			for x in 0..64 {
+				dish.set_cell(x, 0, Cell(1));
+			}
+			dish.rebuild_cache();
+			dish
+		};
+		let fallen = |dish: &Dish| -> Vec<f64> {
+			(0..64)
+				.map(|x| {
+					(0..400)
+						.find(|&y| dish.get_cell(x, y) == Some(Cell(1)))
+						.unwrap() as f64
+				})
+				.collect()
+		};
+		let spread = |distances: &[f64]| {
+			let mean = distances.iter().sum::<f64>() / distances.len() as f64;
+			let variance = distances.iter().map(|d| (d - mean).powi(2)).sum::<f64>();
+			(mean, (variance / distances.len() as f64).sqrt())
+		};
+
+		let mut epochs = falling();
+		let mut rng = StdRng::seed_from_u64(6);
+		let positions = 64 * 401;
+		for _ in 0..20 {
+			epochs.step_epoch(positions, &mut rng);
+		}
+		let (epoch_mean, epoch_spread) = spread(&fallen(&epochs));
+
+		// the same number of moves, picked at random
+		let mut random = falling();
+		random.seed(6);
+		let moves = (epoch_mean * 64.).round() as usize;
+		for _ in 0..moves {
+			random.apply_one_match();
+		}
+		let (random_mean, random_spread) = spread(&fallen(&random));
+		assert!((random_mean - epoch_mean).abs() < 0.1);
+		// a cell can move more than once per epoch, so the spread doesn't go away entirely
+		assert!(
+			epoch_spread < random_spread * 0.8,
+			"{epoch_spread} {random_spread}"
+		);
+	}
+
+	#[test]
+	fn map_cells_swap_matches_swap_cell_ids() {
+		let mut mapped = sand_dish(12);
+		let mut swapped = sand_dish(12);
+		mapped.map_cells(|_, _, cell| match cell {
+			Cell(0) => Cell(1),
+			Cell(1) => Cell(0),
+			other => other,
+		});
+		swapped.swap_cell_ids(Cell(0), Cell(1));
+		assert_eq!(mapped.world_hash(), swapped.world_hash());
+		assert!(mapped.verify_cache_integrity().is_empty());
+
+		// map_cells passes the position along
+		mapped.map_cells(|x, y, _| Cell(((x + y) % 2) as u16));
+		assert_eq!(mapped.get_cell(3, 4), Some(Cell(1)));
+		assert_eq!(mapped.get_cell(4, 4), Some(Cell(0)));
+	}
+
+	#[test]
+	fn fill_with_the_same_content_keeps_the_cache() {
+		let mut dish = Dish::new();
+		dish.fill(Cell(0));
+		let cache_state = |dish: &Dish| -> Vec<(*const (isize, isize), usize)> {
+			dish.cache
+				.iter()
+				.map(|c| (c.matches.as_ptr(), c.matches.len()))
+				.collect()
+		};
+		let before = cache_state(&dish);
+		let revision = dish.world.revision;
+		dish.reset_frame_counters();
+		dish.fill(Cell(0));
+		assert_eq!(dish.frame_counters(), FrameCounters::default());
+		assert_eq!(dish.world.revision, revision);
+		assert_eq!(cache_state(&dish), before);
+
+		// a single changed cell only rescans around it
+		dish.reset_frame_counters();
+		dish.rebuild_cache();
+		let full = dish.frame_counters().matches_examined;
+		dish.set_cell(7, 7, Cell(1));
+		dish.update_cache(7, 7, 1, 1);
+		dish.reset_frame_counters();
+		dish.fill(Cell(0));
+		let examined = dish.frame_counters().matches_examined;
+		assert!(examined > 0 && examined * 20 < full, "{examined} of {full}");
+		assert!(dish.verify_cache_integrity().is_empty());
+	}
+
+	#[test]
+	fn nearest_cell_type_by_color() {
+		let mut dish = Dish::new();
+		assert_eq!(dish.nearest_cell_type([255, 147, 219]), Some(Cell(1)));
+		assert_eq!(dish.nearest_cell_type([230, 160, 200]), Some(Cell(1)));
+		assert_eq!(dish.nearest_cell_type([20, 10, 30]), Some(Cell(0)));
+		assert_eq!(
+			dish.nearest_cell_type_below_distance([230, 160, 200], 10.),
+			None
+		);
+
+		let (air, sand) = (&dish.types[0], &dish.types[1]);
+		let expected = ((255f32).powi(2) + (147f32).powi(2) + (219f32).powi(2)).sqrt();
+		assert_eq!(air.color_distance(sand), expected);
+		assert_eq!(sand.color_distance(air), expected);
+		assert_eq!(sand.color_distance(sand), 0.);
+
+		dish.remove_all_types();
+		assert_eq!(dish.nearest_cell_type([255, 147, 219]), None);
+	}
+
+	#[test]
+	fn rules_json_round_trip_and_bad_refs() {
+		let source = Dish::new();
+		let json = source.export_rules_as_json_array().unwrap();
+		let mut target = Dish::new();
+		target.rules.clear();
+		target.update_all_rules();
+		assert_eq!(target.import_rules_from_json_array(&json), Ok(()));
+		assert_eq!(target.export_rules_as_json_array().unwrap(), json);
+		assert_eq!(target.rules_hash(), source.rules_hash());
+
+		let good = crate::dsl::parse_rule("1 -> 0").unwrap();
+		let bad_cell = crate::dsl::parse_rule("1 -> 5").unwrap();
+		let bad_group = crate::dsl::parse_rule("g3 -> 0").unwrap();
+		let json = |rules: &[&Rule]| serde_json::to_string(rules).unwrap();
+		let count = target.rules.len();
+		assert_eq!(
+			target.import_rules_from_json_array(&json(&[&good, &bad_cell])),
+			Err(ImportError::InvalidCellRef {
+				rule: 1,
+				cell: Cell(5)
+			})
+		);
+		assert_eq!(
+			target.import_rules_from_json_array(&json(&[&bad_group])),
+			Err(ImportError::InvalidGroupRef { rule: 0, group: 3 })
+		);
+		assert!(matches!(
+			target.import_rules_from_json_array("[{"),
+			Err(ImportError::Json(_))
+		));
+		// nothing from a failed import is kept
+		assert_eq!(target.rules.len(), count);
+	}
+
+	#[test]
+	fn painting_between_steps() {
+		let mut dish = sand_dish(13);
+		dish.seed(13);
+		let mut rng = StdRng::seed_from_u64(14);
+		let (w, h) = (dish.world_width(), dish.world_height());
+		for _ in 0..3000 {
+			let (x, y) = (rng.gen_range(0..w), rng.gen_range(0..h));
+			let cell = Cell(rng.gen_range(0..2));
+			match rng.gen_range(0..8) {
+				0 => dish.fill(cell),
+				1 => {
+					dish.paint_fill_bucket(x, y, cell, 40);
+				}
+				2 => dish.randomize_region(x, y, 5, 5, &[(cell, 1.)], &mut rng),
+				3 => {
+					// painting without updating the cache, like a brush stroke in progress
+					dish.set_cell(x, y, cell);
+				}
+				4 => {
+					let rule = rng.gen_range(0..dish.rules.len());
+					dish.rules[rule].enabled ^= true;
+					dish.update_cache_single_rule(rule);
+				}
+				5 => dish.set_frozen(x, y, rng.gen()),
+				6 => {
+					// move a rule to the end
+					let rule = dish.remove_rule(rng.gen_range(0..dish.rules.len()));
+					dish.rules.push(rule);
+					dish.cache_last_added_rule();
+				}
+				_ => dish.update_cache(x as isize, y as isize, 1, 1),
+			}
+			for _ in 0..rng.gen_range(0..5) {
+				dish.apply_one_match();
+			}
+		}
+		dish.rebuild_cache();
+		assert!(dish.verify_cache_integrity().is_empty());
+	}
+
+	#[test]
+	fn swap_cell_ids_keeps_the_dish_equivalent() {
+		let original = sand_dish(15);
+		let (w, h) = (original.world_width(), original.world_height());
+		let mut dish = sand_dish(15);
+		dish.swap_cell_ids(Cell(0), Cell(1));
+		for x in 0..w {
+			for y in 0..h {
+				let swapped = Cell(1 - original.get_cell(x, y).unwrap().0);
+				assert_eq!(dish.get_cell(x, y), Some(swapped));
+			}
+		}
+		let counts = original.census_region(0, 0, w, h);
+		assert_eq!(dish.census_region(0, 0, w, h), [counts[1], counts[0]]);
+		assert_eq!(dish.types[0].name, "pink_sand");
+		assert_eq!(
+			dish.rules[0].get(0, 0),
+			(RuleCellFrom::One(Cell(0)), RuleCellTo::One(Cell(1)))
+		);
+		assert_eq!(dish.groups[0].cells, [Cell(1)]);
+
+		// sorting by name swaps them back
+		dish.normalize_cell_ordering();
+		assert_eq!(dish.world_hash(), original.world_hash());
+		assert_eq!(dish.rules_hash(), original.rules_hash());
+	}
+
+	#[test]
+	fn group_random_other_always_changes_the_cell() {
+		let mut dish = Dish::new();
+		dish.ensure_minimum_types(3);
+		let all = dish.add_group(CellGroup {
+			cells: vec![Cell(0), Cell(1), Cell(2)],
+			..Default::default()
+		});
+		let single = dish.add_group(CellGroup {
+			cells: vec![Cell(1)],
+			..Default::default()
+		});
+		let mut rule = enabled_rule(&format!("g{all} * -> g{all}! g{single}!"));
+		rule.flip_x = true;
+		dish.rules = vec![rule];
+		dish.update_all_rules();
+		assert!(dish.rules[0].variants().iter().all(|v| v
+			.contents
+			.contains(&(RuleCellFrom::Group(all), RuleCellTo::GroupRandomOther(all)))));
+		let cells = [(Cell(0), 1.), (Cell(1), 1.), (Cell(2), 1.)];
+		dish.randomize_world(&cells, &mut StdRng::seed_from_u64(16));
+		dish.seed(16);
+		let mut rng = StdRng::seed_from_u64(17);
+		for _ in 0..2000 {
+			let x = rng.gen_range(0..dish.world_width() as isize - 1);
+			let y = rng.gen_range(0..dish.world_height() as isize);
+			let trace = dish.apply_match_traced(0, 0, (x, y));
+			assert!(trace.applied);
+			assert_ne!(trace.new_state[0], trace.old_state[0]);
+			// the only member of the single group can't differ from itself
+			if trace.old_state[1] == Some(Cell(1)) {
+				assert_eq!(trace.new_state[1], Some(Cell(1)));
+			} else {
+				assert_ne!(trace.new_state[1], trace.old_state[1]);
+			}
+		}
+
+		let json = dish.export_rules_as_json_array().unwrap();
+		let rules: Vec<Rule> = serde_json::from_str(&json).unwrap();
+		assert_eq!(rules[0].get(0, 0).1, RuleCellTo::GroupRandomOther(all));
+	}
+
+	#[test]
+	fn cache_rebuild_progress() {
+		let mut dish = sand_dish(18);
+		let variants: usize = dish
+			.rules
+			.iter()
+			.filter(|r| r.enabled)
+			.map(|r| r.variants().len())
+			.sum();
+		assert!(variants > 1);
+		let mut calls = Vec::new();
+		dish.rebuild_cache_with_progress(|done, total| calls.push((done, total)));
+		let expected: Vec<_> = (1..=variants).map(|done| (done, variants)).collect();
+		assert_eq!(calls, expected);
+
+		let rect = RegionMask {
+			x: 0,
+			y: 0,
+			w: dish.world_width(),
+			h: dish.world_height(),
+		};
+		let full = dish.matches_in_region(rect);
+		// without a time budget every call scans exactly one variant
+		dish.start_cache_rebuild();
+		for done in 1..variants {
+			assert_eq!(
+				dish.continue_cache_rebuild(Duration::ZERO),
+				Some((done, variants))
+			);
+		}
+		assert_eq!(dish.continue_cache_rebuild(Duration::ZERO), None);
+		assert_eq!(dish.continue_cache_rebuild(Duration::ZERO), None);
+		assert_eq!(dish.matches_in_region(rect), full);
+	}
+
+	#[test]
+	fn region_round_trip() {
+		let mut dish = sand_dish(19);
+		let hash = dish.world_hash();
+		let region = dish.get_region(3, 5, 10, 7);
+		assert_eq!((region.len(), region[0].len()), (7, 10));
+		assert_eq!(region[2][4], dish.get_cell(7, 7).unwrap());
+		dish.set_region(3, 5, &region);
+		assert_eq!(dish.world_hash(), hash);
+
+		// copying a region elsewhere, partly past the edge
+		let (w, h) = (dish.world_width(), dish.world_height());
+		dish.set_region(w - 4, h - 2, &region);
+		assert_eq!(dish.world_width(), w);
+		assert_eq!(
+			dish.get_region(w - 4, h - 2, 4, 2),
+			[&region[0][..4], &region[1][..4]]
+		);
+		// outside the world reads as Cell(0)
+		assert_eq!(
+			dish.get_region(w - 1, 0, 2, 1),
+			[[dish.get_cell(w - 1, 0).unwrap(), Cell(0)]]
+		);
+		assert!(dish.verify_cache_integrity().is_empty());
+	}
+
+	#[test]
+	fn compact_cache_repairs_every_error_kind() {
+		let mut dish = sand_dish(20);
+		dish.seed(20);
+		for _ in 0..500 {
+			dish.apply_one_match();
+			assert_eq!(dish.verify_cache_integrity(), vec![]);
+		}
+		let rect = RegionMask {
+			x: 0,
+			y: 0,
+			w: dish.world_width(),
+			h: dish.world_height(),
+		};
+		let mut matches = dish.matches_in_region(rect);
+		matches.sort();
+
+		let tiles = Tiles::new(&dish.world);
+		let (rule, variant) = (dish.cache[0].rule, dish.cache[0].variant);
+		let pos = dish.cache[0].matches[0];
+		dish.cache[0].push(pos, tiles);
+		dish.cache
+			.push(RuleCache::new(rule, variant, vec![pos], tiles));
+		dish.cache.push(RuleCache::new(99, 0, vec![(1, 1)], tiles));
+		let last = dish.cache.len() - 1;
+		assert_eq!(
+			dish.verify_cache_integrity(),
+			[
+				CacheError::DuplicateMatch {
+					cache_index: 0,
+					pos
+				},
+				CacheError::DuplicateEntry {
+					cache_index: last - 1,
+					rule,
+					variant
+				},
+				CacheError::OrphanRule {
+					cache_index: last,
+					rule: 99
+				},
+			]
+		);
+
+		dish.compact_cache();
+		assert_eq!(dish.verify_cache_integrity(), vec![]);
+		let mut compacted = dish.matches_in_region(rect);
+		compacted.sort();
+		assert_eq!(compacted, matches);
+		for _ in 0..500 {
+			dish.apply_one_match();
+			assert_eq!(dish.verify_cache_integrity(), vec![]);
+		}
+	}
+
+	#[test]
+	fn dirty_chunks_are_exactly_the_changed_ones() {
+		fn snapshot(dish: &Dish) -> Vec<Cell> {
+			let (w, h) = (dish.world_width(), dish.world_height());
+			(0..w * h)
+				.map(|i| dish.get_cell(i % w, i / w).unwrap())
+				.collect()
+		}
+		fn changed_chunks(dish: &Dish, before: &[Cell]) -> Vec<(usize, usize)> {
+			let w = dish.world_width();
+			let mut chunks: Vec<_> = (before.iter().zip(snapshot(dish)).enumerate())
+				.filter(|(_, (a, b))| **a != *b)
+				.map(|(i, _)| (i % w / CHUNK_SIZE, i / w / CHUNK_SIZE))
+				.collect();
+			chunks.sort();
+			chunks.dedup();
+			chunks
+		}
+		fn dirty(dish: &mut Dish) -> Vec<(usize, usize)> {
+			let mut chunks = dish.take_dirty_chunks();
+			chunks.sort();
+			chunks
+		}
+
+		let mut dish = Dish::new();
+		dish.resize_world(100, 70, Anchor::TopLeft);
+		// 4 by 3 chunks, the last column and row only partly inside the world
+		assert_eq!(dirty(&mut dish).len(), 12);
+		assert_eq!(dirty(&mut dish), vec![]);
+
+		let before = snapshot(&dish);
+		let same = dish.get_cell(5, 5).unwrap();
+		dish.set_cell(5, 5, same);
+		dish.set_cell(
+			99,
+			69,
+			Cell(if before[99 + 69 * 100] == Cell(0) {
+				1
+			} else {
+				0
+			}),
+		);
+		dish.set_cell(33, 0, Cell(if before[33] == Cell(0) { 1 } else { 0 }));
+		assert_eq!(dirty(&mut dish), vec![(1, 0), (3, 2)]);
+
+		dish.rebuild_cache();
+		for _ in 0..300 {
+			let before = snapshot(&dish);
+			dish.apply_one_match();
+			assert_eq!(dirty(&mut dish), changed_chunks(&dish, &before));
+		}
+	}
+
+	#[test]
+	fn footprints_of_rotated_variants() {
+		let mut dish = sand_dish(8);
+		let mut rule = enabled_rule("1 0 0 -> 0 0 1");
+		rule.rotate = true;
+		dish.rules = vec![rule];
+		dish.update_all_rules();
+
+		let variants = dish.rules[0].variants();
+		let mut shapes: Vec<_> = variants
+			.iter()
+			.map(|v| (v.width, v.height, v.origin()))
+			.collect();
+		shapes.sort();
+		// rotating moves the origin to the cell that was the top-left corner
+		assert_eq!(
+			shapes,
+			[
+				(1, 3, (0, 0)),
+				(1, 3, (0, 2)),
+				(3, 1, (0, 0)),
+				(3, 1, (2, 0))
+			]
+		);
+		for (v, variant) in variants.iter().enumerate() {
+			let (ox, oy) = variant.origin();
+			assert_eq!(
+				dish.match_footprint(0, v, (10, 7)),
+				(
+					10 - ox as isize,
+					7 - oy as isize,
+					variant.width,
+					variant.height
+				)
+			);
+		}
+
+		let rect = RegionMask {
+			x: 0,
+			y: 0,
+			w: dish.world_width(),
+			h: dish.world_height(),
+		};
+		let matches = dish.matches_in_region(rect);
+		for v in 0..4 {
+			assert!(
+				matches.iter().any(|m| m.1 == v),
+				"no matches of variant {v}"
+			);
+		}
+		for (rule, v, at) in matches {
+			let (cx, cy, w, h) = dish.match_footprint(rule, v, at);
+			assert!(cx >= 0 && cy >= 0);
+			assert!(
+				cx as usize + w <= dish.world_width() && cy as usize + h <= dish.world_height()
+			);
+			let variant = &dish.rules[rule].variants[v];
+			assert!(variant.matches_at(cx, cy, &dish.groups, |x, y| dish.get_cell_isize(x, y)));
+		}
+	}
+
+	#[test]
+	fn toggling_cell_oscillates_with_period_two() {
+		// The request asked for a Game of Life blinker, but rules here are local rewrites applied
+		// one match at a time: nothing can count live neighbours or update every cell at once,
+		// so Life's rule and its blinker can't be written. A single cell toggling between two
+		// types is the smallest pattern with a known period, so it stands in for the blinker.
+		let mut dish = Dish::new();
+		dish.ensure_minimum_types(3);
+		dish.rules = vec![enabled_rule("1 -> 2"), enabled_rule("2 -> 1")];
+		dish.update_all_rules();
+		dish.resize_world(1, 1, Anchor::TopLeft);
+		dish.set_cell(0, 0, Cell(1));
+		dish.rebuild_cache();
+		assert_eq!(dish.find_oscillating_patterns(2, 40, 1), [(0, 0, 2)]);
+		assert_eq!(dish.find_oscillating_patterns(4, 40, 1), [(0, 0, 2)]);
+		assert_eq!(dish.find_oscillating_patterns(3, 40, 1), []);
+
+		// settled sand never changes, so nothing oscillates
+		let mut settled = Dish::new();
+		settled.rules = vec![enabled_rule("1 -> 0\n0 -> 1")];
+		settled.update_all_rules();
+		settled.fill(Cell(0));
+		for x in 0..settled.world_width() {
+			settled.set_cell(x, settled.world_height() - 1, Cell(1));
+		}
+		settled.rebuild_cache();
+		assert_eq!(settled.find_oscillating_patterns(2, 20, 1), []);
+	}
+
+	#[test]
+	fn profile_times_add_up() {
+		let mut dish = sand_dish(11);
+		let profile = dish.run_profiled(2000, &mut StdRng::seed_from_u64(11));
+		assert_eq!(profile.rule_match_time.len(), dish.rules.len());
+		assert_eq!(profile.rule_apply_time.len(), dish.rules.len());
+		let matching: Duration = profile.rule_match_time.iter().sum();
+		let applying: Duration = profile.rule_apply_time.iter().sum();
+		assert!(matching > Duration::ZERO && applying > Duration::ZERO);
+		assert!(profile.total_time >= matching + applying, "{profile:?}");
+		assert!(profile.cache_update_time >= matching, "{profile:?}");
+
+		let report = profile.report(&dish.rules);
+		for rule in &dish.rules {
+			assert!(report.contains(&rule.name), "{report}");
+		}
+		// profiling is off again afterwards
+		assert!(dish.profile.is_none());
+	}
+
+	#[test]
+	fn chunk_skipping_misses_no_matches() {
+		let mut rng = StdRng::seed_from_u64(686);
+		let from_cells = ["*", "*", "0", "1", "2", "g0", "g1", "g2", "#"];
+		for world in 0..60 {
+			let mut dish = Dish::new();
+			dish.ensure_minimum_types(3);
+			dish.add_group(CellGroup {
+				name: "solid".into(),
+				cells: vec![Cell(1), Cell(2)],
+				..Default::default()
+			});
+			dish.add_group(CellGroup {
+				name: "edge".into(),
+				cells: vec![Cell(2), Cell::BOUNDARY],
+				..Default::default()
+			});
+			let (width, height) = [(7, 5), (32, 32), (45, 70)][world % 3];
+			dish.resize_world(width, height, Anchor::TopLeft);
+			// mostly air, so many chunks have none of the required cells
+			let cells = [(Cell(0), 30.), (Cell(1), 1.), (Cell(2), 0.5)];
+			dish.randomize_world(&cells, &mut rng);
+
+			dish.rules = (0..4)
+				.map(|_| {
+					let (w, h) = (rng.gen_range(1..=3), rng.gen_range(1..=3));
+					let pattern = (0..h)
+						.map(|_| {
+							let from: Vec<_> = (0..w)
+								.map(|_| *from_cells.choose(&mut rng).unwrap())
+								.collect();
+							format!("{} -> {}", from.join(" "), vec!["1"; w].join(" "))
+						})
+						.collect::<Vec<_>>()
+						.join("\n");
+					let mut rule = enabled_rule(&pattern);
+					rule.rotate = rng.gen();
+					rule.flip_x = rng.gen();
+					rule.flip_y = rng.gen();
+					rule
+				})
+				.collect();
+			dish.update_all_rules();
+
+			let mut scanned = 0;
+			for cache in &dish.cache {
+				let variant = &dish.rules[cache.rule].variants[cache.variant];
+				// every position the scan without chunk skipping tests
+				let (bw, bh) = (variant.width as isize - 1, variant.height as isize - 1);
+				let mut expected = Vec::new();
+				for x in -bw..width as isize + bw {
+					for y in -bh..height as isize + bh {
+						if dish.variant_matches_at(cache.rule, cache.variant, x, y) {
+							expected.push((x, y));
+						}
+					}
+				}
+				let mut found = cache.matches.clone();
+				found.sort();
+				expected.sort();
+				assert_eq!(
+					found,
+					expected,
+					"world {world}, rule {}:\n{}",
+					cache.rule,
+					variant
+						.as_text_grid(TEXT_GRID_CHARS, TEXT_GRID_CHARS)
+						.unwrap()
+				);
+				scanned += 1;
+			}
+			let variants: usize = dish.rules.iter().map(|r| r.variants.len()).sum();
+			assert_eq!(scanned, variants);
+		}
+	}
+
+	#[test]
+	fn fall_on_the_top_half_stays_in_the_top_half() {
+		let mut dish = sand_dish(686);
+		dish.rules = vec![enabled_rule("1 -> 0\n0 -> 1")];
+		dish.update_all_rules();
+		let (width, height) = (dish.world_width(), dish.world_height());
+		let half = height / 2;
+		let bottom = dish.get_region(0, half, width, height - half);
+		let top_sand = dish.census_region(0, 0, width, half)[1];
+
+		let mut rng = StdRng::seed_from_u64(686);
+		let mut total = 0;
+		loop {
+			let applied = dish.apply_rule_on_region(0, 0, 0, width, half, &mut rng);
+			assert_eq!(dish.get_region(0, half, width, height - half), bottom);
+			assert_eq!(dish.census_region(0, 0, width, half)[1], top_sand);
+			if applied == 0 {
+				break;
+			}
+			total += applied;
+		}
+		assert!(total > 0);
+		// the sand has settled onto the bottom row of the region
+		for x in 0..width {
+			let column = dish.get_region_flat(x, 0, 1, half);
+			assert!(
+				column.windows(2).all(|w| w[0].0 <= w[1].0),
+				"column {x}: {column:?}"
+			);
+		}
+		assert_eq!(dish.verify_cache_integrity(), vec![]);
+	}
+
+	#[test]
+	fn world_hash_follows_the_cells() {
+		let a = sand_dish(687);
+		let mut b = sand_dish(687);
+		assert_eq!(a.world_hash(), b.world_hash());
+		assert_eq!(a.full_hash(), b.full_hash());
+		let before = b.world_hash();
+		assert!(!b.has_changed_since(before));
+
+		let cell = b.get_cell(3, 9).unwrap();
+		b.set_cell(3, 9, Cell(1 - cell.0));
+		assert_ne!(a.world_hash(), b.world_hash());
+		assert!(b.has_changed_since(before));
+		assert_eq!(a.rules_hash(), b.rules_hash());
+		b.set_cell(3, 9, cell);
+		assert_eq!(a.world_hash(), b.world_hash());
+
+		// the same cells in a different shape are a different world
+		let mut wide = Dish::new();
+		wide.resize_world(8, 2, Anchor::TopLeft);
+		wide.fill(Cell(0));
+		let mut tall = Dish::new();
+		tall.resize_world(2, 8, Anchor::TopLeft);
+		tall.fill(Cell(0));
+		assert_ne!(wide.world_hash(), tall.world_hash());
+	}
+
+	#[test]
+	fn undefined_cells_are_found_and_fixed() {
+		let mut dish = Dish::new();
+		dish.fill(Cell(0));
+		assert_eq!(dish.undefined_cells(), []);
+		dish.set_cell(1, 1, Cell(5));
+		dish.set_cell(2, 1, Cell(5));
+		// in another chunk
+		dish.resize_world(70, 40, Anchor::TopLeft);
+		dish.set_cell(65, 35, Cell(3));
+		dish.set_cell(4, 4, Cell(1));
+		assert_eq!(dish.undefined_cells(), [(Cell(3), 1), (Cell(5), 2)]);
+
+		assert_eq!(dish.replace_all(Cell(5), Cell(1)), 2);
+		assert_eq!(dish.undefined_cells(), [(Cell(3), 1)]);
+		assert_eq!(dish.count_cells(Cell(1)), 3);
+
+		assert_eq!(dish.add_placeholder_types(Cell::BOUNDARY), 0);
+		assert_eq!(dish.add_placeholder_types(Cell(3)), 2);
+		assert_eq!(dish.types.len(), 4);
+		assert_eq!(dish.types[3].name, "undefined #3");
+		assert_eq!(dish.add_placeholder_types(Cell(3)), 0);
+		assert_eq!(dish.undefined_cells(), []);
+	}
+
+	#[test]
+	fn noise_and_gradient_worlds() {
+		let mut dish = Dish::new();
+		dish.ensure_minimum_types(4);
+		assert_eq!((dish.world_width(), dish.world_height()), (32, 32));
+		let counts = |dish: &Dish| dish.census_region(0, 0, 32, 32);
+
+		let thresholds = [(0.0, Cell(0)), (0.25, Cell(1)), (0.75, Cell(2))];
+		dish.generate_noise_world(&|x, _| x, &thresholds);
+		assert_eq!(counts(&dish), [256, 512, 256, 0]);
+		assert_eq!(dish.get_cell(7, 20), Some(Cell(0)));
+		assert_eq!(dish.get_cell(8, 20), Some(Cell(1)));
+		assert_eq!(dish.get_cell(24, 20), Some(Cell(2)));
+		assert_eq!(dish.verify_cache_integrity(), vec![]);
+		// values below every threshold get the first cell
+		dish.generate_noise_world(&|_, y| y - 0.5, &thresholds);
+		assert_eq!(counts(&dish), [768, 256, 0, 0]);
+
+		dish.generate_gradient_world(false, &[Cell(3), Cell(2), Cell(1), Cell(0)]);
+		assert_eq!(counts(&dish), [256; 4]);
+		assert_eq!(dish.get_cell(0, 0), Some(Cell(3)));
+		assert_eq!(dish.get_cell(0, 31), Some(Cell(0)));
+		dish.generate_gradient_world(true, &[Cell(0), Cell(1), Cell(2)]);
+		assert_eq!(counts(&dish), [352, 352, 320, 0]);
+		assert_eq!(dish.verify_cache_integrity(), vec![]);
+	}
+
+	#[test]
+	fn fall_added_twice_is_a_duplicate() {
+		let mut dish = Dish::new();
+		let rules = dish.rules.len();
+		let fall = dish.find_rule_by_name("fall").unwrap();
+		assert_eq!(dish.find_duplicate_rules(), []);
+
+		dish.rules.push(dish.rules[fall].clone());
+		// the same pattern with a column of (Any, None) beside it
+		let mut padded = enabled_rule("1 * -> 0 _\n0 * -> 1 _");
+		padded.failrate = 100;
+		dish.rules.push(padded);
+		// an extra requirement makes it a different rule
+		dish.rules.push(enabled_rule("1 1 -> 0 _\n0 * -> 1 _"));
+		dish.update_all_rules();
+		assert_eq!(
+			dish.find_duplicate_rules(),
+			[(fall, rules), (fall, rules + 1), (rules, rules + 1)]
+		);
+
+		assert_eq!(dish.remove_duplicate_rules(), [rules, rules + 1]);
+		assert_eq!(dish.rules.len(), rules + 1);
+		assert_eq!(dish.find_duplicate_rules(), []);
+		assert_eq!(dish.verify_cache_integrity(), vec![]);
+	}
+
+	#[test]
+	fn scaled_fall_moves_a_block() {
+		let fall = enabled_rule("1 -> 0\n0 -> 1");
+		let big_fall = fall.scale(2);
+		assert_eq!((big_fall.base().width, big_fall.base().height), (2, 4));
+
+		let mut dish = Dish::new();
+		dish.rules = vec![big_fall];
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		let block = vec![vec![Cell(1); 2]; 2];
+		dish.set_region(5, 5, &block);
+		// only the whole block matches, a lone column of sand is too thin
+		dish.set_cell(20, 5, Cell(1));
+		dish.set_cell(20, 6, Cell(1));
+		dish.rebuild_cache();
+		assert_eq!(dish.cache_stats().total_matches, 1);
+
+		dish.apply_one_match();
+		assert_eq!(dish.get_region(5, 5, 2, 2), vec![vec![Cell(0); 2]; 2]);
+		assert_eq!(dish.get_region(5, 7, 2, 2), block);
+		assert_eq!(dish.count_cells(Cell(1)), 6);
+	}
+
+	#[test]
+	fn schedules_switch_on_the_exact_step() {
+		let scheduled = |schedule| {
+			let mut dish = Dish::new();
+			let mut rule = enabled_rule("0 -> 1");
+			rule.schedule = Some(schedule);
+			dish.rules = vec![rule];
+			dish.update_all_rules();
+			dish.fill(Cell(0));
+			dish.seed(692);
+			dish
+		};
+
+		let mut dish = scheduled(Schedule::Between(20, 30));
+		for step in 0..40 {
+			assert_eq!(dish.step_count(), step);
+			// the cache only holds the rule while it is active
+			assert_eq!(
+				dish.cache_stats().total_matches > 0,
+				(20..30).contains(&step),
+				"step {step}"
+			);
+			dish.step(StepMode::Batch, 1);
+			let active_steps = (0..=step).filter(|s| (20..30).contains(s)).count();
+			assert_eq!(dish.count_cells(Cell(1)), active_steps, "step {step}");
+		}
+
+		// one call stops at every transition on the way
+		let mut dish = scheduled(Schedule::Periodic {
+			period: 10,
+			duty: 3,
+		});
+		dish.step(StepMode::Batch, 95);
+		assert_eq!(dish.count_cells(Cell(1)), 30);
+		let mut dish = scheduled(Schedule::After(50));
+		dish.step(StepMode::Batch, 60);
+		assert_eq!(dish.count_cells(Cell(1)), 10);
+		let mut dish = scheduled(Schedule::Until(50));
+		dish.step(StepMode::Batch, 60);
+		assert_eq!(dish.count_cells(Cell(1)), 50);
+		assert_eq!(dish.cache_stats().total_matches, 0);
+
+		// seeded runs of the same scenario end in the same world
+		let run = || {
+			let mut dish = scheduled(Schedule::Periodic { period: 7, duty: 2 });
+			dish.step(StepMode::Random, 500);
+			dish.world_hash()
+		};
+		assert_eq!(run(), run());
+	}
+
+	#[test]
+	fn world_json_round_trip() {
+		let mut source = sand_dish(692);
+		source.step(StepMode::Random, 2000);
+		let json = source.export_world_as_json_string().unwrap();
+
+		let mut target = Dish::new();
+		target.rules = source.rules.clone();
+		target.rules[0].failrate = 50;
+		target.update_all_rules();
+		target.import_world_from_json_string(&json).unwrap();
+		assert_eq!(target.world_hash(), source.world_hash());
+		assert_eq!(target.verify_cache_integrity(), vec![]);
+		assert_eq!(target.rules[0].failrate, 50);
+		assert_eq!(
+			target.cache_stats().total_matches,
+			source.cache_stats().total_matches
+		);
+
+		let mut other = Dish::new();
+		other.import_world_from_dish(&source).unwrap();
+		assert_eq!(other.world_hash(), source.world_hash());
+
+		let mut small = Dish::new();
+		small.resize_world(8, 8, Anchor::TopLeft);
+		let before = small.world_hash();
+		assert_eq!(
+			small.import_world_from_json_string(&json),
+			Err(ImportError::DimensionMismatch {
+				expected: (8, 8),
+				got: (32, 32)
+			})
+		);
+		assert_eq!(
+			small.import_world_from_dish(&source),
+			Err(ImportError::DimensionMismatch {
+				expected: (8, 8),
+				got: (32, 32)
+			})
+		);
+		assert_eq!(small.world_hash(), before);
+		let one_cell_too_many = json.replacen("[", "[0,", 1);
+		assert!(matches!(
+			other.import_world_from_json_string(&one_cell_too_many),
+			Err(ImportError::Json(_))
+		));
+	}
+
+	#[test]
+	fn label_colors_contrast_with_the_cell() {
+		let dish = Dish::new();
+		let (air, sand) = (&dish.types[0], &dish.types[1]);
+		// 0.299 + 0.587 * 147/255 + 0.114 * 219/255
+		assert!(
+			(sand.luminance() - 0.7353).abs() < 0.001,
+			"{}",
+			sand.luminance()
+		);
+		assert!(!sand.is_dark());
+		assert_eq!(sand.contrasting_text_color(), [0; 3]);
+
+		assert_eq!(air.luminance(), 0.);
+		assert!(air.is_dark());
+		assert_eq!(air.contrasting_text_color(), [255; 3]);
+		assert_eq!(CellData::new("white", 255, 255, 255).luminance(), 1.);
+		// just below the middle still gets white text
+		assert!(CellData::new("gray", 127, 127, 127).is_dark());
+		assert!(!CellData::new("gray", 128, 128, 128).is_dark());
+	}
+
+	#[test]
+	fn conditional_fill_converts_only_matching_cells() {
+		let mut dish = Dish::new();
+		dish.ensure_minimum_types(4);
+		let cells = [(Cell(0), 1.), (Cell(1), 1.), (Cell(2), 1.), (Cell(3), 1.)];
+		dish.randomize_world(&cells, &mut StdRng::seed_from_u64(694));
+		let before = dish.get_region_flat(0, 0, 32, 32);
+		let ones = dish.count_cells(Cell(1));
+		let twos = dish.count_cells(Cell(2));
+
+		assert_eq!(
+			dish.conditional_fill(&RuleCellFrom::One(Cell(1)), Cell(2)),
+			ones
+		);
+		for (after, before) in dish.get_region_flat(0, 0, 32, 32).iter().zip(&before) {
+			let expected = if *before == Cell(1) { Cell(2) } else { *before };
+			assert_eq!(*after, expected);
+		}
+		assert_eq!(dish.count_cells(Cell(2)), ones + twos);
+		assert_eq!(dish.verify_cache_integrity(), vec![]);
+
+		// the empty group holds air, cells already equal to the replacement are not counted
+		let air = dish.count_cells(Cell(0));
+		let in_rect = dish.census_region(4, 4, 8, 8);
+		assert_eq!(
+			dish.conditional_fill_region(&RuleCellFrom::Group(0), Cell(3), 4, 4, 8, 8),
+			in_rect[0] as usize
+		);
+		assert_eq!(dish.count_cells(Cell(0)), air - in_rect[0] as usize);
+		assert_eq!(
+			dish.conditional_fill(&RuleCellFrom::BoundaryVoid, Cell(3)),
+			0
+		);
+		let threes = dish.count_cells(Cell(3));
+		assert_eq!(
+			dish.conditional_fill(&RuleCellFrom::Any, Cell(3)),
+			32 * 32 - threes
+		);
+	}
+
+	#[test]
+	fn coverage_of_fall() {
+		let mut dish = Dish::new();
+		dish.rules = vec![enabled_rule("1 -> 0\n0 -> 1")];
+		dish.update_all_rules();
+		dish.fill(Cell(1));
+		assert_eq!(dish.rule_coverage_fractions(), [0.]);
+		dish.fill(Cell(0));
+		assert_eq!(dish.rule_coverage_fractions(), [0.]);
+
+		dish.set_cell(5, 5, Cell(1));
+		dish.set_cell(9, 31, Cell(1));
+		// 32 columns and 34 rows of origins, counting the rows hanging over the top and bottom
+		let total = 32. * 34.;
+		assert_eq!(dish.rule_coverage_fractions(), [1. / total]);
+		assert_eq!(
+			dish.expected_applications_per_step(0),
+			Some(1. / total * 1024.)
+		);
+		assert_eq!(dish.expected_applications_per_step(1), None);
+		let fall = &dish.rules[0];
+		assert_eq!(
+			fall.coverage_fraction(&dish.world_view(), &dish.groups),
+			1. / total
+		);
+	}
+
+	/// how many times each element appears, panics if the text isn't well-formed xml
+	fn xml_elements(xml: &str) -> HashMap<String, usize> {
+		let mut reader = quick_xml::Reader::from_str(xml);
+		let mut counts = HashMap::new();
+		let mut open = 0;
+		loop {
+			let element = match reader.read_event().unwrap() {
+				Event::Start(e) => {
+					open += 1;
+					e
+				}
+				Event::Empty(e) => e,
+				Event::End(_) => {
+					open -= 1;
+					continue;
+				}
+				Event::Text(text) => {
+					text.unescape().unwrap();
+					continue;
+				}
+				Event::Eof => break,
+				_ => continue,
+			};
+			for attribute in element.attributes() {
+				attribute.unwrap().unescape_value().unwrap();
+			}
+			let name = String::from_utf8(element.name().as_ref().to_vec()).unwrap();
+			*counts.entry(name).or_default() += 1;
+		}
+		assert_eq!(open, 0, "unclosed elements");
+		counts
+	}
+
+	#[test]
+	fn fall_rule_svg() {
+		let mut dish = Dish::new();
+		dish.types[1].name = "sand & <dust>".into();
+		let fall = dish.find_rule_by_name("fall").unwrap();
+		let svg = dish.export_rule_as_svg(fall, 16).unwrap();
+		let elements = xml_elements(&svg);
+		assert_eq!(elements["svg"], 1);
+		// two input cells and two output cells
+		assert_eq!(elements["rect"], 4, "{svg}");
+		assert!(svg.contains("sand &amp; &lt;dust&gt;"), "{svg}");
+
+		let all = dish.export_all_rules_as_svg().unwrap();
+		assert_eq!(xml_elements(&all)["svg"], 1);
+		assert!(matches!(
+			dish.export_rule_as_svg(dish.rules.len(), 16),
+			Err(ExportError::NoSuchRule { .. })
+		));
+	}
+
+	#[test]
+	fn revisions_follow_every_mutation() {
+		use DishPart::{Groups, Rules, Types, World};
+		let mut dish = sand_dish(698);
+		dish.ensure_minimum_types(3);
+		let mut check = |parts: &[DishPart], what: &str, f: &dyn Fn(&mut Dish)| {
+			let before = dish.revisions();
+			f(&mut dish);
+			for part in [Rules, Groups, Types, World] {
+				let (old, new) = (before.get(part), dish.revision(part));
+				if parts.contains(&part) {
+					assert!(new > old, "{what} should bump {part:?}");
+				} else {
+					assert_eq!(new, old, "{what} should not bump {part:?}");
+				}
+			}
+		};
+
+		check(&[Rules], "update_all_rules", &|d| d.update_all_rules());
+		check(&[Rules], "add_source", &|d| {
+			d.add_source(Cell(1), Edge::Top, 0.5);
+		});
+		check(&[Rules], "add_sink", &|d| {
+			d.add_sink(RuleCellFrom::One(Cell(1)), Edge::Bottom);
+		});
+		check(&[Rules], "with_rule_mut", &|d| {
+			d.with_rule_mut(0, |r| r.failrate = 10);
+		});
+		check(&[Rules], "edit_rules", &|d| {
+			d.edit_rules(&[0, 1], |r| r.flip_x = true)
+		});
+		check(&[Rules], "iter_rules_mut", &|d| {
+			let mut rules = d.iter_rules_mut();
+			let (_, rule, changed) = rules.next_rule().unwrap();
+			rule.flip_y = true;
+			*changed = true;
+		});
+		check(&[Rules], "set_rule_region", &|d| d.set_rule_region(0, None));
+		check(&[Rules], "auto_name_rules", &|d| d.auto_name_rules());
+		check(&[Rules], "import_rules", &|d| {
+			d.import_rules(vec![enabled_rule("1 -> 2")]).unwrap();
+		});
+		check(&[Rules], "import_rules_from_json_array", &|d| {
+			d.import_rules_from_json_array(&d.export_rules_as_json_array().unwrap())
+				.unwrap();
+		});
+		check(&[Rules], "remove_duplicate_rules", &|d| {
+			d.remove_duplicate_rules();
+		});
+		check(&[Rules], "normalize_rule_name", &|d| {
+			d.rules[0].name = " fall ".into();
+			d.normalize_rule_name(0);
+		});
+		check(&[Rules], "remove_rule", &|d| {
+			d.remove_rule(d.rules.len() - 1);
+		});
+
+		check(&[Groups], "add_group", &|d| {
+			d.add_group(CellGroup::default());
+		});
+		check(&[Groups], "set_group_cells", &|d| {
+			d.set_group_cells(1, vec![Cell(2)])
+		});
+		check(&[Groups], "set_group_out_of_bounds", &|d| {
+			d.set_group_out_of_bounds(1, true)
+		});
+		check(&[Groups], "merge_groups", &|d| {
+			d.merge_groups(0, 1, "merged").unwrap();
+		});
+		check(&[Groups, Rules], "remove_group", &|d| {
+			d.remove_group(2).unwrap()
+		});
+		check(&[Groups, Rules], "remove_group_force", &|d| {
+			d.remove_group_force(0)
+		});
+
+		check(&[Types], "add_type", &|d| {
+			d.add_type(CellData::new("water", 0, 0, 255));
+		});
+		check(&[Types], "ensure_minimum_types", &|d| {
+			d.ensure_minimum_types(6);
+		});
+		check(&[], "ensure_minimum_types with enough types", &|d| {
+			d.ensure_minimum_types(2);
+		});
+		check(&[Types, Groups, Rules, World], "swap_cell_ids", &|d| {
+			d.swap_cell_ids(Cell(0), Cell(1))
+		});
+
+		check(&[World], "set_cell", &|d| {
+			let cell = d.get_cell(0, 0).unwrap();
+			d.set_cell(0, 0, Cell(1 - cell.0));
+		});
+		check(&[], "set_cell with the same cell", &|d| {
+			let cell = d.get_cell(0, 0).unwrap();
+			d.set_cell(0, 0, cell);
+		});
+		check(&[World], "fill", &|d| d.fill(Cell(2)));
+		check(&[World], "set_region", &|d| {
+			d.set_region(1, 1, &[vec![Cell(1)]])
+		});
+		check(&[World], "randomize_world", &|d| {
+			d.randomize_world(
+				&[(Cell(0), 1.), (Cell(1), 1.)],
+				&mut StdRng::seed_from_u64(1),
+			)
+		});
+		check(&[World], "conditional_fill", &|d| {
+			d.conditional_fill(&RuleCellFrom::One(Cell(1)), Cell(2));
+		});
+		check(&[World], "map_cells", &|d| {
+			d.map_cells(|x, _, _| Cell(x as u16 % 2))
+		});
+		check(&[World], "import_world_from_json_string", &|d| {
+			let mut other = Dish::new();
+			other.fill(Cell(0));
+			let json = other.export_world_as_json_string().unwrap();
+			d.import_world_from_json_string(&json).unwrap();
+		});
+		check(&[World], "step", &|d| d.step(StepMode::Random, 500));
+		check(&[World], "resize_world", &|d| {
+			d.resize_world(40, 20, Anchor::Center)
+		});
+
+		check(&[Types], "add_placeholder_types", &|d| {
+			d.add_placeholder_types(Cell(9));
+		});
+		check(&[Types], "remove_all_types", &|d| d.remove_all_types());
+	}
+
+	#[test]
+	fn checkerboard_mask() {
+		let mut dish = Dish::new();
+		dish.resize_world(6, 4, Anchor::TopLeft);
+		dish.fill(Cell(1));
+		let checkerboard: Vec<bool> = (0..24).map(|i| (i % 6 + i / 6) % 2 == 0).collect();
+		dish.apply_mask(&checkerboard, Cell(0));
+		assert_eq!(
+			dish.get_region(0, 0, 6, 4),
+			(0..4)
+				.map(|y| (0..6).map(|x| Cell(((x + y + 1) % 2) as u16)).collect())
+				.collect::<Vec<Vec<Cell>>>()
+		);
+		assert_eq!(dish.mask_from_cell_type(Cell(1)), checkerboard);
+		assert_eq!(dish.verify_cache_integrity(), vec![]);
+
+		// keep sand only inside a rect, on the white squares
+		let rect = dish.mask_from_region(1, 1, 3, 2);
+		assert_eq!(rect.iter().filter(|&&m| m).count(), 6);
+		let both = Dish::mask_intersection(&rect, &checkerboard);
+		assert_eq!(Dish::mask_invert(&Dish::mask_invert(&both)), both);
+		assert_eq!(
+			Dish::mask_union(&both, &Dish::mask_invert(&both)),
+			vec![true; 24]
+		);
+		dish.apply_mask(&both, Cell(0));
+		assert_eq!(dish.count_cells(Cell(1)), 3);
+		assert_eq!(dish.mask_from_cell_type(Cell(1)), both);
+
+		// a short mask leaves the rest of the world alone
+		dish.fill(Cell(1));
+		dish.apply_mask(&[false; 6], Cell(0));
+		assert_eq!(dish.count_cells(Cell(0)), 6);
+	}
+
+	#[test]
+	fn dropping_the_rule_iterator_updates_the_cache() {
+		let mut dish = Dish::new();
+		dish.rules = vec![enabled_rule("1 -> 0\n0 -> 1"), enabled_rule("1 -> 0")];
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		dish.set_cell(5, 5, Cell(1));
+		dish.rebuild_cache();
+		let whole = RegionMask {
+			x: 0,
+			y: 0,
+			w: dish.world_width(),
+			h: dish.world_height(),
+		};
+		let origins = |dish: &Dish, rule: usize| {
+			dish.matches_in_region(whole)
+				.into_iter()
+				.filter(|m| m.0 == rule)
+				.map(|(_, _, at)| at)
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(origins(&dish, 0), vec![(5, 5)]);
+
+		let mut rules = dish.iter_rules_mut();
+		while let Some((i, rule, changed)) = rules.next_rule() {
+			if i == 0 {
+				rule.flip_y = true;
+				*changed = true;
+			}
+		}
+		drop(rules);
+		// no step in between, the cache is already up to date
+		assert!(dish.verify_cache_integrity().is_empty());
+		assert_eq!(origins(&dish, 0).len(), 2);
+
+		// nothing is left pending that could be applied to the wrong rule later
+		dish.remove_rule(0);
+		assert!(dish.verify_cache_integrity().is_empty());
+		assert_eq!(origins(&dish, 0), vec![(5, 5)]);
+		dish.step_batch(1);
+		assert!(dish.verify_cache_integrity().is_empty());
+	}
+
+	#[test]
+	fn batches_keep_dynamic_matches_apart() {
+		for seed in 0..20 {
+			let mut dish = Dish::new();
+			dish.rules = vec![enabled_rule("1 -> 0\n0 -> 1")];
+			dish.update_all_rules();
+			dish.fill(Cell(0));
+			// both fall and the teleport want the same sand cell
+			dish.set_cell(3, 30, Cell(1));
+			dish.rebuild_cache();
+			dish.add_dynamic_rule(Box::new(TeleportToTop {
+				cell: Cell(1),
+				empty: Cell(0),
+			}));
+			dish.seed(seed);
+			// whichever goes first moves the sand, the other overlaps it and has to wait a batch
+			assert_eq!(dish.step_batch(50), 1);
+			let sand = (0..32).filter(|&y| dish.get_cell(3, y) == Some(Cell(1)));
+			assert_eq!(sand.count(), 1);
+			assert!(dish.verify_cache_integrity().is_empty());
+		}
+	}
+}
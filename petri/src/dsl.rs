@@ -0,0 +1,231 @@
+use std::fmt;
+
+use crate::{Cell, Rule, RuleCellFrom, RuleCellTo, SubRule, TEXT_GRID_CHARS};
+
+/// text that isn't a pattern in the `SubRule::as_text_grid` format, lines count from 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+	Empty,
+	MissingArrow {
+		line: usize,
+	},
+	InvalidToken {
+		line: usize,
+		token: String,
+	},
+	/// the from and to sides of a row have different widths
+	SidesDiffer {
+		line: usize,
+		from: usize,
+		to: usize,
+	},
+	/// rows must all be as wide as the first one
+	RaggedRow {
+		line: usize,
+		expected: usize,
+		got: usize,
+	},
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ParseError::Empty => write!(f, "no pattern"),
+			ParseError::MissingArrow { line } => write!(f, "line {line}: missing '->'"),
+			ParseError::InvalidToken { line, token } => {
+				write!(f, "line {line}: invalid cell '{token}'")
+			}
+			ParseError::SidesDiffer { line, from, to } => {
+				write!(f, "line {line}: {from} cells before '->' but {to} after")
+			}
+			ParseError::RaggedRow {
+				line,
+				expected,
+				got,
+			} => write!(f, "line {line}: expected {expected} cells, got {got}"),
+		}
+	}
+}
+
+impl std::error::Error for ParseError {}
+
+/// the base pattern of the rule as text, using `TEXT_GRID_CHARS` for cells
+pub fn rule_to_dsl(rule: &Rule) -> String {
+	rule.base().text_grid(TEXT_GRID_CHARS, TEXT_GRID_CHARS).0
+}
+
+/// a new rule with the pattern written by `rule_to_dsl`
+pub fn parse_rule(text: &str) -> Result<Rule, ParseError> {
+	let mut rule = Rule::new();
+	rule.paste_base(parse_pattern(text)?);
+	Ok(rule)
+}
+
+/// Parse rows of `from -> to` tokens, see `SubRule::as_text_grid`.
+/// Blank lines are skipped, and the origin is the top-left cell.
+pub fn parse_pattern(text: &str) -> Result<SubRule, ParseError> {
+	let mut width = None;
+	let mut height = 0;
+	let mut contents = Vec::new();
+	for (i, row) in text.lines().enumerate() {
+		let line = i + 1;
+		if row.trim().is_empty() {
+			continue;
+		}
+		let (from, to) = row
+			.split_once("->")
+			.ok_or(ParseError::MissingArrow { line })?;
+		let from = from
+			.split_whitespace()
+			.map(|token| parse_from(token).ok_or_else(|| invalid(line, token)))
+			.collect::<Result<Vec<_>, _>>()?;
+		let to = to
+			.split_whitespace()
+			.map(|token| parse_to(token).ok_or_else(|| invalid(line, token)))
+			.collect::<Result<Vec<_>, _>>()?;
+		if from.len() != to.len() {
+			return Err(ParseError::SidesDiffer {
+				line,
+				from: from.len(),
+				to: to.len(),
+			});
+		}
+		let expected = *width.get_or_insert(from.len());
+		if from.len() != expected {
+			return Err(ParseError::RaggedRow {
+				line,
+				expected,
+				got: from.len(),
+			});
+		}
+		contents.extend(from.into_iter().zip(to));
+		height += 1;
+	}
+	match width {
+		Some(width) if width > 0 => Ok(SubRule {
+			width,
+			height,
+			origin_x: 0,
+			origin_y: 0,
+			contents,
+		}),
+		_ => Err(ParseError::Empty),
+	}
+}
+
+/// Parse separate input and output grids of whitespace separated cell ids, see `Rule::from_pattern_string`.
+/// `*` and `_` stand for Any on the input side and no change on the output side.
+/// Blank lines are skipped, and `line` in errors counts rows from 1.
+pub fn parse_id_grids(from: &str, to: &str) -> Result<SubRule, ParseError> {
+	let rows = |text: &str| -> Vec<Vec<String>> {
+		text.lines()
+			.filter(|row| !row.trim().is_empty())
+			.map(|row| row.split_whitespace().map(str::to_owned).collect())
+			.collect()
+	};
+	let (from, to) = (rows(from), rows(to));
+	let mut width = None;
+	let mut contents = Vec::new();
+	for i in 0..from.len().max(to.len()) {
+		let line = i + 1;
+		let from_row = from.get(i).map_or(&[][..], Vec::as_slice);
+		let to_row = to.get(i).map_or(&[][..], Vec::as_slice);
+		if from_row.len() != to_row.len() {
+			return Err(ParseError::SidesDiffer {
+				line,
+				from: from_row.len(),
+				to: to_row.len(),
+			});
+		}
+		let expected = *width.get_or_insert(from_row.len());
+		if from_row.len() != expected {
+			return Err(ParseError::RaggedRow {
+				line,
+				expected,
+				got: from_row.len(),
+			});
+		}
+		for (from, to) in from_row.iter().zip(to_row) {
+			let from = match from.as_str() {
+				"*" | "_" => RuleCellFrom::Any,
+				token => RuleCellFrom::One(parse_id(token).ok_or_else(|| invalid(line, token))?),
+			};
+			let to = match to.as_str() {
+				"*" | "_" => RuleCellTo::None,
+				token => RuleCellTo::One(parse_id(token).ok_or_else(|| invalid(line, token))?),
+			};
+			contents.push((from, to));
+		}
+	}
+	match width {
+		Some(width) if width > 0 => Ok(SubRule {
+			width,
+			height: contents.len() / width,
+			origin_x: 0,
+			origin_y: 0,
+			contents,
+		}),
+		_ => Err(ParseError::Empty),
+	}
+}
+
+fn parse_id(token: &str) -> Option<Cell> {
+	token.parse().ok().map(Cell)
+}
+
+fn invalid(line: usize, token: &str) -> ParseError {
+	ParseError::InvalidToken {
+		line,
+		token: token.to_owned(),
+	}
+}
+
+fn parse_cell(token: &str) -> Option<Cell> {
+	let mut chars = token.chars();
+	let (Some(c), None) = (chars.next(), chars.next()) else {
+		return None;
+	};
+	TEXT_GRID_CHARS
+		.chars()
+		.position(|t| t == c)
+		.map(|id| Cell(id as u16))
+}
+
+fn parse_group(token: &str) -> Option<usize> {
+	token.strip_prefix('g')?.parse().ok()
+}
+
+fn parse_from(token: &str) -> Option<RuleCellFrom> {
+	match token {
+		"*" => Some(RuleCellFrom::Any),
+		"#" => Some(RuleCellFrom::BoundaryVoid),
+		_ => parse_group(token)
+			.map(RuleCellFrom::Group)
+			.or_else(|| parse_cell(token).map(RuleCellFrom::One)),
+	}
+}
+
+fn parse_to(token: &str) -> Option<RuleCellTo> {
+	match token {
+		"_" => Some(RuleCellTo::None),
+		"#" => Some(RuleCellTo::One(Cell::BOUNDARY)),
+		_ => {
+			if let Some(group) = token.strip_suffix('!').and_then(parse_group) {
+				return Some(RuleCellTo::GroupRandomOther(group));
+			}
+			if let Some((x, y)) = token
+				.strip_prefix("c(")
+				.and_then(|t| t.strip_suffix(')'))
+				.and_then(|t| t.split_once(','))
+			{
+				return Some(RuleCellTo::Copy(
+					x.trim().parse().ok()?,
+					y.trim().parse().ok()?,
+				));
+			}
+			parse_group(token)
+				.map(RuleCellTo::GroupRandom)
+				.or_else(|| parse_cell(token).map(RuleCellTo::One))
+		}
+	}
+}
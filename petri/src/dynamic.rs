@@ -0,0 +1,129 @@
+use std::fmt::Debug;
+
+use crate::{world::World, Cell};
+
+/// A rule implemented in code, for behaviour that can't be written as a local pattern.
+/// Dynamic rules are not serialized, the host has to register them again after loading.
+/// They must be `Send` so dishes can be simulated on other threads.
+pub trait DynamicRule: Debug + Send {
+	/// every position the rule could apply to right now
+	fn find_matches(&self, world: &WorldView) -> Vec<DynMatch>;
+
+	fn apply(&self, world: &mut WorldWriter, m: &DynMatch);
+
+	/// Rect (x, y, width, height) that `apply` may write for this match, without applying it.
+	/// `Dish::step_batch` uses it to skip matches that overlap cells already changed in the batch.
+	/// The default is the whole world, so such a rule only runs in a batch before anything else has.
+	fn footprint(&self, world: &WorldView, _m: &DynMatch) -> (isize, isize, usize, usize) {
+		(0, 0, world.width(), world.height())
+	}
+
+	/// chance of being picked relative to a single cached rule variant
+	fn weight(&self) -> usize {
+		1
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DynMatch {
+	pub x: isize,
+	pub y: isize,
+	/// rule-specific payload
+	pub data: usize,
+}
+
+/// read-only access to the world for `DynamicRule::find_matches`
+pub struct WorldView<'a> {
+	pub(crate) world: &'a World,
+}
+
+/// Write access for `DynamicRule::apply`.
+/// Writes to frozen cells are ignored, and every written cell is tracked so the cache can be updated afterwards.
+pub struct WorldWriter<'a> {
+	pub(crate) world: &'a mut World,
+	pub(crate) frozen: &'a [bool],
+	/// min and max corner of all written cells
+	pub(crate) changed: Option<(isize, isize, isize, isize)>,
+}
+
+impl<'a> WorldView<'a> {
+	pub fn width(&self) -> usize {
+		self.world.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.world.height
+	}
+
+	pub fn get_cell(&self, x: isize, y: isize) -> Option<Cell> {
+		self.world.get_cell(x, y)
+	}
+}
+
+impl<'a> WorldWriter<'a> {
+	pub fn width(&self) -> usize {
+		self.world.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.world.height
+	}
+
+	pub fn get_cell(&self, x: isize, y: isize) -> Option<Cell> {
+		self.world.get_cell(x, y)
+	}
+
+	/// returns false if nothing was written because the position is outside the world or frozen
+	pub fn set_cell(&mut self, x: isize, y: isize, cell: Cell) -> bool {
+		if !self.world.is_in_bounds(x, y) || self.world.rect_in_mask(self.frozen, x, y, 1, 1) {
+			return false;
+		}
+		self.world.set_cell(x, y, cell);
+		self.changed = Some(match self.changed {
+			None => (x, y, x, y),
+			Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+		});
+		true
+	}
+
+	/// the rect covering every written cell as (x, y, width, height)
+	pub fn changed_rect(&self) -> Option<(isize, isize, usize, usize)> {
+		self.changed
+			.map(|(x0, y0, x1, y1)| (x0, y0, (x1 - x0 + 1) as usize, (y1 - y0 + 1) as usize))
+	}
+}
+
+/// example dynamic rule: moves a cell straight to the top of its column if the top cell is empty
+#[derive(Debug, Clone)]
+pub struct TeleportToTop {
+	pub cell: Cell,
+	pub empty: Cell,
+}
+
+impl DynamicRule for TeleportToTop {
+	fn find_matches(&self, world: &WorldView) -> Vec<DynMatch> {
+		let mut matches = Vec::new();
+		for x in 0..world.width() as isize {
+			if world.get_cell(x, 0) != Some(self.empty) {
+				continue;
+			}
+			for y in 1..world.height() as isize {
+				if world.get_cell(x, y) == Some(self.cell) {
+					matches.push(DynMatch { x, y, data: 0 });
+				}
+			}
+		}
+		matches
+	}
+
+	fn apply(&self, world: &mut WorldWriter, m: &DynMatch) {
+		// either cell may be frozen, only move the cell if both writes go through
+		if world.set_cell(m.x, m.y, self.empty) && !world.set_cell(m.x, 0, self.cell) {
+			world.set_cell(m.x, m.y, self.cell);
+		}
+	}
+
+	fn footprint(&self, _world: &WorldView, m: &DynMatch) -> (isize, isize, usize, usize) {
+		(m.x, 0, 1, m.y as usize + 1)
+	}
+}
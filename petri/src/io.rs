@@ -0,0 +1,412 @@
+use std::{
+	ffi::OsString,
+	fmt,
+	fs::{self, File},
+	io::{BufReader, Read, Write},
+	path::{Path, PathBuf},
+	process,
+};
+
+use serde_json::{json, Value};
+
+use crate::{Dish, Rule};
+
+/// written at the start of `.snad` files
+const BINARY_MAGIC: &[u8; 4] = b"SNAD";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+	Json,
+	/// only readable and writable with the `ron` feature
+	Ron,
+	Binary,
+}
+
+/// how cells and groups are referred to in saved rules and groups
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum References {
+	/// indices into `types` and `groups`, compact but broken by reordering
+	#[default]
+	Ids,
+	/// names, resolved when loading. only used for json, and falls back to ids for duplicate or empty names
+	Names,
+}
+
+/// a name in a saved file that doesn't resolve to exactly one cell type or group
+#[derive(Debug, Clone, PartialEq)]
+pub enum NameError {
+	UnknownCell { name: String, used_in: String },
+	UnknownGroup { name: String, used_in: String },
+	AmbiguousCell { name: String, used_in: String },
+	AmbiguousGroup { name: String, used_in: String },
+}
+
+#[derive(Debug)]
+pub enum IoError {
+	Io(std::io::Error),
+	Json(serde_json::Error),
+	#[cfg(feature = "ron")]
+	Ron(ron::Error),
+	Binary(bincode::Error),
+	UnknownExtension(String),
+	Name(NameError),
+}
+
+impl fmt::Display for IoError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			IoError::Io(e) => write!(f, "{e}"),
+			IoError::Json(e) => write!(f, "invalid json: {e}"),
+			#[cfg(feature = "ron")]
+			IoError::Ron(e) => write!(f, "invalid ron: {e}"),
+			IoError::Binary(e) => write!(f, "invalid binary file: {e}"),
+			IoError::UnknownExtension(ext) => write!(f, "unknown file extension '{ext}'"),
+			IoError::Name(e) => write!(f, "{e}"),
+		}
+	}
+}
+
+impl std::error::Error for IoError {}
+
+impl fmt::Display for NameError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			NameError::UnknownCell { name, used_in } => {
+				write!(f, "unknown cell '{name}' in {used_in}")
+			}
+			NameError::UnknownGroup { name, used_in } => {
+				write!(f, "unknown group '{name}' in {used_in}")
+			}
+			NameError::AmbiguousCell { name, used_in } => {
+				write!(
+					f,
+					"more than one cell is named '{name}' (used in {used_in})"
+				)
+			}
+			NameError::AmbiguousGroup { name, used_in } => {
+				write!(
+					f,
+					"more than one group is named '{name}' (used in {used_in})"
+				)
+			}
+		}
+	}
+}
+
+impl From<NameError> for IoError {
+	fn from(e: NameError) -> Self {
+		IoError::Name(e)
+	}
+}
+
+impl From<std::io::Error> for IoError {
+	fn from(e: std::io::Error) -> Self {
+		IoError::Io(e)
+	}
+}
+
+impl From<serde_json::Error> for IoError {
+	fn from(e: serde_json::Error) -> Self {
+		IoError::Json(e)
+	}
+}
+
+#[cfg(feature = "ron")]
+impl From<ron::Error> for IoError {
+	fn from(e: ron::Error) -> Self {
+		IoError::Ron(e)
+	}
+}
+
+impl From<bincode::Error> for IoError {
+	fn from(e: bincode::Error) -> Self {
+		IoError::Binary(e)
+	}
+}
+
+impl FileFormat {
+	pub fn from_extension(path: &Path) -> Result<Self, IoError> {
+		let ext = path
+			.extension()
+			.and_then(|e| e.to_str())
+			.unwrap_or_default()
+			.to_lowercase();
+		match ext.as_str() {
+			"json" => Ok(FileFormat::Json),
+			"ron" if cfg!(feature = "ron") => Ok(FileFormat::Ron),
+			"snad" => Ok(FileFormat::Binary),
+			_ => Err(IoError::UnknownExtension(ext)),
+		}
+	}
+}
+
+/// guess the format from the first bytes of the file
+pub fn detect_format(path: &Path) -> Option<FileFormat> {
+	let mut start = [0; 64];
+	let len = File::open(path).ok()?.read(&mut start).ok()?;
+	let start = &start[..len];
+	if start.starts_with(BINARY_MAGIC) {
+		return Some(FileFormat::Binary);
+	}
+	match start.iter().find(|b| !b.is_ascii_whitespace())? {
+		b'{' => Some(FileFormat::Json),
+		b'(' => Some(FileFormat::Ron),
+		_ => None,
+	}
+}
+
+/// write the dish in the format matching the file extension
+pub fn save(dish: &Dish, path: &Path) -> Result<(), IoError> {
+	save_with_references(dish, path, References::Ids)
+}
+
+/// like `save`, choosing how json files refer to cells and groups
+pub fn save_with_references(dish: &Dish, path: &Path, refs: References) -> Result<(), IoError> {
+	let bytes = to_bytes(dish, FileFormat::from_extension(path)?, refs)?;
+	save_atomic(path, &bytes, false)?;
+	Ok(())
+}
+
+/// the file contents `save_with_references` writes
+pub fn to_bytes(dish: &Dish, format: FileFormat, refs: References) -> Result<Vec<u8>, IoError> {
+	let mut bytes = Vec::new();
+	match format {
+		FileFormat::Json => serde_json::to_writer(&mut bytes, &to_json_value(dish, refs)?)?,
+		#[cfg(feature = "ron")]
+		FileFormat::Ron => bytes.extend_from_slice(ron::to_string(dish)?.as_bytes()),
+		#[cfg(not(feature = "ron"))]
+		FileFormat::Ron => return Err(IoError::UnknownExtension("ron".into())),
+		FileFormat::Binary => {
+			bytes.extend_from_slice(BINARY_MAGIC);
+			bincode::serialize_into(&mut bytes, dish)?;
+		}
+	}
+	Ok(bytes)
+}
+
+/// Write the file next to `path` and rename it over the target once it is synced,
+/// so a failed write never leaves a truncated file behind.
+/// With `backup`, an existing file is first copied to `backup_path(path)`.
+pub fn save_atomic(path: &Path, bytes: &[u8], backup: bool) -> std::io::Result<()> {
+	let name = path
+		.file_name()
+		.ok_or_else(|| std::io::Error::other("the path has no file name"))?;
+	let dir = match path.parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => dir,
+		_ => Path::new("."),
+	};
+	let mut temp_name = OsString::from(".");
+	temp_name.push(name);
+	temp_name.push(format!(".{}.tmp", process::id()));
+	let temp = dir.join(temp_name);
+
+	let write = || -> std::io::Result<()> {
+		let mut file = File::create(&temp)?;
+		file.write_all(bytes)?;
+		file.sync_all()
+	};
+	if let Err(e) = write() {
+		fs::remove_file(&temp).ok();
+		return Err(e);
+	}
+	if backup && path.exists() {
+		if let Err(e) = fs::copy(path, backup_path(path)) {
+			fs::remove_file(&temp).ok();
+			return Err(e);
+		}
+	}
+	if let Err(e) = fs::rename(&temp, path) {
+		fs::remove_file(&temp).ok();
+		return Err(e);
+	}
+	// make the rename itself durable
+	#[cfg(unix)]
+	if let Ok(dir) = File::open(dir) {
+		dir.sync_all().ok();
+	}
+	Ok(())
+}
+
+/// where `save_atomic` keeps the previous file, the path with `.bak` appended
+pub fn backup_path(path: &Path) -> PathBuf {
+	let mut name = path.as_os_str().to_owned();
+	name.push(".bak");
+	PathBuf::from(name)
+}
+
+/// Read a dish saved by `save`. Files with an unknown extension are probed with `detect_format`.
+/// The returned dish has its variants and cache ready.
+pub fn load(path: &Path) -> Result<Dish, IoError> {
+	let format = match FileFormat::from_extension(path) {
+		Ok(format) => format,
+		Err(e) => detect_format(path).ok_or(e)?,
+	};
+	let mut dish: Dish = match format {
+		FileFormat::Json => {
+			from_json_value(serde_json::from_reader(BufReader::new(File::open(path)?))?)?
+		}
+		#[cfg(feature = "ron")]
+		FileFormat::Ron => ron::from_str(&fs::read_to_string(path)?).map_err(ron::Error::from)?,
+		#[cfg(not(feature = "ron"))]
+		FileFormat::Ron => return Err(IoError::UnknownExtension("ron".into())),
+		FileFormat::Binary => {
+			let bytes = fs::read(path)?;
+			let data = bytes.strip_prefix(BINARY_MAGIC).unwrap_or(&bytes);
+			bincode::deserialize(data)?
+		}
+	};
+	dish.add_default_cell_types();
+	dish.update_all_rules();
+	Ok(dish)
+}
+
+pub fn to_json_value(dish: &Dish, refs: References) -> Result<Value, IoError> {
+	let mut value = serde_json::to_value(dish)?;
+	if refs == References::Names {
+		write_names(&mut value, dish)?;
+	}
+	Ok(value)
+}
+
+/// Parse a dish from json, resolving references written as names.
+/// Does not regenerate the rule variants or cache.
+pub fn from_json_value(mut value: Value) -> Result<Dish, IoError> {
+	let names = |list: &str| -> Vec<String> {
+		value[list]
+			.as_array()
+			.map(|items| {
+				items
+					.iter()
+					.map(|item| item["name"].as_str().unwrap_or_default().to_owned())
+					.collect()
+			})
+			.unwrap_or_default()
+	};
+	let cell_names = names("types");
+	let group_names = names("groups");
+	resolve_names(&mut value, &cell_names, &group_names)?;
+	Ok(serde_json::from_value(value)?)
+}
+
+/// One rule with its cells and groups referred to by name, like `References::Names`,
+/// so `rule_from_json_value` can add it to a dish where they have other ids.
+pub fn rule_to_json_value(dish: &Dish, rule: usize) -> Result<Value, IoError> {
+	let mut value = json!({ "rules": [dish.rules[rule]] });
+	write_names(&mut value, dish)?;
+	Ok(value["rules"][0].take())
+}
+
+/// Parse a rule from `rule_to_json_value`, resolving names against the cells and groups of `dish`.
+/// The rule is not added, pass it to `Dish::import_rules` for that.
+pub fn rule_from_json_value(dish: &Dish, value: Value) -> Result<Rule, IoError> {
+	let cell_names: Vec<String> = dish.types.iter().map(|t| t.name.clone()).collect();
+	let group_names: Vec<String> = dish.groups.iter().map(|g| g.name.clone()).collect();
+	let mut value = json!({ "rules": [value] });
+	resolve_names(&mut value, &cell_names, &group_names)?;
+	Ok(serde_json::from_value(value["rules"][0].take())?)
+}
+
+/// replace ids with names in the references of a serialized dish, where the names are unique
+fn write_names(value: &mut Value, dish: &Dish) -> Result<(), NameError> {
+	let cell_names = unique_names(dish.types.iter().map(|t| t.name.as_str()));
+	let group_names = unique_names(dish.groups.iter().map(|g| g.name.as_str()));
+	let to_name = |names: &[Option<String>], v: &mut Value| {
+		if let Some(name) = v.as_u64().and_then(|i| names.get(i as usize)?.clone()) {
+			*v = Value::String(name);
+		}
+	};
+	for_each_reference(value, |kind, v, _| {
+		match kind {
+			RefKind::Cell => to_name(&cell_names, v),
+			RefKind::Group => to_name(&group_names, v),
+		}
+		Ok(())
+	})
+}
+
+/// replace names with ids in the references of a serialized dish, leaving ids as they are
+fn resolve_names(
+	value: &mut Value,
+	cell_names: &[String],
+	group_names: &[String],
+) -> Result<(), NameError> {
+	for_each_reference(value, |kind, v, used_in| {
+		let Some(name) = v.as_str() else {
+			return Ok(());
+		};
+		let names = match kind {
+			RefKind::Cell => cell_names,
+			RefKind::Group => group_names,
+		};
+		let matching: Vec<usize> = (0..names.len()).filter(|&i| names[i] == name).collect();
+		if let [index] = matching[..] {
+			*v = Value::from(index);
+			return Ok(());
+		}
+		let (name, used_in) = (name.to_owned(), used_in.to_owned());
+		Err(match (kind, matching.is_empty()) {
+			(RefKind::Cell, true) => NameError::UnknownCell { name, used_in },
+			(RefKind::Cell, false) => NameError::AmbiguousCell { name, used_in },
+			(RefKind::Group, true) => NameError::UnknownGroup { name, used_in },
+			(RefKind::Group, false) => NameError::AmbiguousGroup { name, used_in },
+		})
+	})
+}
+
+#[derive(Clone, Copy)]
+enum RefKind {
+	Cell,
+	Group,
+}
+
+/// names that belong to exactly one item, None for the rest
+fn unique_names<'a>(names: impl Iterator<Item = &'a str> + Clone) -> Vec<Option<String>> {
+	names
+		.clone()
+		.map(|name| {
+			let unique = !name.is_empty() && names.clone().filter(|n| *n == name).count() == 1;
+			unique.then(|| name.to_owned())
+		})
+		.collect()
+}
+
+/// call `f` on every cell or group reference in the rules and groups of a serialized dish,
+/// along with a description of where it is used
+fn for_each_reference(
+	value: &mut Value,
+	mut f: impl FnMut(RefKind, &mut Value, &str) -> Result<(), NameError>,
+) -> Result<(), NameError> {
+	if let Some(rules) = value["rules"].as_array_mut() {
+		for rule in rules {
+			let used_in = format!("rule '{}'", rule["name"].as_str().unwrap_or_default());
+			let Some(contents) = rule["base"]["contents"].as_array_mut() else {
+				continue;
+			};
+			for pair in contents {
+				for (key, kind) in [
+					("One", RefKind::Cell),
+					("Group", RefKind::Group),
+					("GroupRandom", RefKind::Group),
+					("GroupRandomOther", RefKind::Group),
+				] {
+					for side in [0, 1] {
+						if let Some(v) = pair[side].get_mut(key) {
+							f(kind, v, &used_in)?;
+						}
+					}
+				}
+			}
+		}
+	}
+	if let Some(groups) = value["groups"].as_array_mut() {
+		for group in groups {
+			let used_in = format!("group '{}'", group["name"].as_str().unwrap_or_default());
+			if let Some(cells) = group["cells"].as_array_mut() {
+				for cell in cells {
+					f(RefKind::Cell, cell, &used_in)?;
+				}
+			}
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,241 @@
+use std::{any::Any, fmt::Debug};
+
+use crate::{Cell, Dish, SimStats};
+
+/// Receives simulation events from a dish, for logging, recording or tests.
+/// Observers are not serialized, the host has to attach them again after loading.
+pub trait Observer: Any + Debug + Send {
+	/// after each `Dish::step`, with `Dish::stats` just refreshed by `Dish::snapshot_stats`
+	fn on_step_complete(&mut self, _dish: &Dish, _stats: &SimStats) {}
+
+	/// a world cell changed, through `Dish::set_cell` or a pattern rule
+	fn on_cell_changed(&mut self, _x: usize, _y: usize, _old: Cell, _new: Cell) {}
+
+	/// a pattern rule was applied with its origin at (x, y)
+	fn on_rule_fired(&mut self, _rule_index: usize, _variant_index: usize, _x: isize, _y: isize) {}
+}
+
+/// an observer that can end `Dish::simulate_with_observer_and_early_exit`
+pub trait EarlyExitObserver: Observer {
+	/// checked after each `on_step_complete`
+	fn should_stop(&self) -> bool;
+}
+
+impl dyn Observer {
+	pub fn downcast_ref<T: Observer>(&self) -> Option<&T> {
+		(self as &dyn Any).downcast_ref()
+	}
+}
+
+/// prints every event to stderr, nothing is logged unless it is attached with `Dish::add_observer`
+#[derive(Debug, Default)]
+pub struct LogObserver;
+
+impl Observer for LogObserver {
+	fn on_step_complete(&mut self, _dish: &Dish, stats: &SimStats) {
+		eprintln!("step complete, entropy {:?}", stats.entropy.back());
+	}
+
+	fn on_cell_changed(&mut self, x: usize, y: usize, old: Cell, new: Cell) {
+		eprintln!("cell {x},{y} changed from {} to {}", old.0, new.0);
+	}
+
+	fn on_rule_fired(&mut self, rule_index: usize, variant_index: usize, x: isize, y: isize) {
+		eprintln!("rule {rule_index} variant {variant_index} fired at {x},{y}");
+	}
+}
+
+/// counts events, read it back with `Dish::observers` and `downcast_ref`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CountingObserver {
+	/// times each rule fired, indexed by rule
+	pub fires: Vec<u64>,
+	pub changes: u64,
+	pub steps: u64,
+}
+
+impl Observer for CountingObserver {
+	fn on_step_complete(&mut self, _dish: &Dish, _stats: &SimStats) {
+		self.steps += 1;
+	}
+
+	fn on_cell_changed(&mut self, _x: usize, _y: usize, _old: Cell, _new: Cell) {
+		self.changes += 1;
+	}
+
+	fn on_rule_fired(&mut self, rule_index: usize, _variant_index: usize, _x: isize, _y: isize) {
+		if self.fires.len() <= rule_index {
+			self.fires.resize(rule_index + 1, 0);
+		}
+		self.fires[rule_index] += 1;
+	}
+}
+
+/// stops once no rule can change the world, see `Dish::is_quiescent`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QuiescenceObserver {
+	pub stopped: bool,
+}
+
+impl Observer for QuiescenceObserver {
+	fn on_step_complete(&mut self, dish: &Dish, _stats: &SimStats) {
+		self.stopped = dish.is_quiescent();
+	}
+}
+
+impl EarlyExitObserver for QuiescenceObserver {
+	fn should_stop(&self) -> bool {
+		self.stopped
+	}
+}
+
+/// stops once the world holds more than `max_count` of `target_cell`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaxCellCountObserver {
+	pub target_cell: Cell,
+	pub max_count: usize,
+	pub stopped: bool,
+}
+
+impl MaxCellCountObserver {
+	pub fn new(target_cell: Cell, max_count: usize) -> Self {
+		Self {
+			target_cell,
+			max_count,
+			stopped: false,
+		}
+	}
+}
+
+impl Observer for MaxCellCountObserver {
+	fn on_step_complete(&mut self, dish: &Dish, _stats: &SimStats) {
+		self.stopped = dish.count_cells(self.target_cell) > self.max_count;
+	}
+}
+
+impl EarlyExitObserver for MaxCellCountObserver {
+	fn should_stop(&self) -> bool {
+		self.stopped
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::{rngs::StdRng, SeedableRng};
+
+	use super::*;
+	use crate::StepMode;
+
+	/// the default dish with only the fall rule, an empty world and `observer` attached
+	fn falling_dish(observer: impl Observer) -> Dish {
+		let mut dish = Dish::new();
+		dish.rules.retain(|r| r.name == "fall");
+		dish.update_all_rules();
+		dish.fill(Cell(0));
+		dish.add_observer(Box::new(observer));
+		dish.seed(0);
+		dish
+	}
+
+	#[test]
+	fn counting_observer_counts_events() {
+		let mut dish = falling_dish(CountingObserver::default());
+		dish.set_cell(3, 3, Cell(1));
+		// writing the same cell again is not a change
+		dish.set_cell(3, 3, Cell(1));
+		dish.update_cache(3, 3, 1, 1);
+		for _ in 0..40 {
+			dish.step(StepMode::Batch, 1);
+		}
+		let landed = (0..dish.world_height())
+			.position(|y| dish.get_cell(3, y) == Some(Cell(1)))
+			.unwrap();
+		assert_eq!(landed, dish.world_height() - 1);
+
+		let counts = dish.observers()[0]
+			.downcast_ref::<CountingObserver>()
+			.unwrap();
+		let moves = (landed - 3) as u64;
+		assert_eq!(counts.steps, 40);
+		assert_eq!(counts.fires, [moves]);
+		// each fall writes two cells
+		assert_eq!(counts.changes, 1 + 2 * moves);
+	}
+
+	/// counts the steps whose stats don't describe the world as it is
+	#[derive(Debug, Default)]
+	struct StaleStats {
+		steps: u64,
+		stale: u64,
+	}
+
+	impl Observer for StaleStats {
+		fn on_step_complete(&mut self, dish: &Dish, stats: &SimStats) {
+			self.steps += 1;
+			if stats.world_hash != dish.world_hash()
+				|| stats.entropy.back() != Some(&dish.world_entropy())
+			{
+				self.stale += 1;
+			}
+		}
+	}
+
+	impl EarlyExitObserver for StaleStats {
+		fn should_stop(&self) -> bool {
+			false
+		}
+	}
+
+	#[test]
+	fn observers_get_fresh_stats() {
+		let mut dish = falling_dish(StaleStats::default());
+		for x in 0..8 {
+			dish.set_cell(x * 3, x, Cell(1));
+		}
+		dish.rebuild_cache();
+		for _ in 0..20 {
+			dish.step(StepMode::Batch, 1);
+		}
+		let attached = dish.observers()[0].downcast_ref::<StaleStats>().unwrap();
+		assert_eq!((attached.steps, attached.stale), (20, 0));
+
+		let mut passed = StaleStats::default();
+		let mut rng = StdRng::seed_from_u64(678);
+		dish.simulate_with_observer_and_early_exit(&mut passed, 20, &mut rng);
+		assert_eq!((passed.steps, passed.stale), (20, 0));
+	}
+
+	#[test]
+	fn early_exit_observers_stop_the_run() {
+		let mut dish = falling_dish(CountingObserver::default());
+		let grains = [(3, 3), (3, 10), (3, 11), (20, 0), (31, 30)];
+		for (x, y) in grains {
+			dish.set_cell(x, y, Cell(1));
+		}
+		dish.rebuild_cache();
+
+		let mut quiet = QuiescenceObserver::default();
+		let mut rng = StdRng::seed_from_u64(695);
+		let steps = dish.simulate_with_observer_and_early_exit(&mut quiet, 10_000, &mut rng);
+		assert!(quiet.should_stop());
+		assert!(dish.is_quiescent());
+		// every step moves one grain down by one until they have all landed
+		let bottom = dish.world_height() - 1;
+		let landed = [bottom - 2, bottom - 1, bottom, bottom, bottom];
+		let distance: usize = grains.iter().zip(landed).map(|((_, y), end)| end - y).sum();
+		assert_eq!(steps, distance as u64);
+		assert_eq!(
+			dish.simulate_with_observer_and_early_exit(&mut quiet, 10_000, &mut rng),
+			1
+		);
+
+		dish.rules = vec![crate::dsl::parse_rule("0 -> 1").unwrap()];
+		dish.rules[0].enabled = true;
+		dish.update_all_rules();
+		let mut limit = MaxCellCountObserver::new(Cell(1), 40);
+		let steps = dish.simulate_with_observer_and_early_exit(&mut limit, 10_000, &mut rng);
+		assert!(limit.stopped);
+		assert_eq!(steps, 36);
+		assert_eq!(dish.count_cells(Cell(1)), 41);
+	}
+}
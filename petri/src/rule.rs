@@ -0,0 +1,1715 @@
+use std::{
+	collections::hash_map::DefaultHasher,
+	fmt,
+	hash::{Hash, Hasher},
+};
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	dsl::{self, parse_id_grids},
+	dynamic::WorldView,
+	Cell, CellData, CellGroup, ExportError,
+};
+
+/// longest rule name `Dish::validate_rule_names` accepts, in chars
+pub const MAX_RULE_NAME_LEN: usize = 64;
+
+/// default characters for cell types in text grids, the nth char stands for cell type n
+pub const TEXT_GRID_CHARS: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+#[cfg(test)]
+thread_local! {
+	/// calls of `Rule::generate_variants` on this thread
+	static VARIANT_GENERATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+	#[serde(default)]
+	pub name: String,
+	/// free text shown to users, has no effect on the simulation
+	#[serde(default)]
+	pub description: String,
+	pub(crate) base: SubRule,
+	#[serde(skip)]
+	pub(crate) variants: Vec<SubRule>,
+	pub enabled: bool,
+	/// why the rule was disabled, shown to users. cleared when it is enabled again
+	#[serde(default)]
+	pub enabled_reason: Option<String>,
+	pub flip_x: bool,
+	pub flip_y: bool,
+	pub rotate: bool,
+	#[serde(default)]
+	pub failrate: u8,
+	/// always run at the normal rate regardless of the dish temperature
+	#[serde(default)]
+	pub ignore_temperature: bool,
+	/// only used by `Dish::step_phased`, which runs one phase at a time
+	#[serde(default)]
+	pub phase: u8,
+	/// only match where the origin of the match lies inside this rect
+	#[serde(default)]
+	pub region_mask: Option<RegionMask>,
+	/// only match during these steps of `Dish::step_count`
+	#[serde(default)]
+	pub schedule: Option<Schedule>,
+	/// permutation of variant indices, earlier variants are picked more often.
+	/// reset to identity (uniform selection) whenever the variants are regenerated
+	#[serde(skip)]
+	pub(crate) variant_priority: Vec<usize>,
+}
+
+/// Rect of world positions a rule can match at.
+/// Variant origins follow the top-left cell of the base pattern through flips and rotations,
+/// so the mask refers to the same part of the pattern in every variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RegionMask {
+	pub x: usize,
+	pub y: usize,
+	pub w: usize,
+	pub h: usize,
+}
+
+/// the given order is not a permutation of the rule's variant indices
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidPermutation;
+
+/// grids that `Rule::from_pattern_string` can't read, see `dsl::ParseError` for the rule text format
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+	/// neither grid has a cell
+	Empty,
+	/// a token that is not a cell id, `*` or `_`
+	InvalidToken(String),
+	/// rows differ in length, within a grid or between the from and to grids
+	JaggedRows,
+}
+
+impl From<dsl::ParseError> for ParseError {
+	fn from(error: dsl::ParseError) -> Self {
+		match error {
+			dsl::ParseError::Empty => ParseError::Empty,
+			dsl::ParseError::InvalidToken { token, .. } => ParseError::InvalidToken(token),
+			dsl::ParseError::SidesDiffer { .. } | dsl::ParseError::RaggedRow { .. } => {
+				ParseError::JaggedRows
+			}
+			dsl::ParseError::MissingArrow { .. } => unreachable!("id grids have no arrow"),
+		}
+	}
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ParseError::Empty => write!(f, "no pattern"),
+			ParseError::InvalidToken(token) => write!(f, "invalid cell '{token}'"),
+			ParseError::JaggedRows => write!(f, "rows have different lengths"),
+		}
+	}
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SubRule {
+	pub(crate) width: usize,
+	pub(crate) height: usize,
+	/// offset from top-left corner used to find and sample matches fairly, normally only used by rotated/mirrored variants
+	#[serde(default, skip)]
+	pub(crate) origin_x: usize,
+	/// offset from top-left corner used to find and sample matches fairly, normally only used by rotated/mirrored variants
+	#[serde(default, skip)]
+	pub(crate) origin_y: usize,
+	pub(crate) contents: Vec<(RuleCellFrom, RuleCellTo)>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Hash, Serialize, Deserialize)]
+pub enum RuleCellFrom {
+	/// matches anything
+	#[default]
+	Any,
+	/// matches one cell type
+	One(Cell),
+	/// matches anything defined in the group referenced by this index
+	Group(usize),
+	/// matches only positions outside the world
+	BoundaryVoid,
+}
+
+impl RuleCellFrom {
+	/// whether a world position holding `cell` (None outside the world) fits this input
+	pub fn matches(&self, cell: Option<Cell>, groups: &[CellGroup]) -> bool {
+		match self {
+			RuleCellFrom::Any => true,
+			RuleCellFrom::One(rule_cell) => cell.unwrap_or(Cell::BOUNDARY) == *rule_cell,
+			RuleCellFrom::Group(g) => groups.get(*g).is_some_and(|g| g.contains(cell)),
+			RuleCellFrom::BoundaryVoid => cell.is_none(),
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Hash, Serialize, Deserialize)]
+pub enum RuleCellTo {
+	/// don't modify this cell
+	#[default]
+	None,
+	/// set to this cell
+	One(Cell),
+	/// randomly choose from the group
+	GroupRandom(usize),
+	/// randomly choose a member of the group other than the current cell,
+	/// does nothing if there is no other member
+	GroupRandomOther(usize),
+	/// copy the cell from the corresponding input position
+	Copy(usize, usize),
+}
+
+/// side of the world, used by source and sink rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Edge {
+	Top,
+	Bottom,
+	Left,
+	Right,
+}
+
+/// Steps of `Dish::step_count` during which a rule is active.
+/// Ranges include the start and exclude the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Schedule {
+	After(u64),
+	Until(u64),
+	Between(u64, u64),
+	/// active for the first `duty` steps of every `period` steps
+	Periodic {
+		period: u64,
+		duty: u64,
+	},
+}
+
+impl Schedule {
+	pub fn is_active(&self, step: u64) -> bool {
+		match *self {
+			Schedule::After(start) => step >= start,
+			Schedule::Until(end) => step < end,
+			Schedule::Between(start, end) => (start..end).contains(&step),
+			Schedule::Periodic { period, duty } => step % period.max(1) < duty,
+		}
+	}
+
+	/// the first step after `step` where `is_active` changes, None if it never changes again
+	pub fn next_transition(&self, step: u64) -> Option<u64> {
+		match *self {
+			Schedule::After(at) | Schedule::Until(at) => (step < at).then_some(at),
+			Schedule::Between(start, end) if start >= end => None,
+			Schedule::Between(start, end) => [start, end].into_iter().find(|&at| step < at),
+			Schedule::Periodic { period, duty } => {
+				if duty == 0 || duty >= period {
+					return None;
+				}
+				let cycle_start = step - step % period;
+				let next = if step % period < duty {
+					cycle_start + duty
+				} else {
+					cycle_start + period
+				};
+				Some(next)
+			}
+		}
+	}
+}
+
+impl RegionMask {
+	pub fn contains(&self, x: isize, y: isize) -> bool {
+		x >= self.x as isize
+			&& y >= self.y as isize
+			&& x < (self.x + self.w) as isize
+			&& y < (self.y + self.h) as isize
+	}
+}
+
+impl SubRule {
+	/// one cell next to the outside of the world on the given side
+	fn at_edge(edge: Edge, cell: (RuleCellFrom, RuleCellTo)) -> Self {
+		let void = (RuleCellFrom::BoundaryVoid, RuleCellTo::None);
+		let (width, height, contents) = match edge {
+			Edge::Top => (1, 2, vec![void, cell]),
+			Edge::Bottom => (1, 2, vec![cell, void]),
+			Edge::Left => (2, 1, vec![void, cell]),
+			Edge::Right => (2, 1, vec![cell, void]),
+		};
+		Self {
+			width,
+			height,
+			origin_x: 0,
+			origin_y: 0,
+			contents,
+		}
+	}
+
+	fn new() -> Self {
+		Self {
+			width: 1,
+			height: 1,
+			origin_x: 0,
+			origin_y: 0,
+			contents: vec![Default::default()],
+		}
+	}
+
+	pub(crate) fn get(&self, x: usize, y: usize) -> (RuleCellFrom, RuleCellTo) {
+		if x >= self.width || y >= self.height {
+			Default::default()
+		} else {
+			self.contents[x + self.width * y].clone()
+		}
+	}
+
+	/// (a, b) for a 1x2 or 2x1 pattern that swaps two different cells, a being the top or left one
+	fn swapped_pair(&self) -> Option<(Cell, Cell)> {
+		let [(RuleCellFrom::One(a), to_a), (RuleCellFrom::One(b), to_b)] = &self.contents[..]
+		else {
+			return None;
+		};
+		let output = |to: &RuleCellTo| match to {
+			RuleCellTo::One(cell) => Some(*cell),
+			RuleCellTo::Copy(x, y) => match self.get(*x, *y).0 {
+				RuleCellFrom::One(cell) => Some(cell),
+				_ => None,
+			},
+			_ => None,
+		};
+		(a != b && output(to_a) == Some(*b) && output(to_b) == Some(*a)).then_some((*a, *b))
+	}
+
+	fn get_mut(&mut self, x: usize, y: usize) -> &mut (RuleCellFrom, RuleCellTo) {
+		assert!(x < self.width || y < self.height);
+		&mut self.contents[x + self.width * y]
+	}
+
+	fn set_both(&mut self, x: usize, y: usize, cells: (RuleCellFrom, RuleCellTo)) {
+		if x < self.width && y < self.height {
+			self.contents[x + self.width * y] = cells;
+		}
+	}
+
+	fn set_from(&mut self, x: usize, y: usize, cell: RuleCellFrom) {
+		if x < self.width && y < self.height {
+			self.contents[x + self.width * y].0 = cell;
+		}
+	}
+
+	fn set_to(&mut self, x: usize, y: usize, cell: RuleCellTo) {
+		if x < self.width && y < self.height {
+			self.contents[x + self.width * y].1 = cell;
+		}
+	}
+
+	fn scaled(&self, factor: usize) -> Self {
+		assert!(factor >= 1, "scale factor must be at least 1");
+		let width = self.width * factor;
+		let height = self.height * factor;
+		let mut contents = Vec::with_capacity(width * height);
+		for y in 0..height {
+			for x in 0..width {
+				let (from, mut to) = self.get(x / factor, y / factor);
+				if let RuleCellTo::Copy(cx, cy) = &mut to {
+					*cx = *cx * factor + x % factor;
+					*cy = *cy * factor + y % factor;
+				}
+				contents.push((from, to));
+			}
+		}
+		Self {
+			width,
+			height,
+			origin_x: self.origin_x * factor,
+			origin_y: self.origin_y * factor,
+			contents,
+		}
+	}
+
+	fn flipped_x(&self) -> Self {
+		let mut new = self.clone();
+		new.origin_x = new.width - new.origin_x - 1;
+		for y in 0..new.height {
+			for x in 0..new.width {
+				let mut cell = self.get(new.width - x - 1, y);
+				if let (_, RuleCellTo::Copy(cx, _cy)) = &mut cell {
+					*cx = new.width - *cx - 1;
+				}
+				new.set_both(x, y, cell);
+			}
+		}
+		new
+	}
+
+	fn flipped_y(&self) -> Self {
+		let mut new = self.clone();
+		new.origin_y = new.height - new.origin_y - 1;
+		for y in 0..new.height {
+			for x in 0..new.width {
+				let mut cell = self.get(x, new.height - y - 1);
+				if let (_, RuleCellTo::Copy(_cx, cy)) = &mut cell {
+					*cy = new.height - *cy - 1;
+				}
+				new.set_both(x, y, cell);
+			}
+		}
+		new
+	}
+
+	/// same as flipping x and y
+	fn rotated_180(&self) -> Self {
+		let mut new = self.clone();
+		new.origin_x = new.width - new.origin_x - 1;
+		new.origin_y = new.height - new.origin_y - 1;
+		for y in 0..new.height {
+			for x in 0..new.width {
+				let mut cell = self.get(new.width - x - 1, new.height - y - 1);
+				if let (_, RuleCellTo::Copy(cx, cy)) = &mut cell {
+					let new_x = new.width - *cx - 1;
+					let new_y = new.height - *cy - 1;
+					(*cx, *cy) = (new_x, new_y);
+				}
+				new.set_both(x, y, cell);
+			}
+		}
+		new
+	}
+
+	/// rotate 90° clockwise
+	fn rotated_90(&self) -> Self {
+		let mut new = self.clone();
+		new.height = self.width;
+		new.width = self.height;
+		new.origin_x = self.height - self.origin_y - 1;
+		new.origin_y = self.origin_x;
+		for y in 0..new.height {
+			for x in 0..new.width {
+				let mut cell = self.get(y, new.width - x - 1);
+				if let (_, RuleCellTo::Copy(cx, cy)) = &mut cell {
+					let new_x = self.height - *cy - 1;
+					let new_y = *cx;
+					(*cx, *cy) = (new_x, new_y);
+				}
+				new.set_both(x, y, cell);
+			}
+		}
+		new
+	}
+
+	/// whether the pattern matches with its top-left corner at this position, `get_cell` returns None out of bounds
+	pub(crate) fn matches_at(
+		&self,
+		corner_x: isize,
+		corner_y: isize,
+		groups: &[CellGroup],
+		get_cell: impl Fn(isize, isize) -> Option<Cell>,
+	) -> bool {
+		for dx in 0..self.width {
+			for dy in 0..self.height {
+				let x = corner_x.wrapping_add_unsigned(dx);
+				let y = corner_y.wrapping_add_unsigned(dy);
+				if !self.get(dx, dy).0.matches(get_cell(x, y), groups) {
+					return false;
+				}
+			}
+		}
+		true
+	}
+
+	/// A pattern position (dx, dy) that only matches cells from the list, never positions outside the world.
+	/// Picks the most selective one, None if every position matches the outside or anything.
+	pub(crate) fn required_cells(&self, groups: &[CellGroup]) -> Option<(usize, usize, Vec<Cell>)> {
+		let mut best: Option<(usize, usize, Vec<Cell>)> = None;
+		for dy in 0..self.height {
+			for dx in 0..self.width {
+				let cells = match self.get(dx, dy).0 {
+					RuleCellFrom::One(cell) if !cell.is_boundary() => vec![cell],
+					RuleCellFrom::Group(group) => match groups.get(group) {
+						Some(group) if !group.contains(None) => group.cells.clone(),
+						_ => continue,
+					},
+					_ => continue,
+				};
+				// air is in nearly every chunk, so it rarely lets a chunk be skipped
+				let cost = |cells: &[Cell]| (cells.contains(&Cell(0)), cells.len());
+				if best.as_ref().is_none_or(|(_, _, b)| cost(&cells) < cost(b)) {
+					best = Some((dx, dy, cells));
+				}
+			}
+		}
+		best
+	}
+
+	/// offset of the match position from the top-left corner
+	pub fn origin(&self) -> (usize, usize) {
+		(self.origin_x, self.origin_y)
+	}
+
+	/// world rect (corner x, corner y, width, height) covered by a match at (x, y)
+	pub fn footprint(&self, x: isize, y: isize) -> (isize, isize, usize, usize) {
+		(
+			x.wrapping_sub_unsigned(self.origin_x),
+			y.wrapping_sub_unsigned(self.origin_y),
+			self.width,
+			self.height,
+		)
+	}
+
+	/// the match position whose footprint has its corner here, the inverse of `footprint`
+	pub(crate) fn match_at_corner(&self, corner_x: isize, corner_y: isize) -> (isize, isize) {
+		(
+			corner_x.wrapping_add_unsigned(self.origin_x),
+			corner_y.wrapping_add_unsigned(self.origin_y),
+		)
+	}
+
+	/// cells under the pattern with its corner at this position, row-major like `contents`
+	pub(crate) fn read_footprint(
+		&self,
+		corner_x: isize,
+		corner_y: isize,
+		get_cell: impl Fn(isize, isize) -> Option<Cell>,
+	) -> Vec<Option<Cell>> {
+		let mut cells = Vec::with_capacity(self.width * self.height);
+		for dy in 0..self.height {
+			for dx in 0..self.width {
+				cells.push(get_cell(
+					corner_x.wrapping_add_unsigned(dx),
+					corner_y.wrapping_add_unsigned(dy),
+				));
+			}
+		}
+		cells
+	}
+
+	/// write the outputs of the pattern, `old_state` is the footprint from before any writes
+	pub(crate) fn write_outputs(
+		&self,
+		corner_x: isize,
+		corner_y: isize,
+		old_state: &[Option<Cell>],
+		groups: &[CellGroup],
+		rng: &mut impl Rng,
+		mut set_cell: impl FnMut(isize, isize, Cell),
+	) {
+		for dx in 0..self.width {
+			for dy in 0..self.height {
+				let px = corner_x.wrapping_add_unsigned(dx);
+				let py = corner_y.wrapping_add_unsigned(dy);
+
+				match self.get(dx, dy).1 {
+					RuleCellTo::One(rule_cell) => {
+						if !rule_cell.is_boundary() {
+							set_cell(px, py, rule_cell);
+						}
+					}
+					RuleCellTo::GroupRandom(group_id) => {
+						let group = &groups[group_id];
+						let count = group.placeable_cells().count();
+						if count > 0 {
+							let i = rng.gen::<usize>() % count;
+							let cell = group.placeable_cells().nth(i).unwrap();
+							set_cell(px, py, cell);
+						}
+					}
+					RuleCellTo::GroupRandomOther(group_id) => {
+						let current = old_state.get(dx + dy * self.width).copied().flatten();
+						let others = || {
+							groups[group_id]
+								.placeable_cells()
+								.filter(move |&c| Some(c) != current)
+						};
+						let count = others().count();
+						if count > 0 {
+							let i = rng.gen::<usize>() % count;
+							set_cell(px, py, others().nth(i).unwrap());
+						}
+					}
+					RuleCellTo::Copy(x, y) => {
+						let index = x + y * self.width;
+						if index >= old_state.len() {
+							// TODO sanitize the rules somewhere else and remove this bounds check
+							// the copy source is outside the rule bounds
+							continue;
+						}
+						let cell = old_state[index];
+						if let Some(cell) = cell {
+							// if the copy source is outside the world, do nothing
+							set_cell(px, py, cell);
+						}
+					}
+					RuleCellTo::None => (),
+				}
+			}
+		}
+	}
+
+	/// Top-left corners of every position where the pattern matches a row-major buffer.
+	/// Includes positions overhanging the edge, where the outside counts as out of bounds.
+	pub fn find_matches_in_buffer(
+		&self,
+		buf: &[Cell],
+		width: usize,
+		height: usize,
+		groups: &[CellGroup],
+	) -> Vec<(isize, isize)> {
+		let mut matches = Vec::new();
+		let border_x = self.width as isize - 1;
+		let border_y = self.height as isize - 1;
+		for x in -border_x..width as isize {
+			for y in -border_y..height as isize {
+				if self.matches_at(x, y, groups, |x, y| buffer_get(buf, width, height, x, y)) {
+					matches.push((x, y));
+				}
+			}
+		}
+		matches
+	}
+
+	/// Apply the pattern with its top-left corner at (x, y), reading `from_buf` and writing `to_buf`.
+	/// Only output cells are written, so `to_buf` should start as a copy of `from_buf`.
+	#[allow(clippy::too_many_arguments)]
+	pub fn apply_at_position(
+		&self,
+		from_buf: &[Cell],
+		to_buf: &mut [Cell],
+		x: isize,
+		y: isize,
+		width: usize,
+		height: usize,
+		groups: &[CellGroup],
+		rng: &mut impl Rng,
+	) {
+		let old_state = self.read_footprint(x, y, |x, y| buffer_get(from_buf, width, height, x, y));
+		self.write_outputs(x, y, &old_state, groups, rng, |x, y, cell| {
+			if buffer_get(to_buf, width, height, x, y).is_some() {
+				to_buf[x as usize + y as usize * width] = cell;
+			}
+		});
+	}
+
+	/// Crop away outer rows and columns that are all (Any, None), keeping cells a copy reads from,
+	/// so patterns that only differ by such padding compare equal. The origin is moved to the new top-left.
+	/// A trivial pattern becomes a single (Any, None) cell.
+	pub fn canonicalize(&self) -> SubRule {
+		let keep = |x: usize, y: usize| {
+			self.get(x, y) != (RuleCellFrom::Any, RuleCellTo::None)
+				|| self
+					.contents
+					.iter()
+					.any(|(_, to)| *to == RuleCellTo::Copy(x, y))
+		};
+		let xs = (0..self.width).filter(|&x| (0..self.height).any(|y| keep(x, y)));
+		let ys = (0..self.height).filter(|&y| (0..self.width).any(|x| keep(x, y)));
+		let (Some(min_x), Some(max_x)) = (xs.clone().min(), xs.max()) else {
+			return SubRule::new();
+		};
+		let (min_y, max_y) = (ys.clone().min().unwrap(), ys.max().unwrap());
+		let width = max_x - min_x + 1;
+		let height = max_y - min_y + 1;
+		let mut contents = Vec::with_capacity(width * height);
+		for y in min_y..=max_y {
+			for x in min_x..=max_x {
+				let (from, mut to) = self.get(x, y);
+				if let RuleCellTo::Copy(cx, cy) = &mut to {
+					*cx -= min_x;
+					*cy -= min_y;
+				}
+				contents.push((from, to));
+			}
+		}
+		SubRule {
+			width,
+			height,
+			origin_x: 0,
+			origin_y: 0,
+			contents,
+		}
+	}
+
+	/// true if no output can ever write a cell: outputs are None, the boundary, empty or missing groups,
+	/// or copies from outside the pattern or the world
+	fn never_writes(&self, groups: &[CellGroup]) -> bool {
+		let empty_group = |g: usize| {
+			groups
+				.get(g)
+				.is_none_or(|group| group.placeable_cells().next().is_none())
+		};
+		self.contents.iter().all(|(_, to)| match to {
+			RuleCellTo::None => true,
+			RuleCellTo::One(cell) => cell.is_boundary(),
+			RuleCellTo::GroupRandom(g) | RuleCellTo::GroupRandomOther(g) => empty_group(*g),
+			RuleCellTo::Copy(x, y) => {
+				*x >= self.width
+					|| *y >= self.height
+					|| match self.get(*x, *y).0 {
+						RuleCellFrom::BoundaryVoid => true,
+						RuleCellFrom::One(cell) => cell.is_boundary(),
+						_ => false,
+					}
+			}
+		})
+	}
+
+	/// whether x, y is a cell of the pattern, like `Dish::is_in_bounds` for the world
+	pub fn is_in_bounds(&self, x: isize, y: isize) -> bool {
+		x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+	}
+
+	/// true if every cell is (Any, None), so the rule can never change the world
+	pub fn is_trivial(&self) -> bool {
+		self.contents
+			.iter()
+			.all(|c| c == &(RuleCellFrom::Any, RuleCellTo::None))
+	}
+
+	/// true if every output either does nothing or copies the cell from its own position
+	pub fn is_identity(&self) -> bool {
+		self.contents
+			.iter()
+			.enumerate()
+			.all(|(i, (_, to))| match to {
+				RuleCellTo::None => true,
+				RuleCellTo::Copy(x, y) => x + self.width * y == i,
+				_ => false,
+			})
+	}
+
+	/// Rows of from tokens, then " -> ", then to tokens, with tokens separated by spaces.
+	/// The nth char of `from_chars`/`to_chars` stands for cell id n, `*` is Any, `_` is None,
+	/// `#` is outside the world, `g<n>` a group, `g<n>!` a different member of a group
+	/// and `c(x,y)` a copy.
+	pub fn as_text_grid(&self, from_chars: &str, to_chars: &str) -> Result<String, ExportError> {
+		match self.text_grid(from_chars, to_chars) {
+			(_, Some(e)) => Err(e),
+			(text, None) => Ok(text),
+		}
+	}
+
+	/// the grid with `?` for cells without a char, and the error for the first side missing chars
+	pub(crate) fn text_grid(
+		&self,
+		from_chars: &str,
+		to_chars: &str,
+	) -> (String, Option<ExportError>) {
+		let from_chars: Vec<char> = from_chars.chars().collect();
+		let to_chars: Vec<char> = to_chars.chars().collect();
+		let mut needed = (0, 0);
+		let char_of = |chars: &[char], cell: Cell, needed: &mut usize| {
+			if cell.is_boundary() {
+				return '#'.to_string();
+			}
+			*needed = (*needed).max(cell.id() + 1);
+			chars.get(cell.id()).copied().unwrap_or('?').to_string()
+		};
+		let mut rows = Vec::new();
+		for y in 0..self.height {
+			let mut from = Vec::new();
+			let mut to = Vec::new();
+			for x in 0..self.width {
+				let (from_cell, to_cell) = self.get(x, y);
+				from.push(match from_cell {
+					RuleCellFrom::Any => "*".into(),
+					RuleCellFrom::One(cell) => char_of(&from_chars, cell, &mut needed.0),
+					RuleCellFrom::Group(g) => format!("g{g}"),
+					RuleCellFrom::BoundaryVoid => "#".into(),
+				});
+				to.push(match to_cell {
+					RuleCellTo::None => "_".into(),
+					RuleCellTo::One(cell) => char_of(&to_chars, cell, &mut needed.1),
+					RuleCellTo::GroupRandom(g) => format!("g{g}"),
+					RuleCellTo::GroupRandomOther(g) => format!("g{g}!"),
+					RuleCellTo::Copy(x, y) => format!("c({x},{y})"),
+				});
+			}
+			rows.push((from.join(" "), to.join(" ")));
+		}
+		let from_width = rows.iter().map(|(from, _)| from.len()).max().unwrap_or(0);
+		let text = rows
+			.iter()
+			.map(|(from, to)| format!("{from:from_width$} -> {to}"))
+			.collect::<Vec<_>>()
+			.join("\n");
+		let missing = [(needed.0, from_chars.len()), (needed.1, to_chars.len())]
+			.into_iter()
+			.find(|&(needed, got)| needed > got)
+			.map(|(needed, got)| ExportError::NotEnoughChars { needed, got });
+		(text, missing)
+	}
+
+	/// the pattern as a table, from side then to side, see `Rule::grid_display`
+	fn table(&self, types: &[CellData], style: &TableStyle) -> String {
+		let name_of = |cell: Cell| -> (String, Option<[u8; 3]>) {
+			if cell.is_boundary() {
+				return ("#".into(), None);
+			}
+			match types.get(cell.id()) {
+				Some(data) => (
+					data.name.chars().next().unwrap_or(' ').to_string(),
+					Some(data.color),
+				),
+				None => (cell.id().to_string(), None),
+			}
+		};
+		let tokens: Vec<_> = self
+			.contents
+			.iter()
+			.map(|(from, to)| {
+				let from = match from {
+					RuleCellFrom::Any => ("*".into(), None),
+					RuleCellFrom::One(cell) => name_of(*cell),
+					RuleCellFrom::Group(g) => (format!("g{g}"), None),
+					RuleCellFrom::BoundaryVoid => ("#".into(), None),
+				};
+				let to = match to {
+					RuleCellTo::None => ("_".into(), None),
+					RuleCellTo::One(cell) => name_of(*cell),
+					RuleCellTo::GroupRandom(g) => (format!("g{g}"), None),
+					RuleCellTo::GroupRandomOther(g) => (format!("g{g}!"), None),
+					RuleCellTo::Copy(x, y) => (format!("c({x},{y})"), None),
+				};
+				(from, to)
+			})
+			.collect();
+		let width = tokens
+			.iter()
+			.flat_map(|(from, to)| [from, to])
+			.map(|(text, _)| text.chars().count())
+			.max()
+			.unwrap_or(1);
+		let line = |[left, cross, divider, right]: [&str; 4]| {
+			let side = vec![style.horizontal.repeat(width + 2); self.width].join(cross);
+			format!("{left}{side}{divider}{side}{right}")
+		};
+		let cell = |(text, color): &(String, Option<[u8; 3]>)| {
+			let padded = format!(" {text:^width$} ");
+			if style.color {
+				colored(padded, *color)
+			} else {
+				padded
+			}
+		};
+		let mut lines = vec![line(style.top)];
+		for y in 0..self.height {
+			if y > 0 {
+				lines.push(line(style.middle));
+			}
+			let row = &tokens[y * self.width..(y + 1) * self.width];
+			let from: Vec<String> = row.iter().map(|(from, _)| cell(from)).collect();
+			let to: Vec<String> = row.iter().map(|(_, to)| cell(to)).collect();
+			lines.push(format!(
+				"{v}{}{}{}{v}",
+				from.join(style.vertical),
+				style.divider,
+				to.join(style.vertical),
+				v = style.vertical
+			));
+		}
+		lines.push(line(style.bottom));
+		lines.join("\n")
+	}
+}
+
+impl Default for Rule {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+	FlipX,
+	FlipY,
+	Rotate90,
+	Rotate180,
+	Rotate270,
+}
+
+type ResizeParam = (isize, isize, isize, isize);
+
+impl Rule {
+	pub const EXTEND_LEFT: ResizeParam = (1, 0, -1, 0);
+	pub const EXTEND_RIGHT: ResizeParam = (1, 0, 0, 0);
+	pub const EXTEND_UP: ResizeParam = (0, 1, 0, -1);
+	pub const EXTEND_DOWN: ResizeParam = (0, 1, 0, 0);
+	pub const SHRINK_LEFT: ResizeParam = (-1, 0, 1, 0);
+	pub const SHRINK_RIGHT: ResizeParam = (-1, 0, 0, 0);
+	pub const SHRINK_UP: ResizeParam = (0, -1, 0, 1);
+	pub const SHRINK_DOWN: ResizeParam = (0, -1, 0, 0);
+
+	pub fn new() -> Self {
+		Self {
+			name: "new rule".into(),
+			description: String::new(),
+			enabled: false,
+			enabled_reason: None,
+			base: SubRule::new(),
+			variants: vec![SubRule::new()],
+			flip_x: false,
+			flip_y: false,
+			rotate: false,
+			failrate: 0,
+			ignore_temperature: false,
+			phase: 0,
+			region_mask: None,
+			schedule: None,
+			variant_priority: vec![0],
+		}
+	}
+
+	/// trim the name, collapse whitespace to single spaces and replace other control characters with '_'
+	pub fn sanitize_name(&mut self) {
+		self.name = self
+			.name
+			.split_whitespace()
+			.map(|word| word.replace(char::is_control, "_"))
+			.collect::<Vec<_>>()
+			.join(" ");
+	}
+
+	pub fn get(&self, x: usize, y: usize) -> (RuleCellFrom, RuleCellTo) {
+		self.base.get(x, y)
+	}
+
+	pub fn get_mut(&mut self, x: usize, y: usize) -> &mut (RuleCellFrom, RuleCellTo) {
+		self.base.get_mut(x, y)
+	}
+
+	pub fn set_from(&mut self, x: usize, y: usize, cell: RuleCellFrom) {
+		self.base.set_from(x, y, cell);
+		self.generate_variants();
+	}
+
+	pub fn set_to(&mut self, x: usize, y: usize, cell: RuleCellTo) {
+		self.base.set_to(x, y, cell);
+		self.generate_variants();
+	}
+
+	/// `SubRule::as_text_grid` of the base and then every variant, with `?` for cells without a char
+	pub fn as_text_grid(&self, from_chars: &str, to_chars: &str) -> String {
+		let mut text = self.base.text_grid(from_chars, to_chars).0;
+		for (i, variant) in self.variants.iter().enumerate() {
+			text += &format!("\n\nvariant {i}:\n");
+			text += &variant.text_grid(from_chars, to_chars).0;
+		}
+		text
+	}
+
+	/// The base pattern as a box-drawing table, the from side left of `║` and the to side right of it.
+	/// Cells show the first letter of their type name, or their id if they have no type.
+	/// `*` is Any and `_` is None, other cells are written like in `SubRule::as_text_grid`.
+	/// With the `ansi` feature cells are colored with the type color.
+	pub fn grid_display(&self, types: &[CellData]) -> String {
+		self.base.table(types, &UNICODE_TABLE)
+	}
+
+	/// `grid_display` drawn with plain ascii and no colors
+	pub fn ascii_display(&self, types: &[CellData]) -> String {
+		self.base.table(types, &ASCII_TABLE)
+	}
+
+	/// Describe the base pattern. Two cells swapping places become "<cell> fall", "rise" or "slide left/right",
+	/// where the moving cell is the one that isn't empty (id 0). Anything else is "rule_<width>x<height>".
+	/// Left-right symmetric patterns get " (sym)" appended.
+	pub fn auto_name(&self, types: &[CellData]) -> String {
+		let base = &self.base;
+		let name = match base.swapped_pair() {
+			Some((first, second)) => {
+				let forward = first != Cell(0);
+				let moving = if forward { first } else { second };
+				let direction = match (base.width, forward) {
+					(1, true) => "fall",
+					(1, false) => "rise",
+					(_, true) => "slide right",
+					(_, false) => "slide left",
+				};
+				match types.get(moving.id()) {
+					Some(data) => format!("{} {direction}", data.name),
+					None => direction.to_owned(),
+				}
+			}
+			None => format!("rule_{}x{}", base.width, base.height),
+		};
+		if base.width > 1 && base.contents == base.flipped_x().contents {
+			format!("{name} (sym)")
+		} else {
+			name
+		}
+	}
+
+	/// enabled, and inside its schedule if it has one
+	pub fn is_active_at(&self, step: u64) -> bool {
+		self.enabled && self.schedule.is_none_or(|s| s.is_active(step))
+	}
+
+	/// false while the rule still has the name `auto_name` would give it
+	pub fn has_been_renamed(&self, types: &[CellData]) -> bool {
+		self.name != self.auto_name(types)
+	}
+
+	/// enable or disable the rule, remembering the reason only when disabling
+	pub fn set_enabled_with_reason(&mut self, enabled: bool, reason: impl Into<String>) {
+		self.enabled = enabled;
+		self.enabled_reason = (!enabled).then(|| reason.into());
+	}
+
+	/// why the rule is disabled, None if it is enabled or no reason was given
+	pub fn disable_reason(&self) -> Option<&str> {
+		self.enabled_reason.as_deref().filter(|_| !self.enabled)
+	}
+
+	pub fn base(&self) -> &SubRule {
+		&self.base
+	}
+
+	/// `SubRule::canonicalize` of the base pattern
+	pub fn canonical_base(&self) -> SubRule {
+		self.base.canonicalize()
+	}
+
+	/// flipped and rotated copies of the base, in the order used by match caches
+	pub fn variants(&self) -> &[SubRule] {
+		&self.variants
+	}
+
+	/// An enabled rule that places `cell` on empty (id 0) cells along the edge.
+	/// `rate` from 0 to 1 is the chance a picked match is applied, stored as `failrate`.
+	pub fn source(cell: Cell, edge: Edge, rate: f32) -> Self {
+		let mut rule = Self {
+			name: "source".into(),
+			enabled: true,
+			failrate: ((1. - rate.clamp(0., 1.)) * 256.).round().min(255.) as u8,
+			..Self::new()
+		};
+		rule.paste_base(SubRule::at_edge(
+			edge,
+			(RuleCellFrom::One(Cell(0)), RuleCellTo::One(cell)),
+		));
+		rule
+	}
+
+	/// an enabled rule that clears cells matching `from` along the edge to empty (id 0)
+	pub fn sink(from: RuleCellFrom, edge: Edge) -> Self {
+		let mut rule = Self {
+			name: "sink".into(),
+			enabled: true,
+			..Self::new()
+		};
+		rule.paste_base(SubRule::at_edge(edge, (from, RuleCellTo::One(Cell(0)))));
+		rule
+	}
+
+	/// An enabled rule from input and output grids of cell ids, one row per line.
+	/// `*` or `_` is Any in the input and no change in the output, e.g. `"1\n0"`, `"0\n1"` for falling sand.
+	/// See `dsl::parse_id_grids`.
+	pub fn from_pattern_string(from_pattern: &str, to_pattern: &str) -> Result<Self, ParseError> {
+		Self::from_pattern_string_with_flags(from_pattern, to_pattern, false, false, false)
+	}
+
+	/// like `from_pattern_string`, with the symmetry flags set
+	pub fn from_pattern_string_with_flags(
+		from_pattern: &str,
+		to_pattern: &str,
+		flip_x: bool,
+		flip_y: bool,
+		rotate: bool,
+	) -> Result<Self, ParseError> {
+		let mut rule = Self {
+			enabled: true,
+			flip_x,
+			flip_y,
+			rotate,
+			..Self::new()
+		};
+		rule.paste_base(parse_id_grids(from_pattern, to_pattern)?);
+		Ok(rule)
+	}
+
+	/// replace the base pattern, resizing the rule to fit it
+	pub fn paste_base(&mut self, pattern: SubRule) {
+		self.base = SubRule {
+			origin_x: 0,
+			origin_y: 0,
+			..pattern
+		};
+		self.generate_variants();
+	}
+
+	/// Makes the output explicitly reproduce the input:
+	/// One(c) becomes One(c), everything else (Any, Group, BoundaryVoid) becomes None.
+	pub fn copy_from_to_to(&mut self) {
+		for (from, to) in &mut self.base.contents {
+			*to = match from {
+				RuleCellFrom::One(cell) => RuleCellTo::One(*cell),
+				RuleCellFrom::Any | RuleCellFrom::Group(_) | RuleCellFrom::BoundaryVoid => {
+					RuleCellTo::None
+				}
+			};
+		}
+		self.generate_variants();
+	}
+
+	pub fn clear_to(&mut self) {
+		for (_, to) in &mut self.base.contents {
+			*to = RuleCellTo::None;
+		}
+		self.generate_variants();
+	}
+
+	/// sets both sides of one position, regenerating variants once
+	pub fn set_cell(&mut self, x: usize, y: usize, from: RuleCellFrom, to: RuleCellTo) {
+		self.base.set_both(x, y, (from, to));
+		self.generate_variants();
+	}
+
+	/// sets several positions, regenerating variants once at the end
+	pub fn set_cells(&mut self, cells: &[(usize, usize, RuleCellFrom, RuleCellTo)]) {
+		for (x, y, from, to) in cells {
+			self.base.set_both(*x, *y, (from.clone(), to.clone()));
+		}
+		self.generate_variants();
+	}
+
+	/// sets every position to the same values
+	pub fn clear(&mut self, fill_from: RuleCellFrom, fill_to: RuleCellTo) {
+		self.base.contents.fill((fill_from, fill_to));
+		self.generate_variants();
+	}
+
+	pub fn height(&self) -> usize {
+		self.base.height
+	}
+
+	pub fn width(&self) -> usize {
+		self.base.width
+	}
+
+	pub(crate) fn max_width(&self) -> usize {
+		self.variants
+			.iter()
+			.map(|r| r.width)
+			.max()
+			.unwrap_or_default()
+	}
+
+	pub(crate) fn max_height(&self) -> usize {
+		self.variants
+			.iter()
+			.map(|r| r.height)
+			.max()
+			.unwrap_or_default()
+	}
+
+	pub fn resize(&mut self, params: ResizeParam) {
+		let (dw, dh, dx, dy) = params;
+
+		let new_width = self.base.width.saturating_add_signed(dw);
+		let new_height = self.base.height.saturating_add_signed(dh);
+		if new_width < 1 || new_height < 1 {
+			return;
+		}
+		let mut new_contents = vec![Default::default(); new_width * new_height];
+
+		for nx in 0..new_width {
+			let oldx = nx.wrapping_add_signed(dx);
+			for ny in 0..new_height {
+				let oldy = ny.wrapping_add_signed(dy);
+				new_contents[nx + new_width * ny] = self.get(oldx, oldy);
+			}
+		}
+
+		self.base.contents = new_contents;
+		self.base.height = new_height;
+		self.base.width = new_width;
+		self.generate_variants();
+	}
+
+	pub fn variant_count(&self) -> usize {
+		self.variants.len()
+	}
+
+	pub fn has_trivial_variant(&self) -> bool {
+		self.variants.iter().any(SubRule::is_trivial)
+	}
+
+	pub fn all_variants_trivial(&self) -> bool {
+		self.variants.iter().all(SubRule::is_trivial)
+	}
+
+	/// the rule can match and fire but never writes a cell, see `ValidationWarning::NeverWrites`
+	pub fn never_writes(&self, groups: &[CellGroup]) -> bool {
+		self.base.never_writes(groups)
+	}
+
+	/// The most cells past the edge of the world a match of this rule can reach,
+	/// None if no input only matches outside the world: `BoundaryVoid`, `Cell::BOUNDARY`
+	/// or a group that matches out of bounds.
+	pub fn out_of_bounds_reach(&self, groups: &[CellGroup]) -> Option<usize> {
+		let reads_outside = self.base.contents.iter().any(|(from, _)| match from {
+			RuleCellFrom::BoundaryVoid => true,
+			RuleCellFrom::One(cell) => cell.is_boundary(),
+			RuleCellFrom::Group(g) => groups.get(*g).is_some_and(|g| g.contains(None)),
+			RuleCellFrom::Any => false,
+		});
+		reads_outside.then(|| self.base.width.max(self.base.height) - 1)
+	}
+
+	/// Fraction of origin positions, including the overhang past the world edges,
+	/// where any variant matches right now. Scans the whole world instead of using the match cache.
+	/// Get the view with `Dish::world_view`.
+	pub fn coverage_fraction(&self, world: &WorldView, groups: &[CellGroup]) -> f64 {
+		let world = world.world;
+		let border_x = self.max_width() as isize - 1;
+		let border_y = self.max_height() as isize - 1;
+		let xs = -border_x..(world.width as isize + border_x);
+		let ys = -border_y..(world.height as isize + border_y);
+		let total = xs.len() * ys.len();
+		if total == 0 {
+			return 0.;
+		}
+		let mut matching = 0;
+		for x in xs {
+			for y in ys.clone() {
+				if !self.allows_position(x, y) {
+					continue;
+				}
+				let hit = self.variants.iter().any(|variant| {
+					let (corner_x, corner_y, _, _) = variant.footprint(x, y);
+					world.subrule_matches(corner_x, corner_y, variant, groups)
+				});
+				if hit {
+					matching += 1;
+				}
+			}
+		}
+		matching as f64 / total as f64
+	}
+
+	/// rough number of times this rule would be applied per step, `coverage_fraction` scaled by the world area
+	pub fn expected_applications_per_step(&self, world: &WorldView, groups: &[CellGroup]) -> f64 {
+		self.coverage_fraction(world, groups) * (world.width() * world.height()) as f64
+	}
+
+	pub fn has_out_of_bounds_copy(&self) -> bool {
+		self.base.contents.iter().any(|(_, to)| {
+			matches!(to, RuleCellTo::Copy(x, y) if !self.base.is_in_bounds(*x as isize, *y as isize))
+		})
+	}
+
+	pub fn uses_group(&self, group: usize) -> bool {
+		self.base.contents.iter().any(|(from, to)| {
+			*from == RuleCellFrom::Group(group)
+				|| matches!(to, RuleCellTo::GroupRandom(g) | RuleCellTo::GroupRandomOther(g) if *g == group)
+		})
+	}
+
+	/// Apply a random match of any variant directly to row-major cell buffers, without a dish or cache.
+	/// Ignores `enabled` and `failrate`. Returns false if nothing matched.
+	pub fn apply_to_buffer(
+		&self,
+		from_buf: &[Cell],
+		to_buf: &mut [Cell],
+		width: usize,
+		height: usize,
+		groups: &[CellGroup],
+		rng: &mut impl Rng,
+	) -> bool {
+		let mut matches = Vec::new();
+		for (variant_index, variant) in self.variants.iter().enumerate() {
+			for (x, y) in variant.find_matches_in_buffer(from_buf, width, height, groups) {
+				let origin_x = x.wrapping_add_unsigned(variant.origin_x);
+				let origin_y = y.wrapping_add_unsigned(variant.origin_y);
+				if self.allows_position(origin_x, origin_y) {
+					matches.push((variant_index, x, y));
+				}
+			}
+		}
+		if matches.is_empty() {
+			return false;
+		}
+		let (variant_index, x, y) = matches[rng.gen::<usize>() % matches.len()];
+		self.variants[variant_index]
+			.apply_at_position(from_buf, to_buf, x, y, width, height, groups, rng);
+		true
+	}
+
+	/// whether the region mask allows a match with its origin at this position
+	pub(crate) fn allows_position(&self, x: isize, y: isize) -> bool {
+		self.region_mask.is_none_or(|mask| mask.contains(x, y))
+	}
+
+	/// true if applying the rule never changes any cell
+	pub fn is_identity(&self) -> bool {
+		self.base.is_identity()
+	}
+
+	pub fn prune_trivial_variants(&mut self) {
+		self.variants.retain(|v| !v.is_trivial());
+		self.variant_priority = (0..self.variants.len()).collect();
+	}
+
+	pub fn variant_priority(&self) -> &[usize] {
+		&self.variant_priority
+	}
+
+	/// `order` lists every variant index once, highest priority first
+	pub fn set_variant_priority(&mut self, order: Vec<usize>) -> Result<(), InvalidPermutation> {
+		let mut seen = vec![false; self.variants.len()];
+		if order.len() != seen.len() {
+			return Err(InvalidPermutation);
+		}
+		for &i in &order {
+			if i >= seen.len() || seen[i] {
+				return Err(InvalidPermutation);
+			}
+			seen[i] = true;
+		}
+		self.variant_priority = order;
+		Ok(())
+	}
+
+	/// selection weight of a variant: the last in the priority list gets 1, the one before it 2 and so on.
+	/// all variants weigh 1 while the priority is the identity order
+	pub fn variant_weight(&self, variant_index: usize) -> usize {
+		if self
+			.variant_priority
+			.iter()
+			.enumerate()
+			.all(|(i, &v)| i == v)
+		{
+			return 1;
+		}
+		self.variant_priority
+			.iter()
+			.position(|&v| v == variant_index)
+			.map_or(1, |pos| self.variant_priority.len() - pos)
+	}
+
+	/// hash of everything that affects which matches get cached for this rule
+	pub(crate) fn variant_hash(&self) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		self.enabled.hash(&mut hasher);
+		self.variants.hash(&mut hasher);
+		self.region_mask.hash(&mut hasher);
+		self.schedule.hash(&mut hasher);
+		hasher.finish()
+	}
+
+	pub fn dbg_variants(&self) {
+		dbg!(&self.variants);
+	}
+
+	pub fn generate_variants(&mut self) {
+		#[cfg(test)]
+		VARIANT_GENERATIONS.with(|n| n.set(n.get() + 1));
+		self.variants.clear();
+		self.variants.push(self.base.clone());
+
+		fn transform_variants(variants: &mut Vec<SubRule>, f: fn(&SubRule) -> SubRule) {
+			let mut new = Vec::new();
+			for v in variants.iter() {
+				let new_variant = f(v);
+				if !variants.contains(&new_variant) {
+					new.push(new_variant);
+				}
+			}
+			variants.extend_from_slice(&new);
+		}
+
+		if self.flip_x {
+			transform_variants(&mut self.variants, SubRule::flipped_x);
+		}
+		if self.flip_y {
+			transform_variants(&mut self.variants, SubRule::flipped_y);
+		}
+		if self.rotate {
+			transform_variants(&mut self.variants, SubRule::rotated_180);
+			transform_variants(&mut self.variants, SubRule::rotated_90);
+		}
+		self.variant_priority = (0..self.variants.len()).collect();
+	}
+
+	/// A copy of the rule where every cell of the base pattern becomes a `factor`×`factor` block.
+	/// Copy outputs read the same part of the scaled source block. Panics if `factor` is 0.
+	pub fn scale(&self, factor: usize) -> Rule {
+		let mut scaled = self.clone();
+		self.scale_into(factor, &mut scaled);
+		scaled
+	}
+
+	/// replace the base pattern of `target_rule` with this one scaled up like `scale`, then regenerate its variants
+	pub fn scale_into(&self, factor: usize, target_rule: &mut Rule) {
+		target_rule.base = self.base.scaled(factor);
+		target_rule.generate_variants();
+	}
+
+	/// permanently transform the base pattern, then regenerate variants like `paste_base`
+	pub fn transform_base(&mut self, t: Transform) {
+		let transformed = match t {
+			Transform::FlipX => self.base.flipped_x(),
+			Transform::FlipY => self.base.flipped_y(),
+			Transform::Rotate90 => self.base.rotated_90(),
+			Transform::Rotate180 => self.base.rotated_180(),
+			Transform::Rotate270 => self.base.rotated_180().rotated_90(),
+		};
+		self.paste_base(transformed);
+	}
+}
+
+/// the frame characters of `SubRule::table`
+struct TableStyle {
+	horizontal: &'static str,
+	vertical: &'static str,
+	/// between the from and to sides
+	divider: &'static str,
+	/// left end, column crossing, divider crossing and right end of each border line
+	top: [&'static str; 4],
+	middle: [&'static str; 4],
+	bottom: [&'static str; 4],
+	color: bool,
+}
+
+const UNICODE_TABLE: TableStyle = TableStyle {
+	horizontal: "─",
+	vertical: "│",
+	divider: "║",
+	top: ["┌", "┬", "╥", "┐"],
+	middle: ["├", "┼", "╫", "┤"],
+	bottom: ["└", "┴", "╨", "┘"],
+	color: true,
+};
+
+const ASCII_TABLE: TableStyle = TableStyle {
+	horizontal: "-",
+	vertical: "|",
+	divider: "||",
+	top: ["+", "+", "++", "+"],
+	middle: ["+", "+", "++", "+"],
+	bottom: ["+", "+", "++", "+"],
+	color: false,
+};
+
+/// text on the cell color, with black or white letters depending on its brightness
+#[cfg(feature = "ansi")]
+fn colored(text: String, color: Option<[u8; 3]>) -> String {
+	match color {
+		Some([r, g, b]) => {
+			let brightness = r as u32 * 299 + g as u32 * 587 + b as u32 * 114;
+			let fg = if brightness > 128_000 { 30 } else { 97 };
+			format!("\x1b[48;2;{r};{g};{b}m\x1b[{fg}m{text}\x1b[0m")
+		}
+		None => text,
+	}
+}
+
+#[cfg(not(feature = "ansi"))]
+fn colored(text: String, _color: Option<[u8; 3]>) -> String {
+	text
+}
+
+/// cell of a row-major buffer, None outside of it
+fn buffer_get(buf: &[Cell], width: usize, height: usize, x: isize, y: isize) -> Option<Cell> {
+	(x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height)
+		.then(|| buf[x as usize + y as usize * width])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		dsl::{parse_pattern, parse_rule},
+		Dish,
+	};
+
+	/// the variants of a rule as sorted text, ignoring their order and origins
+	fn variant_set(rule: &Rule) -> Vec<String> {
+		let mut set: Vec<String> = rule
+			.variants()
+			.iter()
+			.map(|v| v.as_text_grid(TEXT_GRID_CHARS, TEXT_GRID_CHARS).unwrap())
+			.collect();
+		set.sort();
+		set
+	}
+
+	#[test]
+	fn trivial_and_identity_patterns() {
+		let trivial = parse_pattern("* -> _\n* -> _").unwrap();
+		assert!(trivial.is_trivial());
+		assert!(trivial.is_identity());
+
+		let copies_itself = parse_pattern("1 -> c(0,0)\n* -> _").unwrap();
+		assert!(!copies_itself.is_trivial());
+		assert!(copies_itself.is_identity());
+
+		let fall = parse_pattern("1 -> 0\n0 -> 1").unwrap();
+		assert!(!fall.is_trivial());
+		assert!(!fall.is_identity());
+
+		let copies_other = parse_pattern("1 -> c(0,1)\n0 -> _").unwrap();
+		assert!(!copies_other.is_identity());
+	}
+
+	#[test]
+	fn default_rules_are_not_trivial() {
+		let dish = Dish::new();
+		for rule in &dish.rules {
+			assert!(!rule.base().is_trivial(), "{}", rule.name);
+			assert!(!rule.has_trivial_variant(), "{}", rule.name);
+		}
+		assert!(dish.validate().is_empty());
+	}
+
+	#[test]
+	fn out_of_bounds_copy() {
+		let rule = parse_rule("1 0 -> c(5,0) _").unwrap();
+		assert_eq!((rule.width(), rule.height()), (2, 1));
+		assert!(rule.has_out_of_bounds_copy());
+		assert!(rule.base().is_in_bounds(1, 0));
+		assert!(!rule.base().is_in_bounds(5, 0));
+		assert!(!rule.base().is_in_bounds(-1, 0));
+		assert!(!parse_rule("1 0 -> c(1,0) _")
+			.unwrap()
+			.has_out_of_bounds_copy());
+
+		let mut dish = Dish::new();
+		dish.rules.push(rule);
+		let rule_index = dish.rules.len() - 1;
+		dish.update_all_rules();
+		assert!(dish
+			.validate()
+			.contains(&crate::ValidationWarning::OutOfBoundsCopy { rule_index }));
+		assert!(dish.is_in_bounds(0, 0));
+		assert!(!dish.is_in_bounds(-1, 0));
+		assert!(!dish.is_in_bounds(0, dish.world_height() as isize));
+	}
+
+	#[test]
+	fn transform_base_keeps_symmetric_variants() {
+		let mut rule = parse_rule("1 0 -> 0 1\n0 0 -> _ 1").unwrap();
+		rule.flip_x = true;
+		rule.generate_variants();
+		let before = variant_set(&rule);
+		rule.transform_base(Transform::FlipX);
+		assert_eq!(variant_set(&rule), before);
+		assert_eq!(rule.base().origin(), (0, 0));
+
+		rule.flip_x = false;
+		rule.rotate = true;
+		rule.generate_variants();
+		let before = variant_set(&rule);
+		for t in [
+			Transform::Rotate90,
+			Transform::Rotate180,
+			Transform::Rotate270,
+		] {
+			rule.transform_base(t);
+			assert_eq!(variant_set(&rule), before);
+			assert_eq!(rule.base().origin(), (0, 0));
+		}
+	}
+
+	#[test]
+	fn set_cells_generates_variants_once() {
+		let blank = || {
+			let mut rule = parse_rule("* * -> _ _\n* * -> _ _").unwrap();
+			rule.rotate = true;
+			rule
+		};
+		let mut rule = blank();
+		let cells = [
+			(0, 0, RuleCellFrom::One(Cell(1)), RuleCellTo::One(Cell(0))),
+			(1, 0, RuleCellFrom::Any, RuleCellTo::None),
+			(0, 1, RuleCellFrom::One(Cell(0)), RuleCellTo::One(Cell(1))),
+			(1, 1, RuleCellFrom::Any, RuleCellTo::None),
+		];
+		let before = VARIANT_GENERATIONS.with(|n| n.get());
+		rule.set_cells(&cells);
+		assert_eq!(VARIANT_GENERATIONS.with(|n| n.get()), before + 1);
+
+		let mut one_by_one = blank();
+		for (x, y, from, to) in cells {
+			one_by_one.set_cell(x, y, from, to);
+		}
+		assert_eq!(variant_set(&rule), variant_set(&one_by_one));
+	}
+
+	#[test]
+	fn fall_rule_as_text_grid() {
+		let dish = Dish::new();
+		let fall = &dish.rules[0];
+		assert_eq!(fall.name, "fall");
+		let base = fall.base();
+		assert_eq!(
+			base.as_text_grid(TEXT_GRID_CHARS, TEXT_GRID_CHARS).unwrap(),
+			"1 -> 0\n0 -> 1"
+		);
+		assert_eq!(base.as_text_grid(".s", ".s").unwrap(), "s -> .\n. -> s");
+		assert_eq!(
+			base.as_text_grid(".", ".s"),
+			Err(ExportError::NotEnoughChars { needed: 2, got: 1 })
+		);
+		assert_eq!(
+			&parse_pattern(&base.as_text_grid(TEXT_GRID_CHARS, TEXT_GRID_CHARS).unwrap()).unwrap(),
+			base
+		);
+		assert_eq!(
+			fall.as_text_grid(TEXT_GRID_CHARS, TEXT_GRID_CHARS),
+			"1 -> 0\n0 -> 1\n\nvariant 0:\n1 -> 0\n0 -> 1"
+		);
+	}
+
+	#[test]
+	fn disable_reason_lifecycle() {
+		let mut rule = parse_rule("1 -> 0\n0 -> 1").unwrap();
+		assert_eq!(rule.disable_reason(), None);
+
+		rule.set_enabled_with_reason(false, "reached its limit");
+		assert!(!rule.enabled);
+		assert_eq!(rule.disable_reason(), Some("reached its limit"));
+
+		let json = serde_json::to_string(&rule).unwrap();
+		let mut loaded: Rule = serde_json::from_str(&json).unwrap();
+		assert_eq!(loaded.disable_reason(), Some("reached its limit"));
+
+		// enabling by hand hides the stale reason, enabling through the setter clears it
+		loaded.enabled = true;
+		assert_eq!(loaded.disable_reason(), None);
+		rule.set_enabled_with_reason(true, "ignored");
+		assert!(rule.enabled);
+		assert_eq!(rule.enabled_reason, None);
+
+		// rules saved before the field existed load without a reason
+		let mut value = serde_json::to_value(&rule).unwrap();
+		value.as_object_mut().unwrap().remove("enabled_reason");
+		let old: Rule = serde_json::from_value(value).unwrap();
+		assert_eq!(old.enabled_reason, None);
+	}
+
+	#[test]
+	fn auto_names_describe_the_pattern() {
+		let dish = Dish::new();
+		let name = |pattern: &str| parse_rule(pattern).unwrap().auto_name(&dish.types);
+		assert_eq!(name("1 -> 0\n0 -> 1"), "pink_sand fall");
+		assert_eq!(name("0 -> 1\n1 -> 0"), "pink_sand rise");
+		assert_eq!(name("1 0 -> 0 1"), "pink_sand slide right");
+		assert_eq!(name("0 1 -> 1 0"), "pink_sand slide left");
+		assert_eq!(name("1 0 1 -> 0 1 0"), "rule_3x1 (sym)");
+		assert_eq!(name("1 -> 0\n* -> _"), "rule_1x2");
+
+		let mut dish = Dish::new();
+		let mut fall = parse_rule("1 -> 0\n0 -> 1").unwrap();
+		fall.name = "new rule".into();
+		let mut kept = fall.clone();
+		kept.name = "gravity".into();
+		dish.rules = vec![fall, kept];
+		dish.auto_name_rules();
+		assert_eq!(dish.rules[0].name, "pink_sand fall");
+		assert!(!dish.rules[0].has_been_renamed(&dish.types));
+		assert_eq!(dish.rules[1].name, "gravity");
+		assert!(dish.rules[1].has_been_renamed(&dish.types));
+	}
+
+	#[test]
+	fn fall_rule_grid_display() {
+		let fall = parse_rule("1 -> 0\n0 -> 1").unwrap();
+		// (from, to) of each row, split at the divider
+		let sides = |text: &str, divider: &str, wall: char| -> Vec<(String, String)> {
+			text.lines()
+				.filter(|line| line.starts_with(wall))
+				.map(|line| {
+					let (from, to) = line.split_once(divider).unwrap();
+					let clean = |s: &str| s.replace(wall, "").trim().to_owned();
+					(clean(from), clean(to))
+				})
+				.collect()
+		};
+		let rows = [("1".into(), "0".into()), ("0".into(), "1".into())];
+		assert_eq!(sides(&fall.grid_display(&[]), "║", '│'), rows);
+		assert_eq!(sides(&fall.ascii_display(&[]), "||", '|'), rows);
+
+		let types = Dish::new().types;
+		let text = fall.grid_display(&types);
+		assert_eq!(
+			sides(&text, "║", '│'),
+			[("p".into(), "a".into()), ("a".into(), "p".into())]
+		);
+		assert_eq!(text.lines().count(), 5);
+		assert!(text.starts_with('┌') && text.ends_with('┘'), "{text}");
+	}
+
+	#[test]
+	fn fall_from_pattern_strings() {
+		let fall = Rule::from_pattern_string("1\n0", "0\n1").unwrap();
+		let mut manual = Rule::new();
+		manual.enabled = true;
+		manual.paste_base(SubRule {
+			width: 1,
+			height: 2,
+			origin_x: 0,
+			origin_y: 0,
+			contents: vec![
+				(RuleCellFrom::One(Cell(1)), RuleCellTo::One(Cell(0))),
+				(RuleCellFrom::One(Cell(0)), RuleCellTo::One(Cell(1))),
+			],
+		});
+		assert_eq!(fall.base(), manual.base());
+		assert_eq!(fall.variants(), manual.variants());
+		assert_eq!(
+			serde_json::to_value(&fall).unwrap(),
+			serde_json::to_value(&manual).unwrap()
+		);
+		assert_eq!(fall.base(), parse_rule("1 -> 0\n0 -> 1").unwrap().base());
+
+		let slide =
+			Rule::from_pattern_string_with_flags("1 *\n1 0", "0 *\n_ 1", true, false, false)
+				.unwrap();
+		assert!(slide.flip_x && !slide.flip_y && !slide.rotate);
+		assert_eq!(slide.variants().len(), 2);
+		assert_eq!(
+			slide.base().get(1, 0),
+			(RuleCellFrom::Any, RuleCellTo::None)
+		);
+		assert_eq!(
+			slide.base().get(0, 1),
+			(RuleCellFrom::One(Cell(1)), RuleCellTo::None)
+		);
+
+		assert!(matches!(
+			Rule::from_pattern_string("1 x", "0 0"),
+			Err(ParseError::InvalidToken(token)) if token == "x"
+		));
+		assert_eq!(
+			Rule::from_pattern_string("1 0\n1", "0 1\n0").unwrap_err(),
+			ParseError::JaggedRows
+		);
+		assert_eq!(
+			Rule::from_pattern_string("1\n0", "0 1\n1").unwrap_err(),
+			ParseError::JaggedRows
+		);
+		assert_eq!(
+			Rule::from_pattern_string("", "\n").unwrap_err(),
+			ParseError::Empty
+		);
+	}
+
+	#[test]
+	fn match_at_corner_undoes_footprint() {
+		let mut rule = crate::dsl::parse_rule("1 0 0 -> 0 0 1\n* * 1 -> _ _ 0").unwrap();
+		rule.rotate = true;
+		rule.flip_x = true;
+		rule.generate_variants();
+		for variant in rule.variants() {
+			for at in [(0, 0), (-2, 5), (7, -1)] {
+				let (corner_x, corner_y, _, _) = variant.footprint(at.0, at.1);
+				assert_eq!(variant.match_at_corner(corner_x, corner_y), at);
+			}
+		}
+	}
+}
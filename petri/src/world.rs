@@ -0,0 +1,341 @@
+use std::{
+	collections::hash_map::DefaultHasher,
+	hash::{Hash, Hasher},
+};
+
+use rand::prelude::*;
+
+use crate::{Cell, CellGroup, SubRule};
+
+pub const CHUNK_SIZE: usize = 32;
+
+#[derive(Debug)]
+struct Chunk {
+	pub contents: Box<[[Cell; CHUNK_SIZE]; CHUNK_SIZE]>,
+	/// a cell changed since the last `World::take_dirty_chunks`, new chunks start dirty
+	dirty: bool,
+	/// world revision of the last change to a cell in this chunk
+	revision: u64,
+	summary: ChunkSummary,
+}
+
+/// How many cells of each type a chunk holds, kept up to date on every write.
+/// Parts of edge chunks that hang over the world edge count as empty (id 0).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkSummary {
+	/// indexed by cell id
+	counts: Vec<u32>,
+}
+
+impl Default for ChunkSummary {
+	fn default() -> Self {
+		Self {
+			counts: vec![(CHUNK_SIZE * CHUNK_SIZE) as u32],
+		}
+	}
+}
+
+impl ChunkSummary {
+	pub fn count(&self, cell: Cell) -> u32 {
+		self.counts.get(cell.id()).copied().unwrap_or(0)
+	}
+
+	pub fn contains(&self, cell: Cell) -> bool {
+		self.count(cell) > 0
+	}
+
+	/// cells that are not id 0
+	pub fn non_empty(&self) -> u32 {
+		(CHUNK_SIZE * CHUNK_SIZE) as u32 - self.count(Cell(0))
+	}
+
+	/// every cell type in the chunk with its count
+	pub fn present(&self) -> impl Iterator<Item = (Cell, u32)> + '_ {
+		self.counts
+			.iter()
+			.enumerate()
+			.filter(|&(_, &n)| n > 0)
+			.map(|(id, &n)| (Cell(id as u16), n))
+	}
+
+	fn replace(&mut self, old: Cell, new: Cell) {
+		self.counts[old.id()] -= 1;
+		if self.counts.len() <= new.id() {
+			self.counts.resize(new.id() + 1, 0);
+		}
+		self.counts[new.id()] += 1;
+	}
+}
+
+#[derive(Debug)]
+pub(crate) struct World {
+	pub(crate) width: usize,
+	pub(crate) height: usize,
+	/// row-major grid of chunks covering the world, rounded up to whole chunks
+	chunks: Vec<Chunk>,
+	/// bumped whenever a cell changes, see `Dish::revisions`
+	pub(crate) revision: u64,
+}
+
+/// which part of the world stays in place when resizing
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Anchor {
+	#[default]
+	TopLeft,
+	Top,
+	TopRight,
+	Left,
+	Center,
+	Right,
+	BottomLeft,
+	Bottom,
+	BottomRight,
+}
+
+impl Anchor {
+	/// where the top-left corner of the old contents ends up in the resized world
+	pub fn offset(
+		&self,
+		(old_w, old_h): (usize, usize),
+		(new_w, new_h): (usize, usize),
+	) -> (isize, isize) {
+		let (fx, fy) = match self {
+			Anchor::TopLeft => (0, 0),
+			Anchor::Top => (1, 0),
+			Anchor::TopRight => (2, 0),
+			Anchor::Left => (0, 1),
+			Anchor::Center => (1, 1),
+			Anchor::Right => (2, 1),
+			Anchor::BottomLeft => (0, 2),
+			Anchor::Bottom => (1, 2),
+			Anchor::BottomRight => (2, 2),
+		};
+		let dx = (new_w as isize - old_w as isize) * fx / 2;
+		let dy = (new_h as isize - old_h as isize) * fy / 2;
+		(dx, dy)
+	}
+}
+
+impl Default for Chunk {
+	fn default() -> Self {
+		Self {
+			contents: vec![[Cell(0); CHUNK_SIZE]; CHUNK_SIZE]
+				.into_boxed_slice()
+				.try_into()
+				.unwrap(),
+			dirty: true,
+			revision: 0,
+			summary: ChunkSummary::default(),
+		}
+	}
+}
+
+impl Chunk {
+	fn get_cell(&self, x: usize, y: usize) -> Cell {
+		self.contents[x][y]
+	}
+
+	/// returns whether the cell changed
+	fn set_cell(&mut self, x: usize, y: usize, cell: Cell) -> bool {
+		let old = self.contents[x][y];
+		if old == cell {
+			return false;
+		}
+		self.contents[x][y] = cell;
+		self.dirty = true;
+		self.summary.replace(old, cell);
+		true
+	}
+}
+
+impl Default for World {
+	fn default() -> Self {
+		Self::new(CHUNK_SIZE, CHUNK_SIZE)
+	}
+}
+
+impl World {
+	pub(crate) fn new(width: usize, height: usize) -> Self {
+		let width = width.max(1);
+		let height = height.max(1);
+		let chunk_count = width.div_ceil(CHUNK_SIZE) * height.div_ceil(CHUNK_SIZE);
+		Self {
+			width,
+			height,
+			chunks: (0..chunk_count).map(|_| Chunk::default()).collect(),
+			revision: 0,
+		}
+	}
+
+	/// replace this world with another, with a revision above the current one that every chunk changed at
+	pub(crate) fn replace(&mut self, mut new: World) {
+		new.revision = self.revision + 1;
+		for chunk in &mut new.chunks {
+			chunk.revision = new.revision;
+		}
+		*self = new;
+	}
+
+	pub(crate) fn with_random_ones(mut self) -> Self {
+		for y in 0..self.height {
+			for x in 0..self.width {
+				if random::<u8>().is_multiple_of(4) {
+					self.set_cell(x as isize, y as isize, Cell(1));
+				}
+			}
+		}
+		self
+	}
+
+	/// returns the rect covering every cell that changed, as (x, y, width, height)
+	pub(crate) fn fill(&mut self, cell: Cell) -> Option<(isize, isize, usize, usize)> {
+		let mut changed: Option<(usize, usize, usize, usize)> = None;
+		for y in 0..self.height {
+			for x in 0..self.width {
+				let (chunk, cx, cy) = self.locate(x, y);
+				if self.chunks[chunk].get_cell(cx, cy) == cell {
+					continue;
+				}
+				self.chunks[chunk].set_cell(cx, cy, cell);
+				self.revision += 1;
+				self.chunks[chunk].revision = self.revision;
+				changed = Some(match changed {
+					None => (x, y, x, y),
+					Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+				});
+			}
+		}
+		changed.map(|(x0, y0, x1, y1)| (x0 as isize, y0 as isize, x1 - x0 + 1, y1 - y0 + 1))
+	}
+
+	pub(crate) fn is_in_bounds(&self, x: isize, y: isize) -> bool {
+		x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+	}
+
+	/// whether any in-bounds cell of the rect is set in a row-major mask over the world,
+	/// like `Dish::frozen`. an empty mask has nothing set
+	pub(crate) fn rect_in_mask(
+		&self,
+		mask: &[bool],
+		x: isize,
+		y: isize,
+		width: usize,
+		height: usize,
+	) -> bool {
+		if mask.is_empty() {
+			return false;
+		}
+		(x..x.saturating_add_unsigned(width))
+			.flat_map(|x| (y..y.saturating_add_unsigned(height)).map(move |y| (x, y)))
+			.any(|(x, y)| self.is_in_bounds(x, y) && mask[x as usize + y as usize * self.width])
+	}
+
+	/// set the in-bounds cells of the rect in a world-sized mask
+	pub(crate) fn mark_rect(
+		&self,
+		mask: &mut [bool],
+		x: isize,
+		y: isize,
+		width: usize,
+		height: usize,
+	) {
+		for px in x..x.saturating_add_unsigned(width) {
+			for py in y..y.saturating_add_unsigned(height) {
+				if self.is_in_bounds(px, py) {
+					mask[px as usize + py as usize * self.width] = true;
+				}
+			}
+		}
+	}
+
+	/// (x, y) in chunks of every chunk with changed cells since the last call, clearing them
+	pub(crate) fn take_dirty_chunks(&mut self) -> Vec<(usize, usize)> {
+		let chunks_wide = self.width.div_ceil(CHUNK_SIZE);
+		self.chunks
+			.iter_mut()
+			.enumerate()
+			.filter_map(|(i, chunk)| {
+				std::mem::take(&mut chunk.dirty).then_some((i % chunks_wide, i / chunks_wide))
+			})
+			.collect()
+	}
+
+	/// (x, y) in chunks of every chunk with cells changed after `revision`
+	pub(crate) fn chunks_changed_since(&self, revision: u64) -> Vec<(usize, usize)> {
+		let chunks_wide = self.width.div_ceil(CHUNK_SIZE);
+		self.chunks
+			.iter()
+			.enumerate()
+			.filter(|(_, chunk)| chunk.revision > revision)
+			.map(|(i, _)| (i % chunks_wide, i / chunks_wide))
+			.collect()
+	}
+
+	pub(crate) fn chunk_count(&self) -> usize {
+		self.chunks.len()
+	}
+
+	/// hash of the size and every cell, in one pass over the chunks
+	pub(crate) fn content_hash(&self) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		(self.width, self.height).hash(&mut hasher);
+		// the parts of chunks outside the world are always empty, so they don't disturb the hash
+		for chunk in &self.chunks {
+			for column in chunk.contents.iter() {
+				for cell in column {
+					hasher.write_u16(cell.0);
+				}
+			}
+		}
+		hasher.finish()
+	}
+
+	/// summaries of every chunk, row-major
+	pub(crate) fn chunk_summaries(&self) -> impl Iterator<Item = &ChunkSummary> {
+		self.chunks.iter().map(|c| &c.summary)
+	}
+
+	/// summary of the chunk holding a position inside the world
+	pub(crate) fn summary_at(&self, x: usize, y: usize) -> &ChunkSummary {
+		&self.chunks[self.locate(x, y).0].summary
+	}
+
+	/// chunk index and position within that chunk, for a position inside the world
+	fn locate(&self, x: usize, y: usize) -> (usize, usize, usize) {
+		let chunks_wide = self.width.div_ceil(CHUNK_SIZE);
+		let index = x / CHUNK_SIZE + (y / CHUNK_SIZE) * chunks_wide;
+		(index, x % CHUNK_SIZE, y % CHUNK_SIZE)
+	}
+
+	pub(crate) fn get_cell(&self, x: isize, y: isize) -> Option<Cell> {
+		if self.is_in_bounds(x, y) {
+			let (chunk, x, y) = self.locate(x as usize, y as usize);
+			Some(self.chunks[chunk].get_cell(x, y))
+		} else {
+			None
+		}
+	}
+
+	/// returns whether the cell changed
+	pub(crate) fn set_cell(&mut self, x: isize, y: isize, cell: Cell) -> bool {
+		if self.is_in_bounds(x, y) {
+			let (chunk, x, y) = self.locate(x as usize, y as usize);
+			if self.chunks[chunk].set_cell(x, y, cell) {
+				self.revision += 1;
+				self.chunks[chunk].revision = self.revision;
+				return true;
+			}
+		}
+		false
+	}
+
+	pub(crate) fn subrule_matches(
+		&self,
+		corner_x: isize,
+		corner_y: isize,
+		subrule: &SubRule,
+		groups: &[CellGroup],
+	) -> bool {
+		subrule.matches_at(corner_x, corner_y, groups, |x, y| self.get_cell(x, y))
+	}
+}
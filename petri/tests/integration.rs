@@ -0,0 +1,169 @@
+//! End-to-end runs of the example rulesets and of the default dish.
+//!
+//! There is no `petri::presets` module: the built-in presets are the files in
+//! `example_rulesets`, which the GUI opens like any other saved dish.
+//! Saved dishes don't include the world, so each one starts from random cells of all its types.
+
+use std::path::Path;
+
+use petri::{Cell, Dish, ValidationWarning};
+use rand::{rngs::StdRng, SeedableRng};
+
+const MAX_STEPS: usize = 100_000;
+const SEED: u64 = 42;
+
+struct Expected {
+	/// whether the ruleset settles from random cells within `MAX_STEPS`,
+	/// the others keep changing forever, so `MAX_STEPS` is the known limit for them
+	settles: bool,
+	/// (cell id, min, max) of the final cell counts
+	counts: &'static [(u16, usize, usize)],
+	/// what `Dish::validate` reports about the rules
+	warnings: &'static [ValidationWarning],
+}
+
+fn check_preset(name: &str, expected: Expected) {
+	let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("../example_rulesets")
+		.join(format!("{name}.json"));
+	let mut dish = petri::io::load(&path).unwrap();
+	assert_eq!(dish.validate(), expected.warnings, "{name}");
+	let cells: Vec<(Cell, f32)> = (0..dish.types.len())
+		.map(|id| (Cell(id as u16), 1.))
+		.collect();
+	dish.randomize_world(&cells, &mut StdRng::seed_from_u64(SEED));
+
+	let steps = dish.run_until_stable(MAX_STEPS, SEED);
+	assert_eq!(steps.is_some(), expected.settles, "{name}: {steps:?}");
+	assert_eq!(dish.is_quiescent(), expected.settles, "{name}");
+	assert_eq!(dish.verify_cache_integrity(), vec![], "{name}");
+
+	let counts: Vec<usize> = (0..dish.types.len())
+		.map(|id| dish.count_cells(Cell(id as u16)))
+		.collect();
+	let area = dish.world_width() * dish.world_height();
+	assert_eq!(
+		counts.iter().sum::<usize>(),
+		area,
+		"{name}: undefined cells"
+	);
+	for &(id, min, max) in expected.counts {
+		let count = counts[id as usize];
+		assert!(
+			(min..=max).contains(&count),
+			"{name}: {count} of cell {id}, expected {min}..={max}"
+		);
+	}
+}
+
+#[test]
+fn ants() {
+	check_preset(
+		"ants",
+		Expected {
+			settles: false,
+			counts: &[],
+			warnings: &[],
+		},
+	);
+}
+
+#[test]
+fn corner_finder() {
+	check_preset(
+		"corner_finder",
+		Expected {
+			settles: true,
+			// seeds turn into probes, and probes leave no tail behind once they stop
+			counts: &[(1, 0, 0), (3, 0, 0)],
+			warnings: &[],
+		},
+	);
+}
+
+#[test]
+fn fire() {
+	check_preset(
+		"fire",
+		Expected {
+			settles: false,
+			counts: &[],
+			// the rule clearing cell 5 is there twice, which makes it fire twice as often
+			warnings: &[ValidationWarning::DuplicateRule {
+				rule_index: 7,
+				original: 6,
+			}],
+		},
+	);
+}
+
+#[test]
+fn maze() {
+	check_preset(
+		"maze",
+		Expected {
+			settles: true,
+			counts: &[],
+			warnings: &[],
+		},
+	);
+}
+
+#[test]
+fn rule_110() {
+	check_preset(
+		"rule_110",
+		Expected {
+			settles: true,
+			// copies are always consumed by the next step of the row
+			counts: &[(2, 0, 0)],
+			warnings: &[],
+		},
+	);
+}
+
+#[test]
+fn sand_stress_test() {
+	check_preset(
+		"sand_stress_test",
+		Expected {
+			settles: false,
+			counts: &[],
+			warnings: &[],
+		},
+	);
+}
+
+#[test]
+fn sparks() {
+	check_preset(
+		"sparks",
+		Expected {
+			settles: false,
+			counts: &[],
+			warnings: &[],
+		},
+	);
+}
+
+#[test]
+fn wiper() {
+	check_preset(
+		"wiper",
+		Expected {
+			settles: true,
+			// the wipers sweep the world into right-moving cells
+			counts: &[(0, 0, 50), (1, 900, 1024)],
+			warnings: &[],
+		},
+	);
+}
+
+#[test]
+fn default_dish_runs_with_any_seed() {
+	let worlds = (0..100).map(|_| Dish::new()).collect();
+	// world i is seeded with 0 + i
+	for dish in Dish::run_parallel_worlds(worlds, 10_000, 0) {
+		assert_eq!(dish.verify_cache_integrity(), vec![]);
+	}
+}
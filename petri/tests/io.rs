@@ -0,0 +1,271 @@
+//! Saving and loading dishes through `petri::io` in every file format.
+
+use std::{fs, path::PathBuf};
+
+use petri::{
+	dsl::parse_rule,
+	io::{self, FileFormat, IoError, NameError, References},
+	Cell, CellData, CellGroup, Dish, RegionMask, RuleCellFrom,
+};
+
+/// a dish with something non-default in every saved part
+fn sample_dish() -> Dish {
+	let mut dish = Dish::new();
+	let stone = dish.add_type(CellData::new("stone", 90, 90, 100));
+	let group = dish.add_group(CellGroup {
+		name: "solid".into(),
+		cells: vec![Cell(1), stone],
+		..Default::default()
+	});
+	let mut rule = parse_rule(&format!("1 -> 0\ng{group} -> 1")).unwrap();
+	rule.name = "pile".into();
+	rule.description = "sand rests on anything solid".into();
+	rule.enabled = true;
+	rule.flip_x = true;
+	dish.rules.push(rule);
+	dish.temperature = 0.5;
+	dish.update_all_rules();
+	dish
+}
+
+/// a file in the temp dir, unique to this test process
+fn temp_path(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("petri_io_test_{}_{name}", std::process::id()))
+}
+
+fn assert_same_dish(a: &Dish, b: &Dish) {
+	assert_eq!(
+		serde_json::to_value(a).unwrap(),
+		serde_json::to_value(b).unwrap()
+	);
+	assert_eq!(a.rules_hash(), b.rules_hash());
+}
+
+fn round_trip(extension: &str) {
+	let dish = sample_dish();
+	let path = temp_path(&format!("round_trip.{extension}"));
+	io::save(&dish, &path).unwrap();
+	let loaded = io::load(&path);
+	fs::remove_file(&path).unwrap();
+	assert_same_dish(&dish, &loaded.unwrap());
+}
+
+#[test]
+fn json_round_trip() {
+	round_trip("json");
+}
+
+#[cfg(feature = "ron")]
+#[test]
+fn ron_round_trip() {
+	round_trip("ron");
+}
+
+#[test]
+fn binary_round_trip() {
+	round_trip("snad");
+}
+
+#[test]
+fn json_names_round_trip() {
+	let dish = sample_dish();
+	let path = temp_path("names.json");
+	io::save_with_references(&dish, &path, References::Names).unwrap();
+	let loaded = io::load(&path);
+	fs::remove_file(&path).unwrap();
+	assert_same_dish(&dish, &loaded.unwrap());
+}
+
+#[test]
+fn unknown_extensions_are_probed() {
+	let dish = sample_dish();
+	for format in [FileFormat::Json, FileFormat::Binary] {
+		let path = temp_path(&format!("probed_{format:?}.dish"));
+		fs::write(&path, io::to_bytes(&dish, format, References::Ids).unwrap()).unwrap();
+		assert_eq!(io::detect_format(&path), Some(format));
+		let loaded = io::load(&path);
+		fs::remove_file(&path).unwrap();
+		assert_same_dish(&dish, &loaded.unwrap());
+	}
+	let path = temp_path("unknown.dish");
+	assert!(matches!(
+		io::save(&dish, &path),
+		Err(IoError::UnknownExtension(ext)) if ext == "dish"
+	));
+	assert!(!path.exists());
+}
+
+#[test]
+fn names_survive_reordered_ids() {
+	let source = sample_dish();
+	let pile = source.find_rule_by_name("pile").unwrap();
+	let value = io::rule_to_json_value(&source, pile).unwrap();
+	assert_eq!(value["base"]["contents"][1][0]["Group"], "solid");
+
+	// the same cells and groups under other ids
+	let mut target = Dish::new();
+	target.add_type(CellData::new("glass", 200, 220, 255));
+	let stone = target.add_type(CellData::new("stone", 90, 90, 100));
+	target.add_group(CellGroup {
+		name: "liquid".into(),
+		..Default::default()
+	});
+	let solid = target.add_group(CellGroup {
+		name: "solid".into(),
+		cells: vec![Cell(1), stone],
+		..Default::default()
+	});
+	let rule = io::rule_from_json_value(&target, value).unwrap();
+	assert_eq!(rule.get(0, 0).0, RuleCellFrom::One(Cell(1)));
+	assert_eq!(rule.get(0, 1).0, RuleCellFrom::Group(solid));
+	assert_eq!(target.import_rules(vec![rule]), Ok(()));
+}
+
+#[test]
+fn renamed_cell_is_a_resolution_error() {
+	let dish = sample_dish();
+	let mut value = io::to_json_value(&dish, References::Names).unwrap();
+	let stone = value["types"]
+		.as_array_mut()
+		.unwrap()
+		.iter_mut()
+		.find(|t| t["name"] == "stone")
+		.unwrap();
+	stone["name"] = "rock".into();
+	let err = io::from_json_value(value).unwrap_err();
+	assert!(
+		matches!(
+			&err,
+			IoError::Name(NameError::UnknownCell { name, used_in })
+				if name == "stone" && used_in == "group 'solid'"
+		),
+		"{err}"
+	);
+
+	// a name used twice can't be told apart, so those cells are saved as ids
+	let mut dish = sample_dish();
+	dish.types[2].name = dish.types[1].name.clone();
+	let value = io::to_json_value(&dish, References::Names).unwrap();
+	assert_eq!(value["groups"][1]["cells"], serde_json::json!([1, 2]));
+	assert_same_dish(&dish, &io::from_json_value(value).unwrap());
+}
+
+#[test]
+fn received_rule_is_remapped_before_import() {
+	// the path a rule takes between two running instances
+	let sender = sample_dish();
+	let sent = io::rule_to_json_value(&sender, sender.find_rule_by_name("pile").unwrap()).unwrap();
+	let bytes = serde_json::to_vec(&sent).unwrap();
+	let received: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+	let mut missing_group = Dish::new();
+	missing_group.add_type(CellData::new("stone", 90, 90, 100));
+	let err = io::rule_from_json_value(&missing_group, received.clone()).unwrap_err();
+	assert!(
+		matches!(&err, IoError::Name(NameError::UnknownGroup { name, .. }) if name == "solid"),
+		"{err}"
+	);
+
+	let mut receiver = Dish::new();
+	receiver.add_type(CellData::new("glass", 200, 220, 255));
+	let stone = receiver.add_type(CellData::new("stone", 90, 90, 100));
+	receiver.add_group(CellGroup {
+		name: "liquid".into(),
+		..Default::default()
+	});
+	receiver.add_group(CellGroup {
+		name: "solid".into(),
+		cells: vec![Cell(1), stone],
+		..Default::default()
+	});
+	let rule = io::rule_from_json_value(&receiver, received).unwrap();
+	assert_eq!(receiver.import_rules(vec![rule]), Ok(()));
+	let pile = receiver.rules.len() - 1;
+	assert_eq!(receiver.rules[pile].name, "pile");
+
+	// sand resting on the receiver's stone matches the imported rule
+	receiver.fill(Cell(0));
+	receiver.set_cell(4, 6, Cell(1));
+	receiver.set_cell(4, 7, stone);
+	receiver.rebuild_cache();
+	let world = RegionMask {
+		x: 0,
+		y: 0,
+		w: receiver.world_width(),
+		h: receiver.world_height(),
+	};
+	let matches: Vec<_> = receiver
+		.matches_in_region(world)
+		.into_iter()
+		.filter(|m| m.0 == pile)
+		.map(|m| m.2)
+		.collect();
+	assert_eq!(matches, [(4, 6)]);
+}
+
+#[test]
+fn atomic_save_keeps_a_backup() {
+	let dir = temp_path("atomic");
+	fs::create_dir_all(&dir).unwrap();
+	let entries = || {
+		let mut names: Vec<String> = fs::read_dir(&dir)
+			.unwrap()
+			.map(|e| e.unwrap().file_name().into_string().unwrap())
+			.collect();
+		names.sort();
+		names
+	};
+	let target = dir.join("world.json");
+	fs::write(&target, "old").unwrap();
+
+	io::save_atomic(&target, b"new", true).unwrap();
+	assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+	assert_eq!(io::backup_path(&target), dir.join("world.json.bak"));
+	assert_eq!(fs::read_to_string(io::backup_path(&target)).unwrap(), "old");
+	assert_eq!(entries(), ["world.json", "world.json.bak"]);
+
+	// without a backup the old one is left as it was
+	io::save_atomic(&target, b"newer", false).unwrap();
+	assert_eq!(fs::read_to_string(&target).unwrap(), "newer");
+	assert_eq!(fs::read_to_string(io::backup_path(&target)).unwrap(), "old");
+
+	// a failed rename leaves the target alone and cleans up the temporary file
+	let blocked = dir.join("blocked");
+	fs::create_dir(&blocked).unwrap();
+	fs::write(blocked.join("inside"), "kept").unwrap();
+	assert!(io::save_atomic(&blocked, b"lost", false).is_err());
+	assert_eq!(fs::read_to_string(blocked.join("inside")).unwrap(), "kept");
+	assert_eq!(entries(), ["blocked", "world.json", "world.json.bak"]);
+
+	assert!(io::save_atomic(&dir.join("missing/world.json"), b"lost", true).is_err());
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dish_without_types_still_renders() {
+	let mut value = io::to_json_value(&sample_dish(), References::Ids).unwrap();
+	value["types"] = serde_json::json!([]);
+	let path = temp_path("no_types.json");
+	fs::write(&path, value.to_string()).unwrap();
+	let loaded = io::load(&path);
+	fs::remove_file(&path).unwrap();
+	let mut dish = loaded.unwrap();
+	assert_eq!(dish.types_len(), 2);
+	assert_eq!(dish.types[0].name, "air");
+
+	let (width, height, pixels) = dish.render_world_rgb(2, true);
+	assert_eq!(
+		(width, height),
+		(dish.world_width() * 2, dish.world_height() * 2)
+	);
+	assert_eq!(pixels.len(), width * height * 3);
+
+	// without any types every cell is drawn as undefined
+	dish.remove_all_types();
+	assert_eq!(dish.add_default_cell_types().len(), 2);
+	assert_eq!(dish.add_default_cell_types(), []);
+	dish.remove_all_types();
+	dish.step(petri::StepMode::Random, 100);
+	let (_, _, pixels) = dish.render_world_rgb(1, false);
+	assert!(pixels.chunks(3).all(|p| p == [255, 0, 255]));
+}
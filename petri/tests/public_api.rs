@@ -0,0 +1,76 @@
+//! Every name the crate root re-exports, and the public modules.
+//! If this stops compiling, a public item moved or disappeared:
+//! update downstream users and this list together.
+
+use petri::{
+	analyze, benchmark, dsl, dynamic, io, observer, Anchor, ApplyTrace, CacheError, CacheStats,
+	Cell, CellData, CellGroup, CellPattern, CellShape, ChunkSummary, DeltaError, Dish, DishPart,
+	Edge, ExportError, FixpointResult, FrameCounters, ImportError, InvalidPermutation,
+	ParamMismatch, ParamSet, ParseError, RegionMask, RemoveGroupError, Revisions, Rule,
+	RuleActivity, RuleCellFrom, RuleCellTo, RuleIterMut, RuleNameError, RuleParams, Schedule,
+	SelectionStrategy, SimStats, StepMode, SubRule, Transform, ValidationWarning, WorldDelta,
+	WorldTile, CHUNK_SIZE, MAX_RULE_NAME_LEN, TEXT_GRID_CHARS,
+};
+
+fn exported<T: ?Sized>() {}
+
+#[test]
+fn root_exports() {
+	exported::<Anchor>();
+	exported::<ApplyTrace>();
+	exported::<CacheError>();
+	exported::<CacheStats>();
+	exported::<Cell>();
+	exported::<CellData>();
+	exported::<CellGroup>();
+	exported::<CellPattern>();
+	exported::<CellShape>();
+	exported::<ChunkSummary>();
+	exported::<DeltaError>();
+	exported::<Dish>();
+	exported::<DishPart>();
+	exported::<Edge>();
+	exported::<ExportError>();
+	exported::<FixpointResult>();
+	exported::<FrameCounters>();
+	exported::<ImportError>();
+	exported::<InvalidPermutation>();
+	exported::<ParamMismatch>();
+	exported::<ParamSet>();
+	exported::<ParseError>();
+	exported::<RegionMask>();
+	exported::<RemoveGroupError>();
+	exported::<Revisions>();
+	exported::<Rule>();
+	exported::<RuleActivity>();
+	exported::<RuleCellFrom>();
+	exported::<RuleCellTo>();
+	exported::<RuleIterMut<'static>>();
+	exported::<RuleNameError>();
+	exported::<RuleParams>();
+	exported::<Schedule>();
+	exported::<SelectionStrategy>();
+	exported::<SimStats>();
+	exported::<StepMode>();
+	exported::<SubRule>();
+	exported::<Transform>();
+	exported::<ValidationWarning>();
+	exported::<WorldDelta>();
+	exported::<WorldTile>();
+	let _: usize = CHUNK_SIZE;
+	let _: usize = MAX_RULE_NAME_LEN;
+	let _: &str = TEXT_GRID_CHARS;
+}
+
+#[test]
+fn public_modules() {
+	let _ = analyze::no_op_rules;
+	exported::<benchmark::Profile>();
+	let _ = dsl::parse_rule;
+	exported::<dyn dynamic::DynamicRule>();
+	exported::<dynamic::WorldView<'static>>();
+	let _ = Rule::coverage_fraction;
+	let _ = Rule::expected_applications_per_step;
+	let _ = io::load;
+	exported::<dyn observer::Observer>();
+}
@@ -0,0 +1,117 @@
+use std::{fs, path::Path};
+
+use petri::{Cell, Dish};
+use rand::{rngs::StdRng, SeedableRng};
+
+const USAGE: &str =
+	"usage: uscope ensemble [--worlds N] [--steps M] [--seed S] [--input FILE] [--output PATTERN]
+  every world starts as different random cells of all types, drawn using S
+  PATTERN is a file name where {} is replaced by the world index, default \"world_{}.json\"
+  the cells of each world are saved next to it, with the extension .cells.json
+       uscope stats [--input FILE] [--ascii] [--chars CHARS]
+  prints every rule as a table, --ascii avoids box-drawing characters
+  with --chars every rule and variant is printed as text instead,
+  where the nth char of CHARS stands for cell type n";
+
+/// runs a command-line subcommand, or returns None to start the GUI
+pub fn run(mut args: impl Iterator<Item = String>) -> Option<Result<(), String>> {
+	match args.next()?.as_str() {
+		"ensemble" => Some(ensemble(args)),
+		"stats" => Some(stats(args)),
+		"help" | "--help" | "-h" => {
+			println!("{USAGE}");
+			Some(Ok(()))
+		}
+		other => Some(Err(format!("unknown command '{other}'\n{USAGE}"))),
+	}
+}
+
+/// simulate several copies of a universe in parallel and save each result
+fn ensemble(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+	let mut worlds = 4;
+	let mut steps = 10_000;
+	let mut seed = 0;
+	let mut input = None;
+	let mut output = String::from("world_{}.json");
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+		match arg.as_str() {
+			"--worlds" => worlds = parse(&value()?)?,
+			"--steps" => steps = parse(&value()?)?,
+			"--seed" => seed = parse(&value()?)?,
+			"--input" => input = Some(value()?),
+			"--output" => output = value()?,
+			_ => return Err(format!("unknown option '{arg}'\n{USAGE}")),
+		}
+	}
+	let mut dishes = Vec::new();
+	let mut rng = StdRng::seed_from_u64(seed);
+	for _ in 0..worlds {
+		let mut dish = match &input {
+			Some(path) => petri::io::load(Path::new(path)).map_err(|e| e.to_string())?,
+			None => Dish::new(),
+		};
+		// the world isn't part of saved dishes
+		let cells: Vec<(Cell, f32)> = (0..dish.types.len())
+			.map(|id| (Cell(id as u16), 1.))
+			.collect();
+		dish.randomize_world(&cells, &mut rng);
+		dishes.push(dish);
+	}
+	let results = Dish::run_parallel_worlds(dishes, steps, seed);
+	for (i, dish) in results.iter().enumerate() {
+		let path = output.replace("{}", &i.to_string());
+		petri::io::save(dish, Path::new(&path)).map_err(|e| format!("{path}: {e}"))?;
+		let cells_path = Path::new(&path).with_extension("cells.json");
+		let world = dish
+			.export_world_as_json_string()
+			.map_err(|e| e.to_string())?;
+		fs::write(&cells_path, world).map_err(|e| format!("{}: {e}", cells_path.display()))?;
+		println!("saved {path} and {}", cells_path.display());
+	}
+	Ok(())
+}
+
+/// print the cell types and every rule with its variants and cached matches
+fn stats(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+	let mut input = None;
+	let mut chars = None;
+	let mut ascii = false;
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+		match arg.as_str() {
+			"--input" => input = Some(value()?),
+			"--chars" => chars = Some(value()?),
+			"--ascii" => ascii = true,
+			_ => return Err(format!("unknown option '{arg}'\n{USAGE}")),
+		}
+	}
+	let dish = match &input {
+		Some(path) => petri::io::load(Path::new(path)).map_err(|e| e.to_string())?,
+		None => Dish::new(),
+	};
+	if let Some(chars) = &chars {
+		for (cell, ch) in dish.types.iter().zip(chars.chars()) {
+			println!("{ch}: {}", cell.name);
+		}
+	} else {
+		for cell in &dish.types {
+			println!("{}", cell.name);
+		}
+	}
+	let matches = dish.cache_stats().per_rule(dish.rules.len());
+	for (rule, matches) in dish.rules.iter().zip(matches) {
+		let state = if rule.enabled { "enabled" } else { "disabled" };
+		println!("\nrule '{}' ({state}, {matches} matches)", rule.name);
+		match &chars {
+			Some(chars) => println!("{}", rule.as_text_grid(chars, chars)),
+			None if ascii => println!("{}", rule.ascii_display(&dish.types)),
+			None => println!("{}", rule.grid_display(&dish.types)),
+		}
+	}
+	Ok(())
+}
+
+fn parse<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+	s.parse().map_err(|_| format!("invalid number '{s}'"))
+}
@@ -1,27 +1,58 @@
+mod cli;
+mod net;
+mod presets;
+mod ring;
+mod tutorial;
+
 use std::{
-	fs::{self, File},
-	io::Write,
+	f32::consts::{FRAC_1_SQRT_2, SQRT_2},
+	fmt,
+	ops::RangeInclusive,
+	thread::{self, JoinHandle},
 	time::{Duration, Instant},
 };
 
 use eframe::{
 	egui::{
-		CentralPanel, Color32, Painter, Pos2, Rect, ScrollArea, Sense, SidePanel, Slider, Ui, Vec2,
+		CentralPanel, Color32, ColorImage, Painter, Pos2, Rect, ScrollArea, Sense, SidePanel,
+		Slider, TextureHandle, TextureOptions, Ui, Vec2,
 	},
-	epaint::Hsva,
+	epaint::{Hsva, Shape, Stroke},
 	NativeOptions,
 };
-use egui::{collapsing_header::CollapsingState, DragValue, PointerButton};
+use egui::{
+	collapsing_header::CollapsingState, Align, Align2, Area, Button, CollapsingHeader, ComboBox,
+	DragValue, FontId, Frame, Id, Key, LayerId, Order, PointerButton, ProgressBar, RichText,
+	TextEdit, ViewportCommand, Window,
+};
 use native_dialog::FileDialog;
+use presets::{Brush, BrushMode, Preset, Settings};
 use rand::prelude::*;
+use ring::RingBuffer;
+use tutorial::{Highlight, Tutorial};
 
-use petri::{Cell, CellData, CellGroup, Dish, Rule, RuleCellFrom, RuleCellTo, CHUNK_SIZE};
+use petri::{
+	analyze::{self, Overlap, OverlapKind, Reason},
+	dsl::{self, ParseError},
+	io::{self, FileFormat, IoError, References},
+	Anchor, ApplyTrace, Cell, CellData, CellGroup, CellPattern, CellShape, Dish, DishPart,
+	FixpointResult, FrameCounters, ParamMismatch, ParamSet, RegionMask, Revisions, Rule,
+	RuleActivity, RuleCellFrom, RuleCellTo, RuleNameError, Schedule, SelectionStrategy, StepMode,
+	SubRule, Transform, ValidationWarning, MAX_RULE_NAME_LEN,
+};
 
 fn main() {
+	if let Some(result) = cli::run(std::env::args().skip(1)) {
+		if let Err(e) = result {
+			eprintln!("{e}");
+			std::process::exit(1);
+		}
+		return;
+	}
 	eframe::run_native(
-		"µscope",
+		TITLE,
 		NativeOptions::default(),
-		Box::new(|_cc| Box::new(UScope::new(_cc))),
+		Box::new(|cc| Box::new(UScope::new(cc))),
 	)
 	.unwrap();
 }
@@ -29,106 +60,1363 @@ fn main() {
 #[derive(Debug)]
 struct UScope {
 	dish: Dish,
-	brush: Cell,
-	speed: u32,
-	show_grid: bool,
-	sim_times: Vec<Duration>,
+	settings: Settings,
+	presets: Vec<Preset>,
+	/// named rule settings captured from the dish, for switching between while tuning
+	param_sets: Vec<(String, ParamSet)>,
+	new_param_set_name: String,
+	/// rules the last applied parameter set could not be paired with
+	param_status: Option<String>,
+	new_preset_name: String,
+	sim_times: RingBuffer<Duration>,
+	overlaps: Vec<Overlap>,
+	focus_rule: Option<usize>,
+	rule_clipboard: Option<SubRule>,
+	color_threshold: f32,
+	/// colors from before the last auto-distinguish
+	color_undo: Option<Vec<[u8; 3]>>,
+	/// open when Some, holds the size and anchor being edited
+	resize_world: Option<(usize, usize, Anchor)>,
+	/// probability weight for each cell type, used by random fill
+	random_fill_weights: Vec<f32>,
+	tutorial: Tutorial,
+	/// write cell and group names instead of indices in saved rules
+	save_names: bool,
+	/// copy the file being overwritten to `name.bak` when saving
+	keep_backup: bool,
+	/// why the last save or open failed, until dismissed
+	file_error: Option<String>,
+	/// rule whose region mask is being dragged out on the world
+	define_region: Option<usize>,
+	/// corners of the region being dragged
+	region_drag: Option<((usize, usize), (usize, usize))>,
+	/// corners of the selected part of the world
+	selection: Option<((usize, usize), (usize, usize))>,
+	/// dish counters of the last `history_len` frames
+	frame_counters: RingBuffer<FrameCounters>,
+	/// number of frames kept by the performance history buffers
+	history_len: usize,
+	plot_counters: bool,
+	/// editor state for each rule, by index
+	rule_ui: Vec<RuleUi>,
+	/// rule under the pointer in the rule list last frame
+	hovered_rule: Option<usize>,
+	/// rule a shift-click selects a range from
+	rule_select_anchor: Option<usize>,
+	/// fail rate the bulk edit menu sets on the selected rules
+	bulk_failrate: u8,
+	/// when the unfinished cache rebuild was first seen
+	rebuild_started: Option<Instant>,
+	/// last selected brushes, newest first
+	recent_brushes: Vec<Brush>,
+	/// position in `recent_brushes` of the brush picked by Q/E
+	recent_cursor: usize,
+	/// open when Some
+	oscillators: Option<OscillatorSearch>,
+	/// size of a world cell in points, None until it is fitted to the panel
+	cell_size: Option<f32>,
+	collab: Collab,
+	/// text of the last "Profile 1000 steps" run, shown in a window while Some
+	profile_report: Option<String>,
+	/// world position in cells of the top-left corner of the view
+	view_offset: Vec2,
+	/// size of the view in cells, as of the last frame
+	view_size: Vec2,
+	minimap: Option<Minimap>,
+	/// result of the last "run to stable"
+	fixpoint: Option<FixpointResult>,
+	/// `Dish::rule_coverage_fractions` as of `coverage_updated`,
+	/// rescanned at most every `COVERAGE_INTERVAL` while the dish changes
+	coverage: Vec<f64>,
+	coverage_updated: Option<(Instant, Revisions)>,
+	/// open when Some, cached matches are outlined and clicking one applies it
+	match_debug: Option<MatchDebug>,
+	/// `Dish::edit_count` when the dish was last saved or opened
+	saved_edits: u64,
+	/// waiting for the unsaved changes dialog when Some
+	pending_discard: Option<DiscardAction>,
+	/// set once the user chose to quit with unsaved changes
+	allow_close: bool,
+	/// whether the window title currently has the unsaved marker
+	title_unsaved: bool,
+}
+
+/// something that throws away the current dish, asked about first when it has unsaved changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiscardAction {
+	Open,
+	Tutorial,
+	Quit,
+}
+
+/// state of the "Match debugger" window
+#[derive(Debug, Default)]
+struct MatchDebug {
+	/// the last match applied by clicking it
+	trace: Option<ApplyTrace>,
+}
+
+/// the world at one pixel per cell, for the side panel
+struct Minimap {
+	texture: TextureHandle,
+	updated: Instant,
+	/// world revision drawn
+	revision: u64,
+}
+
+impl fmt::Debug for Minimap {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Minimap")
+			.field("updated", &self.updated)
+			.finish_non_exhaustive()
+	}
 }
 
 impl UScope {
-	fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+	fn new(cc: &eframe::CreationContext<'_>) -> Self {
+		let presets = cc
+			.storage
+			.and_then(|storage| eframe::get_value(storage, presets::STORAGE_KEY))
+			.unwrap_or_default();
 		Self {
 			dish: Dish::new(),
-			speed: 50,
-			show_grid: false,
-			brush: Cell(1),
-			// sim_times: vec![0],
-			sim_times: vec![Duration::from_micros(1)],
+			settings: Settings::default(),
+			presets,
+			new_preset_name: String::new(),
+			param_sets: cc
+				.storage
+				.and_then(|storage| eframe::get_value(storage, PARAM_SETS_KEY))
+				.unwrap_or_default(),
+			new_param_set_name: String::new(),
+			param_status: None,
+			sim_times: RingBuffer::new(HISTORY_LEN),
+			overlaps: Vec::new(),
+			focus_rule: None,
+			rule_clipboard: None,
+			color_threshold: 10.,
+			color_undo: None,
+			resize_world: None,
+			random_fill_weights: Vec::new(),
+			tutorial: Tutorial::default(),
+			save_names: false,
+			keep_backup: cc
+				.storage
+				.and_then(|storage| eframe::get_value(storage, KEEP_BACKUP_KEY))
+				.unwrap_or(true),
+			file_error: None,
+			define_region: None,
+			region_drag: None,
+			selection: None,
+			frame_counters: RingBuffer::new(HISTORY_LEN),
+			history_len: HISTORY_LEN,
+			plot_counters: false,
+			rule_ui: Vec::new(),
+			hovered_rule: None,
+			rule_select_anchor: None,
+			bulk_failrate: 0,
+			rebuild_started: None,
+			recent_brushes: Vec::new(),
+			recent_cursor: 0,
+			oscillators: None,
+			collab: Collab::default(),
+			profile_report: None,
+			view_offset: Vec2::splat(-WORLD_MARGIN),
+			view_size: Vec2::ZERO,
+			minimap: None,
+			fixpoint: None,
+			coverage: Vec::new(),
+			coverage_updated: None,
+			match_debug: None,
+			saved_edits: 0,
+			pending_discard: None,
+			allow_close: false,
+			title_unsaved: false,
+			cell_size: cc
+				.storage
+				.and_then(|storage| eframe::get_value(storage, CELL_SIZE_KEY)),
+		}
+	}
+
+	/// true when the rules, types or groups changed since the last save
+	fn has_unsaved_changes(&self) -> bool {
+		self.dish.edit_count() != self.saved_edits
+	}
+
+	/// save through a file dialog, returns whether the file was written
+	fn save_clicked(&mut self) -> bool {
+		match self.save_universe() {
+			Ok(saved) => {
+				if saved {
+					self.saved_edits = self.dish.edit_count();
+				}
+				saved
+			}
+			Err(e) => {
+				self.file_error = Some(format!("saving failed: {e}"));
+				false
+			}
+		}
+	}
+
+	/// run `action`, or ask first if there are unsaved changes
+	fn discard_checked(&mut self, ctx: &egui::Context, action: DiscardAction) {
+		if self.has_unsaved_changes() {
+			self.pending_discard = Some(action);
+		} else {
+			self.discard(ctx, action);
+		}
+	}
+
+	/// forget state kept about the old dish, its revisions don't carry over to the new one
+	fn dish_replaced(&mut self) {
+		self.saved_edits = self.dish.edit_count();
+		self.minimap = None;
+		self.coverage_updated = None;
+		self.fixpoint = None;
+	}
+
+	fn discard(&mut self, ctx: &egui::Context, action: DiscardAction) {
+		match action {
+			DiscardAction::Open => {
+				if let Err(e) = self.open_universe() {
+					self.file_error = Some(format!("opening failed: {e}"));
+				}
+			}
+			DiscardAction::Tutorial => {
+				self.tutorial.start(&mut self.dish);
+				self.dish_replaced();
+			}
+			DiscardAction::Quit => {
+				self.allow_close = true;
+				ctx.send_viewport_cmd(ViewportCommand::Close);
+			}
+		}
+	}
+
+	fn unsaved_changes_window(&mut self, ctx: &egui::Context) {
+		let Some(action) = self.pending_discard else {
+			return;
+		};
+		let (mut save, mut discard, mut cancel) = (false, false, false);
+		Window::new("Unsaved changes")
+			.collapsible(false)
+			.resizable(false)
+			.anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+			.show(ctx, |ui| {
+				ui.label("The rules, cells or groups have changes that are not saved.");
+				ui.horizontal(|ui| {
+					save = ui.button("Save").clicked();
+					discard = ui.button("Discard").clicked();
+					cancel = ui.button("Cancel").clicked();
+				});
+			});
+		if (save && self.save_clicked()) || discard {
+			self.pending_discard = None;
+			self.discard(ctx, action);
+		} else if cancel {
+			self.pending_discard = None;
 		}
 	}
 
-	fn save_universe(&self) -> Option<()> {
+	fn save_universe(&self) -> Result<bool, IoError> {
 		if let Ok(Some(path)) = FileDialog::new()
 			.set_filename("universe_1.json")
 			.add_filter("JSON", &["json"])
+			.add_filter("RON", &["ron"])
+			.add_filter("Binary", &["snad"])
 			.show_save_single_file()
 		{
-			let mut file = File::create(path).ok()?;
-			let out = serde_json::to_string(&self.dish).ok()?;
-			file.write_all(out.as_bytes()).ok()?;
+			let refs = if self.save_names {
+				References::Names
+			} else {
+				References::Ids
+			};
+			let format = FileFormat::from_extension(&path)?;
+			let bytes = io::to_bytes(&self.dish, format, refs)?;
+			io::save_atomic(&path, &bytes, self.keep_backup)?;
+			return Ok(true);
 		}
-		Some(())
+		Ok(false)
 	}
 
-	fn open_universe(&mut self) {
+	fn open_universe(&mut self) -> Result<(), IoError> {
 		if let Ok(Some(path)) = FileDialog::new()
 			.set_filename("universe_1.json")
-			.add_filter("JSON", &["json"])
+			.add_filter("Universe", &["json", "ron", "snad"])
 			.show_open_single_file()
 		{
-			// TODO: show errors to user
-			let s = fs::read_to_string(path).unwrap();
-			self.dish = serde_json::from_str(&s).unwrap();
-			self.dish.update_all_rules();
+			self.dish = petri::io::load(&path)?;
+			self.dish_replaced();
+		}
+		Ok(())
+	}
+
+	/// captured rule settings with buttons to apply them
+	fn param_sets(&mut self, ui: &mut Ui) {
+		let mut to_remove = None;
+		for (i, (name, params)) in self.param_sets.iter_mut().enumerate() {
+			ui.horizontal(|ui| {
+				if ui
+					.button(name.as_str())
+					.on_hover_text("apply to the rules with the same names")
+					.clicked()
+				{
+					self.param_status = match self.dish.apply_params(params) {
+						Ok(()) => None,
+						Err(mismatch) => Some(param_mismatch_text(&mismatch, &self.dish)),
+					};
+				}
+				if ui
+					.small_button("update")
+					.on_hover_text("replace with the current settings")
+					.clicked()
+				{
+					*params = self.dish.capture_params();
+				}
+				if ui.small_button("x").clicked() {
+					to_remove = Some(i);
+				}
+			});
+		}
+		if let Some(i) = to_remove {
+			self.param_sets.remove(i);
+		}
+		ui.horizontal(|ui| {
+			ui.text_edit_singleline(&mut self.new_param_set_name);
+			if ui
+				.button("capture")
+				.on_hover_text(
+					"save enabled, fail rate, temperature, phase and schedule of every rule",
+				)
+				.clicked() && !self.new_param_set_name.is_empty()
+			{
+				let name = std::mem::take(&mut self.new_param_set_name);
+				self.param_sets.push((name, self.dish.capture_params()));
+			}
+		});
+		if let Some(status) = &self.param_status {
+			ui.colored_label(Color32::YELLOW, status);
+		}
+	}
+
+	/// a row for each undefined type in the world, with buttons to define or replace it
+	fn undefined_cell_warnings(&mut self, ui: &mut Ui) {
+		let mut placeholder = None;
+		let mut replace = None;
+		for (cell, count) in self.dish.undefined_cells() {
+			ui.horizontal(|ui| {
+				let (rect, _) = ui.allocate_exact_size(Vec2::splat(CSIZE), Sense::hover());
+				draw_undefined(ui.painter(), rect.shrink(3.));
+				ui.colored_label(
+					Color32::YELLOW,
+					format!("{count} cells reference undefined type {}", cell.id()),
+				);
+			});
+			ui.horizontal(|ui| {
+				if ui.button("create placeholder type").clicked() {
+					placeholder = Some(cell);
+				}
+				ui.menu_button("replace with…", |ui| {
+					for (i, data) in self.dish.types.iter().enumerate() {
+						if ui.button(&data.name).clicked() {
+							replace = Some((cell, Cell(i as u16)));
+							ui.close_menu();
+						}
+					}
+				});
+			});
+		}
+		if let Some(cell) = placeholder {
+			self.dish.add_placeholder_types(cell);
+		}
+		if let Some((from, to)) = replace {
+			self.dish.replace_all(from, to);
+		}
+	}
+
+	/// step through the recent brushes with Q and E, without reordering them
+	fn cycle_recent_brushes(&mut self, ctx: &egui::Context) {
+		let len = self.recent_brushes.len();
+		if len == 0 || ctx.wants_keyboard_input() {
+			return;
+		}
+		let (back, forward) = ctx.input(|i| (i.key_pressed(Key::Q), i.key_pressed(Key::E)));
+		if back {
+			self.recent_cursor = (self.recent_cursor + len - 1) % len;
+		}
+		if forward {
+			self.recent_cursor = (self.recent_cursor + 1) % len;
+		}
+		if back || forward {
+			self.settings.brush = self.recent_brushes[self.recent_cursor % len];
+		}
+	}
+
+	/// move a newly selected brush to the front of the recent brushes
+	fn remember_brush(&mut self) {
+		let types = self.dish.types.len();
+		let groups = self.dish.groups.len();
+		self.recent_brushes.retain(|brush| match brush {
+			Brush::Cell(cell) => cell.id() < types,
+			Brush::Group(group) => *group < groups,
+		});
+		let brush = self.settings.brush;
+		if self.recent_brushes.get(self.recent_cursor) == Some(&brush) {
+			return;
+		}
+		self.recent_brushes.retain(|&b| b != brush);
+		self.recent_brushes.insert(0, brush);
+		self.recent_brushes.truncate(RECENT_BRUSHES);
+		self.recent_cursor = 0;
+	}
+
+	/// favorite cells followed by recent brushes, click one to select it
+	fn quick_brush_row(&mut self, ui: &mut Ui) {
+		let mut brushes: Vec<Brush> = (0..self.dish.types.len())
+			.filter(|&i| self.dish.types[i].favorite)
+			.map(|i| Brush::Cell(Cell(i as u16)))
+			.collect();
+		for &brush in &self.recent_brushes {
+			if !brushes.contains(&brush) {
+				brushes.push(brush);
+			}
+		}
+		ui.horizontal(|ui| {
+			for brush in brushes {
+				let (rect, response) = ui.allocate_exact_size(Vec2::splat(CSIZE), Sense::click());
+				let name = match brush {
+					Brush::Cell(cell) => {
+						let data = &self.dish.types[cell.id()];
+						render_cell(ui.painter(), rect.shrink(3.), data);
+						&data.name
+					}
+					Brush::Group(group) => {
+						let group = &self.dish.groups[group];
+						draw_group(ui, rect.shrink(3.), group, &self.dish.types);
+						&group.name
+					}
+				};
+				if brush == self.settings.brush {
+					ui.painter().rect_stroke(rect, 0., OUTLINE);
+				}
+				if response.on_hover_text(name).clicked() {
+					self.settings.brush = brush;
+				}
+			}
+		});
+	}
+
+	fn export_rule_docs(&self) -> std::io::Result<()> {
+		if let Ok(Some(path)) = FileDialog::new()
+			.set_filename("rules.md")
+			.add_filter("Markdown", &["md"])
+			.show_save_single_file()
+		{
+			io::save_atomic(&path, self.dish.export_rules_markdown().as_bytes(), false)?;
+		}
+		Ok(())
+	}
+
+	fn resize_world_window(&mut self, ctx: &egui::Context) {
+		let Some((width, height, anchor)) = &mut self.resize_world else {
+			return;
+		};
+		let mut open = true;
+		let mut apply = false;
+		Window::new("World size").open(&mut open).show(ctx, |ui| {
+			ui.horizontal(|ui| {
+				ui.add(
+					DragValue::new(width)
+						.clamp_range(1..=1024)
+						.prefix("width: "),
+				);
+				ui.add(
+					DragValue::new(height)
+						.clamp_range(1..=1024)
+						.prefix("height: "),
+				);
+			});
+			ui.label("anchor:");
+			ui.horizontal(|ui| {
+				let rows = [
+					[Anchor::TopLeft, Anchor::Top, Anchor::TopRight],
+					[Anchor::Left, Anchor::Center, Anchor::Right],
+					[Anchor::BottomLeft, Anchor::Bottom, Anchor::BottomRight],
+				];
+				ui.vertical(|ui| {
+					for row in rows {
+						ui.horizontal(|ui| {
+							for a in row {
+								ui.radio_value(anchor, a, "");
+							}
+						});
+					}
+				});
+				let old_size = (self.dish.world_width(), self.dish.world_height());
+				resize_preview(ui, old_size, (*width, *height), *anchor);
+			});
+			apply = ui.button("resize").clicked();
+		});
+		if apply {
+			self.dish.resize_world(*width, *height, *anchor);
+		}
+		if !open || apply {
+			self.resize_world = None;
+		}
+	}
+
+	fn oscillator_window(&mut self, ctx: &egui::Context) {
+		let Some(search) = &mut self.oscillators else {
+			return;
+		};
+		if search.running.as_ref().is_some_and(JoinHandle::is_finished) {
+			let handle = search.running.take().unwrap();
+			search.found = handle.join().unwrap_or_default();
+		}
+		let mut open = true;
+		let mut start = false;
+		Window::new("Oscillators").open(&mut open).show(ctx, |ui| {
+			ui.add(
+				DragValue::new(&mut search.period)
+					.clamp_range(2..=64)
+					.prefix("period: "),
+			)
+			.on_hover_text("divisors of the period are found too");
+			ui.add(
+				DragValue::new(&mut search.steps)
+					.clamp_range(4..=10_000)
+					.prefix("steps: "),
+			);
+			ui.add(DragValue::new(&mut search.seed).prefix("seed: "));
+			if search.running.is_some() {
+				ui.horizontal(|ui| {
+					ui.spinner();
+					ui.label("simulating a copy of the world");
+				});
+				ctx.request_repaint();
+			} else {
+				start = ui.button("detect").clicked();
+				ui.label(format!("{} oscillating cells", search.found.len()));
+				ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+					for (x, y, period) in &search.found {
+						ui.label(format!("{x},{y}: period {period}"));
+					}
+				});
+			}
+		});
+		if start {
+			let copy = petri::io::to_json_value(&self.dish, References::Ids)
+				.and_then(petri::io::from_json_value);
+			if let Ok(mut copy) = copy {
+				let (period, steps, seed) = (search.period, search.steps, search.seed);
+				search.found.clear();
+				search.running = Some(thread::spawn(move || {
+					copy.update_all_rules();
+					copy.find_oscillating_patterns(period, steps, seed)
+				}));
+			}
+		}
+		if !open {
+			self.oscillators = None;
+		}
+	}
+
+	/// pick up finished sends and received rules from the network threads
+	fn poll_collab(&mut self, ctx: &egui::Context) {
+		let collab = &mut self.collab;
+		collab
+			.sending
+			.retain(|(description, sending)| match sending.poll() {
+				Some(result) => {
+					collab.status = Some(match result {
+						Ok(()) => format!("sent {description}"),
+						Err(e) => format!("sending failed: {e}"),
+					});
+					false
+				}
+				None => true,
+			});
+		if !collab.sending.is_empty() {
+			ctx.request_repaint();
+		}
+		let Some(listener) = &collab.listener else {
+			return;
+		};
+		match listener.poll() {
+			Some(Ok(value)) => match io::rule_from_json_value(&self.dish, value) {
+				Ok(rule) => self.collab.received = Some(rule),
+				Err(e) => self.collab.status = Some(format!("received an invalid rule: {e}")),
+			},
+			Some(Err(e)) => self.collab.status = Some(format!("receiving failed: {e}")),
+			None => (),
+		}
+	}
+
+	/// Select a rule like a file list: a plain click selects only it, `toggle` adds or removes it,
+	/// and `range` selects everything from the last clicked rule.
+	fn select_rule(&mut self, index: usize, toggle: bool, range: bool) {
+		let anchor = self
+			.rule_select_anchor
+			.filter(|&a| range && a < self.rule_ui.len());
+		if let Some(anchor) = anchor {
+			let span = anchor.min(index)..=anchor.max(index);
+			for (i, rule_ui) in self.rule_ui.iter_mut().enumerate() {
+				rule_ui.selected = span.contains(&i) || (toggle && rule_ui.selected);
+			}
+			return;
+		}
+		if toggle {
+			self.rule_ui[index].selected ^= true;
+		} else {
+			let only = self.rule_ui[index].selected
+				&& self.rule_ui.iter().filter(|r| r.selected).count() == 1;
+			for rule_ui in &mut self.rule_ui {
+				rule_ui.selected = false;
+			}
+			self.rule_ui[index].selected = !only;
 		}
+		self.rule_select_anchor = Some(index);
+	}
+
+	fn bulk_edit_menu(&mut self, ui: &mut Ui, selected: &[usize]) {
+		let mut edit: Option<RuleEdit> = None;
+		ui.horizontal(|ui| {
+			if ui.button("enable").clicked() {
+				edit = Some(Box::new(|rule| {
+					rule.enabled = true;
+					rule.enabled_reason = None;
+				}));
+			}
+			if ui.button("disable").clicked() {
+				edit = Some(Box::new(|rule| {
+					rule.enabled = false;
+					rule.enabled_reason = None;
+				}));
+			}
+		});
+		ui.horizontal(|ui| {
+			ui.add(DragValue::new(&mut self.bulk_failrate).prefix("fail rate: "));
+			if ui.button("set").clicked() {
+				let failrate = self.bulk_failrate;
+				edit = Some(Box::new(move |rule| rule.failrate = failrate));
+			}
+		});
+		let flags: [(&str, RuleFlag); 4] = [
+			("flip X", |rule| &mut rule.flip_x),
+			("flip Y", |rule| &mut rule.flip_y),
+			("rotate", |rule| &mut rule.rotate),
+			("ignore temperature", |rule| &mut rule.ignore_temperature),
+		];
+		for (name, flag) in flags {
+			ui.horizontal(|ui| {
+				ui.label(format!("{name}:"));
+				for (label, value) in [("on", true), ("off", false)] {
+					if ui.button(label).clicked() {
+						edit = Some(Box::new(move |rule| *flag(rule) = value));
+					}
+				}
+			});
+		}
+		if let Some(edit) = edit {
+			self.dish.edit_rules(selected, edit);
+		}
+	}
+
+	fn send_rule(&mut self, rule: usize) {
+		let collab = &mut self.collab;
+		match io::rule_to_json_value(&self.dish, rule) {
+			Ok(value) => {
+				let description = format!("'{}' to {}", self.dish.rules[rule].name, collab.peer);
+				collab.status = Some(format!("sending {description}"));
+				let sending = net::send(collab.peer.clone(), value);
+				collab.sending.push((description, sending));
+			}
+			Err(e) => collab.status = Some(format!("sending failed: {e}")),
+		}
+	}
+
+	fn received_rule_window(&mut self, ctx: &egui::Context) {
+		let Some(rule) = &self.collab.received else {
+			return;
+		};
+		let mut accept = false;
+		let mut reject = false;
+		Window::new("Received rule").show(ctx, |ui| {
+			ui.label(format!("a peer sent the rule '{}'", rule.name));
+			pattern_preview(ui, rule, &self.dish.types, &self.dish.groups);
+			ui.horizontal(|ui| {
+				accept = ui.button("accept").clicked();
+				reject = ui.button("reject").clicked();
+			});
+		});
+		if accept {
+			let rule = self.collab.received.take().unwrap();
+			let name = rule.name.clone();
+			self.collab.status = Some(match self.dish.import_rules(vec![rule]) {
+				Ok(()) => format!("imported '{name}'"),
+				Err(e) => format!("could not import '{name}': {e:?}"),
+			});
+		} else if reject {
+			self.collab.received = None;
+		}
+	}
+
+	/// overview of the whole world with the view outlined, clicking or dragging on it moves the view
+	fn minimap(&mut self, ui: &mut Ui) {
+		let (width, height) = (self.dish.world_width(), self.dish.world_height());
+		let stale = self.minimap.as_ref().is_none_or(|m| {
+			let changed = m.revision != self.dish.revision(DishPart::World);
+			(changed && m.updated.elapsed() > MINIMAP_INTERVAL)
+				|| m.texture.size() != [width, height]
+		});
+		if stale {
+			let pixels = self
+				.dish
+				.get_region_flat(0, 0, width, height)
+				.iter()
+				.map(|cell| {
+					self.dish
+						.types
+						.get(cell.id())
+						.map_or(UNDEFINED_COLOR, cell_color)
+				})
+				.collect();
+			let image = ColorImage {
+				size: [width, height],
+				pixels,
+			};
+			match &mut self.minimap {
+				Some(minimap) => {
+					minimap.texture.set(image, TextureOptions::NEAREST);
+					minimap.updated = Instant::now();
+					minimap.revision = self.dish.revision(DishPart::World);
+				}
+				None => {
+					self.minimap = Some(Minimap {
+						texture: ui
+							.ctx()
+							.load_texture("minimap", image, TextureOptions::NEAREST),
+						updated: Instant::now(),
+						revision: self.dish.revision(DishPart::World),
+					})
+				}
+			}
+		}
+		let Some(minimap) = &self.minimap else {
+			return;
+		};
+		let scale = MINIMAP_SIZE / width.max(height) as f32;
+		let (rect, response) = ui.allocate_exact_size(
+			Vec2::new(width as f32, height as f32) * scale,
+			Sense::click_and_drag(),
+		);
+		ui.painter().image(
+			minimap.texture.id(),
+			rect,
+			Rect::from_min_max(Pos2::ZERO, Pos2::new(1., 1.)),
+			Color32::WHITE,
+		);
+		let view = Rect::from_min_size(rect.min + self.view_offset * scale, self.view_size * scale);
+		ui.painter()
+			.rect_stroke(view.intersect(rect), 0., (1., Color32::YELLOW));
+		if let Some(pos) = response.interact_pointer_pos() {
+			// center the view on the cell, the central panel keeps it inside the world
+			self.view_offset = (pos - rect.min) / scale - self.view_size / 2.;
+		}
+	}
+
+	fn profile_window(&mut self, ctx: &egui::Context) {
+		let Some(report) = &self.profile_report else {
+			return;
+		};
+		let mut open = true;
+		Window::new("Profile").open(&mut open).show(ctx, |ui| {
+			ui.label(RichText::new(report).monospace());
+		});
+		if !open {
+			self.profile_report = None;
+		}
+	}
+
+	fn match_debug_window(&mut self, ctx: &egui::Context) {
+		let Some(debug) = &self.match_debug else {
+			return;
+		};
+		let types = &self.dish.types;
+		let name = |cell: Cell| {
+			types
+				.get(cell.id())
+				.map_or_else(|| format!("undefined #{}", cell.id()), |t| t.name.clone())
+		};
+		let mut open = true;
+		Window::new("Match debugger")
+			.open(&mut open)
+			.show(ctx, |ui| {
+				ui.label("click a highlighted match in the world to apply it");
+				let Some(trace) = &debug.trace else {
+					return;
+				};
+				ui.separator();
+				let rule = self.dish.rules.get(trace.rule).map_or("?", |r| &r.name);
+				ui.label(format!("rule '{rule}', variant {}", trace.variant));
+				ui.label(format!("origin: {},{}", trace.origin.0, trace.origin.1));
+				ui.label(match (trace.matched, trace.applied) {
+					(false, _) => "did not match",
+					(true, false) => {
+						"matched, but hit frozen cells or failed the failrate/temperature roll"
+					}
+					(true, true) => "applied",
+				});
+				let width = trace.footprint.2;
+				ui.horizontal(|ui| {
+					ui.vertical(|ui| {
+						ui.label("before");
+						footprint_preview(ui, &trace.old_state, width, types);
+					});
+					ui.vertical(|ui| {
+						ui.label("after");
+						footprint_preview(ui, &trace.new_state, width, types);
+					});
+				});
+				ui.label(format!("{} writes", trace.writes.len()));
+				ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+					for &(x, y, old, new) in &trace.writes {
+						ui.label(format!("{x},{y}: {} -> {}", name(old), name(new)));
+					}
+				});
+			});
+		if !open {
+			self.match_debug = None;
+		}
+	}
+
+	fn selection_window(&mut self, ctx: &egui::Context) {
+		let Some((start, end)) = self.selection else {
+			return;
+		};
+		let rect = rect_between(start, end);
+		let mut open = true;
+		Window::new("Selection").open(&mut open).show(ctx, |ui| {
+			let area = rect.w * rect.h;
+			ui.label(format!("{}x{} at ({}, {})", rect.w, rect.h, rect.x, rect.y));
+			let census = self.dish.census_region(rect.x, rect.y, rect.w, rect.h);
+			for (cell, &count) in self.dish.types.iter().zip(&census) {
+				if count > 0 {
+					let share = count as f32 / area as f32 * 100.;
+					ui.label(format!("{}: {count} ({share:.1}%)", cell.name));
+				}
+			}
+			let matches = self.dish.match_count_region(rect);
+			ui.label(format!(
+				"matches: {matches} ({:.2} per cell)",
+				matches as f32 / area as f32
+			));
+		});
+		if !open {
+			self.selection = None;
+		}
+	}
+
+	fn tutorial_window(&mut self, ctx: &egui::Context) {
+		let Some(step) = self.tutorial.current() else {
+			return;
+		};
+		let complete = (step.is_complete)(&self.dish);
+		Window::new("Tutorial").show(ctx, |ui| {
+			ui.heading(step.title);
+			ui.label(step.text);
+			ui.horizontal(|ui| {
+				if complete {
+					ui.colored_label(Color32::GREEN, "✔ done");
+				}
+				if ui.add_enabled(complete, Button::new("next")).clicked() {
+					self.tutorial.advance();
+				}
+				if ui.button("exit").clicked() {
+					self.tutorial.stop();
+				}
+			});
+		});
 	}
 }
 
 impl eframe::App for UScope {
+	fn save(&mut self, storage: &mut dyn eframe::Storage) {
+		eframe::set_value(storage, presets::STORAGE_KEY, &self.presets);
+		eframe::set_value(storage, CELL_SIZE_KEY, &self.cell_size);
+		eframe::set_value(storage, KEEP_BACKUP_KEY, &self.keep_backup);
+		eframe::set_value(storage, PARAM_SETS_KEY, &self.param_sets);
+	}
+
 	fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
 		ctx.request_repaint();
+		let unsaved = self.has_unsaved_changes();
+		if unsaved && !self.allow_close && ctx.input(|i| i.viewport().close_requested()) {
+			ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+			self.pending_discard = Some(DiscardAction::Quit);
+		}
+		if unsaved != self.title_unsaved {
+			let title = if unsaved {
+				format!("{TITLE} *")
+			} else {
+				TITLE.to_owned()
+			};
+			ctx.send_viewport_cmd(ViewportCommand::Title(title));
+			self.title_unsaved = unsaved;
+		}
+		for (preset, key) in self.presets.iter().zip(presets::SHORTCUTS) {
+			if ctx.input(|i| i.key_pressed(key)) {
+				self.settings = preset.settings.clone();
+			}
+		}
+		self.cycle_recent_brushes(ctx);
+		self.poll_collab(ctx);
+		// counted since the last frame, so edits made through the UI are included
+		self.frame_counters.push(self.dish.frame_counters());
+		self.dish.reset_frame_counters();
+		let rebuilding = self.dish.continue_cache_rebuild(REBUILD_FRAME_BUDGET);
+		match rebuilding {
+			Some((done, total)) => {
+				let started = *self.rebuild_started.get_or_insert_with(Instant::now);
+				if started.elapsed() > REBUILD_PROGRESS_DELAY {
+					Area::new(Id::new("rebuild progress"))
+						.order(Order::Foreground)
+						.anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+						.show(ctx, |ui| {
+							Frame::popup(ui.style()).show(ui, |ui| {
+								ui.label("rebuilding cache");
+								ui.add(
+									ProgressBar::new(done as f32 / total as f32)
+										.desired_width(200.)
+										.show_percentage(),
+								);
+							});
+						});
+				}
+			}
+			None => self.rebuild_started = None,
+		}
 		let sim_frame = Instant::now();
-		for _ in 0..self.speed {
-			self.dish.try_one_location();
-			// self.dish.apply_one_match();
+		// the old cache is out of date until the rebuild finishes
+		if !self.settings.paused && rebuilding.is_none() {
+			self.dish
+				.step(self.settings.step_mode, self.settings.speed as usize);
 		}
 		let sim_time = sim_frame.elapsed();
-		// self.sim_times.push(sim_time.as_micros());
+		self.dish.snapshot_stats();
 		self.sim_times.push(sim_time);
-		if self.sim_times.len() > 60 {
-			self.sim_times.remove(0);
-		}
 		SidePanel::left("left_panel")
 			.min_width(100.)
 			.show(ctx, |ui| {
 				ui.heading("Simulation");
 				ui.add(
-					Slider::new(&mut self.speed, 0..=500)
+					Slider::new(&mut self.settings.speed, 0..=500)
 						.clamp_to_range(false)
 						.text("speed"),
 				);
+				ui.checkbox(&mut self.settings.paused, "paused");
+				ui.horizontal(|ui| {
+					let world_size = self.dish.world_width() * self.dish.world_height();
+					let mode = &mut self.settings.step_mode;
+					ComboBox::from_label("step mode")
+						.selected_text(match mode {
+							StepMode::Random => "random",
+							StepMode::Phased => "phased",
+							StepMode::Batch => "fair batching",
+							StepMode::Epoch { .. } => "epoch",
+						})
+						.show_ui(ui, |ui| {
+							ui.selectable_value(mode, StepMode::Random, "random")
+								.on_hover_text("each step tries a random position");
+							ui.selectable_value(mode, StepMode::Phased, "phased")
+								.on_hover_text("each step only runs the rules of the current phase");
+							ui.selectable_value(mode, StepMode::Batch, "fair batching")
+								.on_hover_text("never apply overlapping matches in the same frame");
+							if !matches!(mode, StepMode::Epoch { .. }) {
+								ui.selectable_value(
+									mode,
+									StepMode::Epoch {
+										epoch_size: world_size,
+									},
+									"epoch",
+								)
+								.on_hover_text(
+									"each step visits every position once in random order",
+								);
+							}
+						});
+					match mode {
+						StepMode::Phased => {
+							ui.label(format!("phase: {}", self.dish.current_phase()));
+						}
+						StepMode::Epoch { epoch_size } => {
+							ui.add(DragValue::new(epoch_size).prefix("positions: "));
+						}
+						_ => (),
+					}
+				});
+				let selection = &mut self.dish.selection;
+				let before = *selection;
+				ComboBox::from_label("match selection")
+					.selected_text(match selection {
+						SelectionStrategy::Uniform => "uniform",
+						SelectionStrategy::Stratified => "stratified",
+					})
+					.show_ui(ui, |ui| {
+						ui.selectable_value(selection, SelectionStrategy::Uniform, "uniform")
+							.on_hover_text("every match is equally likely");
+						ui.selectable_value(selection, SelectionStrategy::Stratified, "stratified")
+							.on_hover_text(
+								"every area with a match is equally likely, so sparse areas don't stall",
+							);
+					})
+					.response
+					.on_hover_text("used by the phased and fair batching step modes");
+				if *selection != before {
+					self.dish.mark_edited();
+				}
+				let temperature = ui.add(
+					Slider::new(&mut self.dish.temperature, 0.0..=4.0)
+						.text("temperature")
+						.logarithmic(true)
+						.smallest_positive(0.01),
+				);
+				if temperature.changed() {
+					self.dish.mark_edited();
+				}
 				ui.label(format!("sim time: {sim_time:?}"));
-				let avg_sim_time =
-					self.sim_times.iter().sum::<Duration>() / self.sim_times.len() as u32;
-				ui.label(format!("average sim time: {avg_sim_time:?}"));
+				ui.horizontal(|ui| {
+					ui.label(format!("step: {}", self.dish.step_count()))
+						.on_hover_text("steps taken, used by rule schedules");
+					if ui.button("reset").clicked() {
+						self.dish.set_step_count(0);
+					}
+				});
+				ui.label(format!("average sim time: {:?}", self.sim_times.average()));
+
+				CollapsingHeader::new("Statistics").show(ui, |ui| {
+					ui.label(format!(
+						"entropy: {:.3} ({:+.4}/step)",
+						self.dish.world_entropy_normalized(),
+						self.dish.entropy_change_rate()
+					));
+					sparkline(ui, self.dish.stats.entropy.make_contiguous());
+					ui.horizontal(|ui| {
+						if ui
+							.button("run to stable")
+							.on_hover_text(format!(
+								"simulate up to {FIXPOINT_MAX_STEPS} steps until nothing can match"
+							))
+							.clicked()
+						{
+							self.fixpoint =
+								Some(self.dish.time_to_fixpoint(FIXPOINT_MAX_STEPS, random()));
+						}
+						match self.fixpoint {
+							Some(FixpointResult::Reached { steps }) => {
+								ui.label(format!("stable after {steps} steps"));
+							}
+							Some(FixpointResult::NotReached {
+								final_entropy,
+								period_detected,
+							}) => {
+								let mut text = format!(
+									"not stable, entropy {final_entropy:.3} ({:+.5}/step)",
+									self.dish.convergence_speed(CONVERGENCE_WINDOW)
+								);
+								if let Some(period) = period_detected {
+									text += &format!(", repeating every {period} steps");
+								}
+								ui.label(text);
+							}
+							None => (),
+						}
+					});
+				});
+				CollapsingHeader::new("Performance").show(ui, |ui| {
+					let names = [
+						"matches examined",
+						"cache entries scanned",
+						"cells written",
+						"rng draws",
+					];
+					let values = |c: &FrameCounters| {
+						[
+							c.matches_examined,
+							c.cache_entries_scanned,
+							c.cells_written,
+							c.rng_draws,
+						]
+					};
+					ui.checkbox(&mut self.plot_counters, "plot");
+					ui.horizontal(|ui| {
+						ui.add(
+							DragValue::new(&mut self.history_len)
+								.clamp_range(1..=MAX_HISTORY_LEN)
+								.prefix("history: "),
+						)
+						.on_hover_text("frames kept for the average sim time and the plots");
+						let bytes =
+							self.sim_times.memory_size() + self.frame_counters.memory_size();
+						ui.label(format!("{:.1} KiB", bytes as f32 / 1024.));
+					});
+					if self.history_len != self.sim_times.capacity() {
+						self.sim_times.set_capacity(self.history_len);
+						self.frame_counters.set_capacity(self.history_len);
+					}
+					let last = values(&self.frame_counters.last().copied().unwrap_or_default());
+					for (i, name) in names.iter().enumerate() {
+						ui.label(format!("{name}: {}/frame", last[i]));
+						if self.plot_counters {
+							let history: Vec<f64> = self
+								.frame_counters
+								.iter()
+								.map(|c| values(c)[i] as f64)
+								.collect();
+							sparkline(ui, &history);
+						}
+					}
+				});
 
-				ui.checkbox(&mut self.show_grid, "show grid");
+				ui.checkbox(&mut self.settings.show_grid, "show grid");
+				ui.checkbox(&mut self.settings.show_labels, "show cell names")
+					.on_hover_text("only drawn when zoomed in");
+				CollapsingHeader::new("Collaboration").show(ui, |ui| {
+					let collab = &mut self.collab;
+					ui.horizontal(|ui| {
+						let mut listening = collab.listener.is_some();
+						if ui
+							.checkbox(&mut listening, "receive rules on port")
+							.on_hover_text("only reachable from this computer")
+							.changed()
+						{
+							collab.listener = None;
+							if listening {
+								match net::Listener::bind(collab.port) {
+									Ok(listener) => collab.listener = Some(listener),
+									Err(e) => collab.status = Some(format!("could not listen: {e}")),
+								}
+							}
+						}
+						ui.add_enabled(
+							collab.listener.is_none(),
+							DragValue::new(&mut collab.port),
+						);
+					});
+					ui.horizontal(|ui| {
+						ui.label("peer:");
+						ui.text_edit_singleline(&mut collab.peer);
+					});
+					if let Some(status) = &collab.status {
+						ui.label(status);
+					}
+				});
+				ui.horizontal(|ui| {
+					if let Some(size) = &mut self.cell_size {
+						ui.add(Slider::new(size, CELL_SIZE_RANGE).text("cell size"))
+							.on_hover_text("ctrl+scroll over the world to zoom");
+					}
+					if ui.button("fit").clicked() {
+						self.cell_size = None;
+						self.view_offset = Vec2::splat(-WORLD_MARGIN);
+					}
+				});
+				self.minimap(ui);
+				CollapsingHeader::new("Presets").show(ui, |ui| {
+					let mut to_remove = None;
+					for (i, preset) in self.presets.iter().enumerate() {
+						ui.horizontal(|ui| {
+							let mut button = ui.button(&preset.name);
+							if let Some(key) = presets::SHORTCUTS.get(i) {
+								button = button.on_hover_text(key.name());
+							}
+							if button.clicked() {
+								self.settings = preset.settings.clone();
+							}
+							if ui.small_button("x").clicked() {
+								to_remove = Some(i);
+							}
+						});
+					}
+					if let Some(i) = to_remove {
+						self.presets.remove(i);
+					}
+					ui.horizontal(|ui| {
+						ui.text_edit_singleline(&mut self.new_preset_name);
+						if ui.button("save preset").clicked() && !self.new_preset_name.is_empty() {
+							self.presets.push(Preset {
+								name: std::mem::take(&mut self.new_preset_name),
+								settings: self.settings.clone(),
+							});
+						}
+					});
+				});
+				CollapsingHeader::new("Parameter sets").show(ui, |ui| self.param_sets(ui));
 				if ui.button("regenerate rules and cache").clicked() {
 					self.dish.update_all_rules();
 				}
-				if ui.button("debug cache").clicked() {
-					self.dish.dbg_cache();
-				}
+				CollapsingHeader::new("Cache").show(ui, |ui| {
+					let stats = self.dish.cache_stats();
+					ui.label(format!("matches: {}", stats.total_matches));
+					ui.label(format!("memory: ~{} KiB", stats.memory_estimate / 1024));
+					if let (Some(at), Some(duration)) =
+						(stats.last_rebuild, stats.last_rebuild_duration)
+					{
+						ui.label(format!(
+							"last rebuild: {duration:?}, {:.0?} ago",
+							at.elapsed()
+						));
+					}
+					ui.label(format!(
+						"updates since: {} ({:?})",
+						stats.incremental_updates, stats.incremental_duration
+					));
+					let per_rule = stats.per_rule(self.dish.rules.len());
+					bar_chart(ui, &self.dish.rules, &per_rule);
+				});
 				ui.horizontal(|ui| {
 					if ui.button("Save").clicked() {
-						self.save_universe();
+						self.save_clicked();
 					}
+					ui.checkbox(&mut self.save_names, "by name")
+						.on_hover_text("refer to cells and groups by name, so reordering them doesn't break saved rules");
+					ui.checkbox(&mut self.keep_backup, "keep backup")
+						.on_hover_text("copy the file being overwritten to <name>.bak");
 					if ui.button("Open").clicked() {
-						self.open_universe();
+						self.discard_checked(ui.ctx(), DiscardAction::Open);
+					}
+					if ui.button("World size").clicked() {
+						self.resize_world = Some((
+							self.dish.world_width(),
+							self.dish.world_height(),
+							Anchor::TopLeft,
+						));
+					}
+					if ui.button("Tutorial").clicked() {
+						self.discard_checked(ui.ctx(), DiscardAction::Tutorial);
+					}
+					if ui.button("Export rule docs").clicked() {
+						if let Err(e) = self.export_rule_docs() {
+							self.file_error = Some(format!("exporting failed: {e}"));
+						}
+					}
+					if ui.button("Profile 1000 steps").clicked() {
+						let profile = self.dish.run_profiled(1000, &mut thread_rng());
+						self.profile_report = Some(profile.report(&self.dish.rules));
+					}
+					if ui.button("Detect oscillators").clicked() {
+						self.oscillators.get_or_insert_with(OscillatorSearch::default);
+					}
+					if ui.button("Debug matches").clicked() {
+						self.match_debug.get_or_insert_with(MatchDebug::default);
 					}
 				});
+				if let Some(error) = &self.file_error {
+					let mut dismissed = false;
+					ui.horizontal(|ui| {
+						ui.colored_label(Color32::RED, error);
+						dismissed = ui.small_button("×").clicked();
+					});
+					if dismissed {
+						self.file_error = None;
+					}
+				}
 				ui.separator();
 
+				let highlight = self.tutorial.highlight();
 				ScrollArea::vertical().show(ui, |ui| {
+					let section_start = ui.cursor().min;
 					ui.heading("Cells");
+					let collisions =
+						analyze::color_collisions(&self.dish.types, self.color_threshold);
+					let names: Vec<String> =
+						self.dish.types.iter().map(|c| c.name.clone()).collect();
+					let mut types_edited = false;
 					for (i, cell) in self.dish.types.iter_mut().enumerate() {
 						ui.horizontal(|ui| {
 							ui.set_width(120.);
-							ui.radio_value(&mut self.brush.0, i as u16, "");
-							ui.text_edit_singleline(&mut cell.name);
-							ui.color_edit_button_srgb(&mut cell.color);
+							ui.radio_value(
+								&mut self.settings.brush,
+								Brush::Cell(Cell(i as u16)),
+								"",
+							);
+							let star = if cell.favorite { "★" } else { "☆" };
+							if ui
+								.selectable_label(cell.favorite, star)
+								.on_hover_text("show in the quick brush row")
+								.clicked()
+							{
+								cell.favorite = !cell.favorite;
+								types_edited = true;
+							}
+							ui.scope(|ui| {
+								let [r, g, b] = cell.color;
+								ui.visuals_mut().extreme_bg_color = Color32::from_rgb(r, g, b);
+								let [r, g, b] = cell.contrasting_text_color();
+								types_edited |= ui
+									.add(
+										TextEdit::singleline(&mut cell.name)
+											.text_color(Color32::from_rgb(r, g, b)),
+									)
+									.changed();
+							});
+							types_edited |= ui.color_edit_button_srgb(&mut cell.color).changed();
+							ComboBox::from_id_source(("shape", i))
+								.width(70.)
+								.selected_text(format!("{:?}", cell.shape))
+								.show_ui(ui, |ui| {
+									for shape in [
+										CellShape::Square,
+										CellShape::Circle,
+										CellShape::Diamond,
+										CellShape::Cross,
+									] {
+										types_edited |= ui
+											.selectable_value(
+												&mut cell.shape,
+												shape,
+												format!("{shape:?}"),
+											)
+											.changed();
+									}
+								});
+							ComboBox::from_id_source(("pattern", i))
+								.width(70.)
+								.selected_text(format!("{:?}", cell.pattern))
+								.show_ui(ui, |ui| {
+									for pattern in CellPattern::ALL {
+										types_edited |= ui
+											.selectable_value(
+												&mut cell.pattern,
+												pattern,
+												format!("{pattern:?}"),
+											)
+											.changed();
+									}
+								})
+								.response
+								.on_hover_text("drawn over the color, to tell types apart without it");
+							let similar: Vec<&str> = collisions
+								.iter()
+								.filter_map(|&(a, b)| match (a == i, b == i) {
+									(true, _) => Some(names[b].as_str()),
+									(_, true) => Some(names[a].as_str()),
+									_ => None,
+								})
+								.collect();
+							if !similar.is_empty() {
+								ui.colored_label(Color32::YELLOW, "⚠")
+									.on_hover_text(format!(
+										"color is hard to tell apart from {}",
+										similar.join(", ")
+									));
+							}
 						});
 					}
+					if types_edited {
+						self.dish.mark_changed(DishPart::Types);
+					}
+					self.undefined_cell_warnings(ui);
 
 					if ui.button("add cell").clicked() {
 						let h = random::<f32>();
@@ -136,65 +1424,377 @@ impl eframe::App for UScope {
 						let v = random::<f32>() * 0.5 + 0.5;
 						let color = Hsva::new(h, s, v, 1.).to_srgb();
 						let name = format!("cell #{}", self.dish.types.len());
-						self.dish.types.push(CellData { name, color })
+						self.dish.add_type(CellData {
+							name,
+							color,
+							..Default::default()
+						});
 					}
 					if ui.button("fill").clicked() {
-						self.dish.fill(self.brush);
+						match self.settings.brush {
+							Brush::Cell(cell) => self.dish.fill(cell),
+							Brush::Group(group) => {
+								for x in 0..self.dish.world_width() {
+									for y in 0..self.dish.world_height() {
+										if let Some(cell) = self.dish.random_from_group(group) {
+											self.dish.set_cell(x, y, cell);
+										}
+									}
+								}
+								self.dish.start_cache_rebuild();
+							}
+						}
+					}
+					CollapsingHeader::new("Random fill").show(ui, |ui| {
+						self.random_fill_weights.resize(self.dish.types.len(), 0.);
+						for (weight, cell) in
+							self.random_fill_weights.iter_mut().zip(&self.dish.types)
+						{
+							ui.add(Slider::new(weight, 0.0..=1.0).text(&cell.name));
+						}
+						if ui.button("random fill").clicked() {
+							let cells: Vec<(Cell, f32)> = self
+								.random_fill_weights
+								.iter()
+								.enumerate()
+								.map(|(i, &weight)| (Cell(i as u16), weight))
+								.collect();
+							self.dish.randomize_world(&cells, &mut thread_rng());
+						}
+					});
+					ui.horizontal(|ui| {
+						let mode = &mut self.settings.brush_mode;
+						ui.radio_value(mode, BrushMode::Paint, "paint");
+						if ui
+							.radio(matches!(mode, BrushMode::FloodFill { .. }), "flood fill")
+							.clicked()
+						{
+							*mode = BrushMode::FloodFill { max_cells: 1024 };
+						}
+						if let BrushMode::FloodFill { max_cells } = mode {
+							ui.add(DragValue::new(max_cells).prefix("max: "));
+						}
+					});
+					ui.horizontal(|ui| {
+						let mode = &mut self.settings.brush_mode;
+						ui.radio_value(mode, BrushMode::Freeze, "freeze");
+						ui.radio_value(mode, BrushMode::Unfreeze, "unfreeze");
+						if ui.button("unfreeze all").clicked() {
+							self.dish.clear_frozen();
+						}
+						ui.radio_value(mode, BrushMode::Select, "select");
+					});
+					ui.horizontal(|ui| {
+						ui.label("color collision threshold:");
+						ui.add(DragValue::new(&mut self.color_threshold).clamp_range(0.0..=100.0));
+					});
+					ui.horizontal(|ui| {
+						if ui
+							.add_enabled(!collisions.is_empty(), Button::new("auto-distinguish"))
+							.clicked()
+						{
+							self.color_undo =
+								Some(self.dish.types.iter().map(|c| c.color).collect());
+							distinguish_colors(&mut self.dish.types, self.color_threshold);
+							self.dish.mark_changed(DishPart::Types);
+						}
+						if let Some(old_colors) = &self.color_undo {
+							if ui.button("undo recolor").clicked() {
+								for (cell, color) in self.dish.types.iter_mut().zip(old_colors) {
+									cell.color = *color;
+								}
+								self.dish.mark_changed(DishPart::Types);
+								self.color_undo = None;
+							}
+						}
+					});
+					if highlight == Some(Highlight::Cells) {
+						highlight_rect(ui, section_rect(ui, section_start));
 					}
 					ui.separator();
 
+					let section_start = ui.cursor().min;
 					ui.heading("Groups");
-					for group in &mut self.dish.groups {
+					let mut to_remove = None;
+					// (group, cells, out of bounds) to apply through the dish so the cache stays valid
+					let mut group_edit = None;
+					let mut group_renamed = false;
+					let group_users: Vec<Vec<usize>> = (0..self.dish.groups.len())
+						.map(|g| self.dish.rules_using_group(g))
+						.collect();
+					for (group_index, group) in self.dish.groups.iter_mut().enumerate() {
 						let (rect, _response) =
 							ui.allocate_exact_size(Vec2::splat(CSIZE), Sense::click());
 						draw_group(ui, rect, group, &self.dish.types);
 						ui.horizontal(|ui| {
+							ui.radio_value(&mut self.settings.brush, Brush::Group(group_index), "");
 							ui.menu_button("edit", |ui| {
-								ui.checkbox(&mut group.void, "void");
-								for (i, celldata) in self.dish.types.iter().enumerate() {
-									let mut included = group.cells.contains(&Cell(i as u16));
-									if ui.checkbox(&mut included, &celldata.name).changed() {
+								let mut cells = group.cells.clone();
+								let mut out_of_bounds = group.matches_out_of_bounds;
+								ui.checkbox(&mut out_of_bounds, "outside the world is a member")
+									.on_hover_text(
+										"positions past the edges match this group in addition to its cells",
+									);
+								ui.label(format!("{} members", cells.len()));
+								ui.horizontal(|ui| {
+									if ui.button("select all").clicked() {
+										cells = (0..self.dish.types.len())
+											.map(|i| Cell(i as u16))
+											.collect();
+									}
+									if ui.button("clear").clicked() {
+										cells.clear();
+									}
+								});
+								ui.horizontal_wrapped(|ui| {
+									for (i, celldata) in self.dish.types.iter().enumerate() {
+										let cell = Cell(i as u16);
+										let included = cells.contains(&cell);
+										let (rect, response) = ui
+											.allocate_exact_size(Vec2::splat(CSIZE), Sense::click());
+										render_cell(ui.painter(), rect.shrink(3.), celldata);
+										if included {
+											ui.painter().rect_stroke(rect, 0., OUTLINE);
+										}
+										if response.on_hover_text(&celldata.name).clicked() {
+											if included {
+												cells.retain(|&c| c != cell);
+											} else {
+												cells.push(cell);
+											}
+										}
+									}
+									let included = cells.contains(&Cell::BOUNDARY);
+									let (rect, response) =
+										ui.allocate_exact_size(Vec2::splat(CSIZE), Sense::click());
+									draw_boundary(ui, rect.shrink(3.));
+									if included {
+										ui.painter().rect_stroke(rect, 0., OUTLINE);
+									}
+									let response = response.on_hover_text(
+										"boundary: only matches outside the world, never placed",
+									);
+									if response.clicked() {
 										if included {
-											group.cells.push(Cell(i as u16));
+											cells.retain(|c| !c.is_boundary());
 										} else {
-											group.cells.retain(|c| c != &Cell(i as u16));
+											cells.push(Cell::BOUNDARY);
 										}
 									}
+								});
+								if cells != group.cells
+									|| out_of_bounds != group.matches_out_of_bounds
+								{
+									group_edit = Some((group_index, cells, out_of_bounds));
+								}
+								let users = &group_users[group_index];
+								if users.is_empty() {
+									ui.label("not used by any rule");
+								} else {
+									let names: Vec<&str> = users
+										.iter()
+										.map(|&i| self.dish.rules[i].name.as_str())
+										.collect();
+									ui.label(format!("used by: {}", names.join(", ")));
+								}
+								ui.separator();
+								if ui
+									.button("delete")
+									.on_hover_text(
+										"rules matching this group will match anything instead",
+									)
+									.clicked()
+								{
+									to_remove = Some(group_index);
+									ui.close_menu();
 								}
 							});
-							ui.text_edit_singleline(&mut group.name);
+							group_renamed |= ui.text_edit_singleline(&mut group.name).changed();
 						});
 					}
+					if group_renamed {
+						self.dish.mark_changed(DishPart::Groups);
+					}
+					if let Some((group, cells, out_of_bounds)) = group_edit {
+						self.dish.set_group_cells(group, cells);
+						self.dish.set_group_out_of_bounds(group, out_of_bounds);
+					}
+					if let Some(i) = to_remove {
+						self.dish.remove_group_force(i);
+						if !brush_after_group_removal(&mut self.settings.brush, i) {
+							self.settings.brush = Brush::Cell(Cell(0));
+						}
+						self.recent_brushes
+							.retain_mut(|brush| brush_after_group_removal(brush, i));
+						self.recent_cursor = 0;
+					}
 					if ui.button("add group").clicked() {
-						self.dish.groups.push(CellGroup::default());
+						self.dish.add_group(CellGroup::default());
+					}
+					if highlight == Some(Highlight::Groups) {
+						highlight_rect(ui, section_rect(ui, section_start));
+					}
+
+					CollapsingHeader::new("Analysis").show(ui, |ui| {
+						if ui.button("find overlapping rules").clicked() {
+							self.overlaps = analyze::find_overlaps(&self.dish);
+						}
+						if ui
+							.button("remove duplicate rules")
+							.on_hover_text("keeps the first of each set of rules with the same pattern")
+							.clicked()
+						{
+							for i in self.dish.remove_duplicate_rules().into_iter().rev() {
+								if i < self.rule_ui.len() {
+									self.rule_ui.remove(i);
+								}
+							}
+							self.overlaps.clear();
+						}
+						for overlap in &self.overlaps {
+							let rules = &self.dish.rules;
+							if overlap.rule_a >= rules.len() || overlap.rule_b >= rules.len() {
+								continue;
+							}
+							ui.horizontal_wrapped(|ui| {
+								if ui.link(&rules[overlap.rule_a].name).clicked() {
+									self.focus_rule = Some(overlap.rule_a);
+								}
+								ui.label(match &overlap.kind {
+									OverlapKind::Identical => "is identical to".to_owned(),
+									OverlapKind::Subsumed => "is shadowed by".to_owned(),
+									OverlapKind::ConflictingOutputs(cells) => {
+										format!("has {} conflicting outputs with", cells.len())
+									}
+								});
+								if ui.link(&rules[overlap.rule_b].name).clicked() {
+									self.focus_rule = Some(overlap.rule_b);
+								}
+							});
+						}
+					});
+
+					ui.heading("Rules");
+					let selected: Vec<usize> = self
+						.rule_ui
+						.iter()
+						.enumerate()
+						.filter(|(i, r)| r.selected && *i < self.dish.rules.len())
+						.map(|(i, _)| i)
+						.collect();
+					if !selected.is_empty() {
+						ui.horizontal(|ui| {
+							ui.label(format!("{} selected", selected.len()));
+							ui.menu_button("bulk edit", |ui| self.bulk_edit_menu(ui, &selected));
+							if ui.button("select none").clicked() {
+								for rule_ui in &mut self.rule_ui {
+									rule_ui.selected = false;
+								}
+							}
+						});
 					}
 
-					ui.heading("Rules");
-
+					let warnings = self.dish.validate();
+					let rule_names: Vec<String> =
+						self.dish.rules.iter().map(|r| r.name.clone()).collect();
+					let unsatisfiable = analyze::unsatisfiable_rules(&self.dish);
+					let name_errors = self.dish.validate_rule_names();
+					let no_ops = analyze::no_op_rules(&self.dish);
+					let activity = self.dish.rule_activity_report(ACTIVITY_WINDOW);
+					let step = self.dish.step_count();
+					let revisions = self.dish.revisions();
+					let coverage_stale = self.coverage_updated.is_none_or(|(time, seen)| {
+						seen != revisions && time.elapsed() > COVERAGE_INTERVAL
+					});
+					if coverage_stale || self.coverage.len() != self.dish.rules.len() {
+						self.coverage = self.dish.rule_coverage_fractions();
+						self.coverage_updated = Some((Instant::now(), revisions));
+					}
+					let world_area = (self.dish.world_width() * self.dish.world_height()) as f64;
 					let mut to_remove = None;
 					let mut to_clone = None;
-					let mut to_update = None;
-					for (i, rule) in self.dish.rules.iter_mut().enumerate() {
-						let changed = rule_editor(
+					let mut to_send = None;
+					let mut to_select = None;
+					let mut rules_edited = false;
+					let mut hovered = None;
+					self.rule_ui
+						.resize_with(self.dish.rules.len(), Default::default);
+					let types = self.dish.types.clone();
+					let groups = self.dish.groups.clone();
+					let mut rules = self.dish.iter_rules_mut();
+					while let Some((i, rule, changed)) = rules.next_rule() {
+						if self.focus_rule == Some(i) {
+							ui.scroll_to_cursor(Some(Align::TOP));
+							self.focus_rule = None;
+						}
+						let status = RuleStatus {
+							warnings: warnings
+								.iter()
+								.filter(|w| w.rule_index() == i)
+								.map(|w| warning_text(w, &rule_names))
+								.chain(
+									unsatisfiable
+										.iter()
+										.filter(|(rule, _)| *rule == i)
+										.map(|(_, reason)| unsatisfiable_text(reason)),
+								)
+								.collect(),
+							activity: activity[i].clone(),
+							step,
+							name_errors: name_errors
+								.iter()
+								.filter(|e| e.rule_index() == i)
+								.map(|e| name_error_text(e, &rule_names))
+								.collect(),
+							coverage: self.coverage[i],
+							world_area,
+							no_op: no_ops.contains(&i),
+						};
+						let section_start = ui.cursor().min;
+						*changed = rule_editor(
 							ui,
 							rule,
 							i,
-							&self.dish.types,
-							&self.dish.groups,
+							&types,
+							&groups,
+							&status,
 							&mut to_remove,
 							&mut to_clone,
+							&mut to_send,
+							&mut to_select,
+							&mut self.rule_clipboard,
+							&mut self.define_region,
+							&mut self.rule_ui[i],
+							&mut rules_edited,
 						);
-						if changed {
-							rule.generate_variants();
-							to_update = Some(i);
+						let section = section_rect(ui, section_start);
+						if self.rule_ui[i].selected {
+							ui.painter()
+								.rect_stroke(section, 2., ui.visuals().selection.stroke);
+						}
+						if ui.rect_contains_pointer(section) {
+							hovered = Some(i);
+						}
+						if matches!(highlight, Some(Highlight::Rule(name)) if name == rule.name) {
+							highlight_rect(ui, section_rect(ui, section_start));
 						}
 					}
-					if let Some(i) = to_update {
-						self.dish.update_cache_single_rule(i);
+					drop(rules);
+					if rules_edited {
+						self.dish.mark_changed(DishPart::Rules);
+					}
+					self.hovered_rule = hovered;
+					if let Some(i) = to_select {
+						let (toggle, range) =
+							ui.input(|input| (input.modifiers.command, input.modifiers.shift));
+						self.select_rule(i, toggle, range);
 					}
 					if let Some(i) = to_remove {
-						self.dish.rules.remove(i);
-						self.dish.rebuild_cache();
+						self.dish.remove_rule(i);
+						self.rule_ui.remove(i);
+					}
+					if let Some(i) = to_send {
+						self.send_rule(i);
 					}
 					if let Some(i) = to_clone {
 						let mut new_rule = self.dish.rules[i].clone();
@@ -204,92 +1804,889 @@ impl eframe::App for UScope {
 					}
 					ui.separator();
 					if ui.button("add rule").clicked() {
-						self.dish.rules.push(Rule::new());
+						let mut rule = Rule::new();
+						rule.name = rule.auto_name(&self.dish.types);
+						self.dish.rules.push(rule);
 						self.dish.cache_last_added_rule()
 					}
 				});
 			});
 		CentralPanel::default().show(ctx, |ui| {
+			self.quick_brush_row(ui);
 			let mut bounds = ui.available_rect_before_wrap();
 			bounds.min = bounds.min.floor();
 			bounds.max = bounds.max.floor();
+			let world_size = Vec2::new(
+				self.dish.world_width() as f32,
+				self.dish.world_height() as f32,
+			);
+			let cell_size = *self.cell_size.get_or_insert_with(|| {
+				(bounds.height() / (world_size.y + 2. * WORLD_MARGIN))
+					.floor()
+					.clamp(*CELL_SIZE_RANGE.start(), *CELL_SIZE_RANGE.end())
+			});
+			self.view_size = bounds.size() / cell_size;
+			let margin = Vec2::splat(WORLD_MARGIN);
+			self.view_offset = self
+				.view_offset
+				.clamp(-margin, (world_size - self.view_size + margin).max(-margin))
+				.floor();
+			let origin = bounds.min - self.view_offset * cell_size;
 			let painter = ui.painter_at(bounds);
-			paint_world(painter, &self.dish, self.show_grid);
+			// how far the hovered and selected rules can match outside the world
+			let reach = self
+				.rule_ui
+				.iter()
+				.enumerate()
+				.filter(|(i, r)| r.selected || self.hovered_rule == Some(*i))
+				.filter_map(|(i, _)| self.dish.rules.get(i))
+				.filter_map(|rule| rule.out_of_bounds_reach(&self.dish.groups))
+				.max();
+			let world_bounds = Rect::from_min_size(origin, world_size * cell_size);
+			paint_world_margin(&painter, world_bounds, cell_size, reach);
+			let grid = self.settings.show_grid
+				&& cell_size * ctx.pixels_per_point() >= MIN_GRID_CELL_PIXELS;
+			let labels = self.settings.show_labels && cell_size >= MIN_LABEL_CELL_SIZE;
+			paint_world(painter, origin, &self.dish, grid, labels, cell_size);
+			if self.tutorial.highlight() == Some(Highlight::World) {
+				highlight_rect(ui, world_bounds);
+			}
 
 			let rect = ui.allocate_rect(bounds, Sense::click_and_drag());
-			if let Some(pos) = rect.interact_pointer_pos() {
-				let p = ((pos - bounds.min) / GRID_SIZE).floor();
+			if rect.hovered() {
+				let zoom = ui.input(|i| i.zoom_delta());
+				if zoom != 1. {
+					self.cell_size = Some(
+						(cell_size * zoom).clamp(*CELL_SIZE_RANGE.start(), *CELL_SIZE_RANGE.end()),
+					);
+				}
+			}
+			// the margin above and left of the world would wrap to cell 0
+			let in_margin = |pos: Pos2| pos.x < origin.x || pos.y < origin.y;
+			if let Some(pos) = rect.interact_pointer_pos().filter(|&pos| !in_margin(pos)) {
+				let p = ((pos - origin) / cell_size).floor();
 				let x = p.x as usize;
 				let y = p.y as usize;
 				let pick = ui.input(|i| i.modifiers.shift);
-				if pick {
-					if let Some(clicked_cell) = self.dish.get_cell(x, y) {
-						self.brush = clicked_cell;
+				if let Some(debug) = &mut self.match_debug {
+					if rect.clicked() {
+						let cell = RegionMask { x, y, w: 1, h: 1 };
+						if let Some(&(rule, variant, at)) =
+							self.dish.matches_in_region(cell).first()
+						{
+							debug.trace = Some(self.dish.apply_match_traced(rule, variant, at));
+						}
+					}
+				} else if self.define_region.is_some() {
+					let start = self.region_drag.map_or((x, y), |(start, _)| start);
+					self.region_drag = Some((start, (x, y)));
+				} else if pick {
+					if let Some(clicked_cell) = self
+						.dish
+						.get_cell(x, y)
+						.filter(|cell| cell.id() < self.dish.types.len())
+					{
+						self.settings.brush = Brush::Cell(clicked_cell);
+					}
+				} else if self.settings.brush_mode == BrushMode::Select {
+					let x = x.min(self.dish.world_width() - 1);
+					let y = y.min(self.dish.world_height() - 1);
+					let pressed = ui.input(|i| i.pointer.any_pressed());
+					let start = match self.selection {
+						Some((start, _)) if !pressed => start,
+						_ => (x, y),
+					};
+					self.selection = Some((start, (x, y)));
+				} else if let BrushMode::Freeze | BrushMode::Unfreeze = self.settings.brush_mode {
+					let frozen = self.settings.brush_mode == BrushMode::Freeze;
+					self.dish.set_frozen(x, y, frozen);
+				} else if let BrushMode::FloodFill { max_cells } = self.settings.brush_mode {
+					if rect.clicked() {
+						match self.settings.brush {
+							Brush::Cell(cell) => self.dish.paint_fill_bucket(x, y, cell, max_cells),
+							Brush::Group(group) => {
+								self.dish.paint_fill_bucket_group(x, y, group, max_cells)
+							}
+						};
 					}
 				} else {
 					let old = self.dish.get_cell(x, y);
-					if Some(self.brush) != old {
-						self.dish.set_cell(x, y, self.brush);
+					let new = match self.settings.brush {
+						Brush::Cell(cell) => Some(cell),
+						Brush::Group(group) => self.dish.random_from_group(group),
+					};
+					if let Some(new) = new.filter(|&new| Some(new) != old) {
+						self.dish.set_cell(x, y, new);
 						self.dish.update_cache(x as isize, y as isize, 1, 1);
 					}
 				}
 			}
+			if let Some(search) = &self.oscillators {
+				for &(x, y, _) in &search.found {
+					let cell = RegionMask { x, y, w: 1, h: 1 };
+					ui.painter().rect_stroke(
+						world_rect(origin, cell, cell_size),
+						0.,
+						(1., Color32::from_rgb(255, 0, 255)),
+					);
+				}
+			}
+			if self.match_debug.is_some() {
+				let view = RegionMask {
+					x: self.view_offset.x as usize,
+					y: self.view_offset.y as usize,
+					w: self.view_size.x.ceil() as usize,
+					h: self.view_size.y.ceil() as usize,
+				};
+				let matches = self.dish.matches_in_region(view);
+				for &(rule, variant, at) in matches.iter().take(MAX_MATCH_OUTLINES) {
+					let (x, y, w, h) = self.dish.match_footprint(rule, variant, at);
+					let footprint = Rect::from_min_size(
+						origin + Vec2::new(x as f32, y as f32) * cell_size,
+						Vec2::new(w as f32, h as f32) * cell_size,
+					);
+					let stroke = if rect.hover_pos().is_some_and(|pos| footprint.contains(pos)) {
+						(2., Color32::from_rgb(255, 165, 0))
+					} else {
+						(1., Color32::from_rgba_unmultiplied(255, 165, 0, 80))
+					};
+					ui.painter().rect_stroke(footprint, 0., stroke);
+				}
+			}
+			if let Some((start, end)) = self.selection {
+				ui.painter().rect_stroke(
+					world_rect(origin, rect_between(start, end), cell_size),
+					0.,
+					(1., Color32::YELLOW),
+				);
+			}
+			if let (Some(rule_index), Some((start, end))) = (self.define_region, self.region_drag) {
+				let mask = rect_between(start, end);
+				ui.painter().rect_stroke(
+					world_rect(origin, mask, cell_size),
+					0.,
+					(2., Color32::LIGHT_BLUE),
+				);
+				if rect.drag_stopped() || rect.clicked() {
+					if rule_index < self.dish.rules.len() {
+						self.dish.set_rule_region(rule_index, Some(mask));
+					}
+					self.define_region = None;
+					self.region_drag = None;
+				}
+			}
 		});
+		self.tutorial_window(ctx);
+		self.resize_world_window(ctx);
+		self.selection_window(ctx);
+		self.oscillator_window(ctx);
+		self.received_rule_window(ctx);
+		self.profile_window(ctx);
+		self.match_debug_window(ctx);
+		self.unsaved_changes_window(ctx);
+		self.remember_brush();
+	}
+}
+
+/// the rect spanned by two corner cells, both included
+fn rect_between(a: (usize, usize), b: (usize, usize)) -> RegionMask {
+	RegionMask {
+		x: a.0.min(b.0),
+		y: a.1.min(b.1),
+		w: a.0.abs_diff(b.0) + 1,
+		h: a.1.abs_diff(b.1) + 1,
 	}
 }
 
-const GRID_SIZE: f32 = 16.;
-fn paint_world(painter: Painter, world: &Dish, grid: bool) {
+/// screen rect of a rect of cells in the world drawn with its corner at `origin`
+fn world_rect(origin: Pos2, rect: RegionMask, cell_size: f32) -> Rect {
+	Rect::from_min_size(
+		origin + Vec2::new(rect.x as f32, rect.y as f32) * cell_size,
+		Vec2::new(rect.w as f32, rect.h as f32) * cell_size,
+	)
+}
+
+/// draws the new world outline with the old contents on top, marking the parts that get cut off in red
+fn resize_preview(ui: &mut Ui, old: (usize, usize), new: (usize, usize), anchor: Anchor) {
+	let (_, rect) = ui.allocate_space(Vec2::splat(96.));
+	let (dx, dy) = anchor.offset(old, new);
+	let min_x = dx.min(0) as f32;
+	let min_y = dy.min(0) as f32;
+	let max_x = (dx + old.0 as isize).max(new.0 as isize) as f32;
+	let max_y = (dy + old.1 as isize).max(new.1 as isize) as f32;
+	let scale = rect.width() / (max_x - min_x).max(max_y - min_y);
+	let to_screen = |x: f32, y: f32| rect.min + Vec2::new(x - min_x, y - min_y) * scale;
+
+	let painter = ui.painter_at(rect);
+	let new_rect = Rect::from_min_max(to_screen(0., 0.), to_screen(new.0 as f32, new.1 as f32));
+	let old_rect = Rect::from_min_max(
+		to_screen(dx as f32, dy as f32),
+		to_screen(dx as f32 + old.0 as f32, dy as f32 + old.1 as f32),
+	);
+	painter.rect_filled(old_rect, 0., Color32::from_rgb(120, 30, 30));
+	painter.rect_filled(old_rect.intersect(new_rect), 0., Color32::DARK_GRAY);
+	painter.rect_stroke(new_rect, 0., (1., Color32::WHITE));
+}
+
+/// area covered by widgets added since `start`
+fn section_rect(ui: &Ui, start: Pos2) -> Rect {
+	Rect::from_min_max(start, Pos2::new(ui.max_rect().right(), ui.cursor().min.y))
+}
+
+fn highlight_rect(ui: &Ui, rect: Rect) {
+	ui.ctx()
+		.layer_painter(LayerId::new(
+			Order::Foreground,
+			Id::new("tutorial highlight"),
+		))
+		.rect_stroke(rect.expand(2.), 4., (2., Color32::GOLD));
+}
+
+/// limits of the cell size setting, in points
+const CELL_SIZE_RANGE: RangeInclusive<f32> = 1.0..=64.0;
+/// the grid is hidden on cells smaller than this, where it would only cause moiré
+const MIN_GRID_CELL_PIXELS: f32 = 3.;
+/// cells of hatched space drawn around the world, which the view can scroll into
+const WORLD_MARGIN: f32 = 3.;
+const HATCH_SPACING: f32 = 6.;
+/// cell size in points where the names of `Settings::show_labels` start being drawn
+const MIN_LABEL_CELL_SIZE: f32 = 32.;
+const TITLE: &str = "µscope";
+const CELL_SIZE_KEY: &str = "cell_size";
+const KEEP_BACKUP_KEY: &str = "keep_backup";
+const PARAM_SETS_KEY: &str = "param_sets";
+/// width or height of the minimap, whichever is larger
+const MINIMAP_SIZE: f32 = 160.;
+const MINIMAP_INTERVAL: Duration = Duration::from_millis(250);
+/// step limit of "run to stable"
+const FIXPOINT_MAX_STEPS: u64 = 100_000;
+/// steps `Dish::convergence_speed` is measured over in the statistics panel
+const CONVERGENCE_WINDOW: usize = 100;
+/// how often rule coverage is rescanned, it ignores the match cache so it is slow on big worlds
+const COVERAGE_INTERVAL: Duration = Duration::from_millis(500);
+/// default number of frames in the performance history
+const HISTORY_LEN: usize = 60;
+const MAX_HISTORY_LEN: usize = 10_000;
+/// the match debugger outlines at most this many matches, the world is unreadable well before that
+const MAX_MATCH_OUTLINES: usize = 4096;
+
+/// draw the world with its top-left corner at `origin`, skipping cells outside the painter's clip rect
+fn paint_world(
+	painter: Painter,
+	origin: Pos2,
+	world: &Dish,
+	grid: bool,
+	labels: bool,
+	cell_size: f32,
+) {
 	let cells = &world.types;
-	let bounds = painter.clip_rect();
-	for x in 0..CHUNK_SIZE {
-		for y in 0..CHUNK_SIZE {
+	let visible = painter.clip_rect();
+	let first = ((visible.min - origin) / cell_size).floor().max(Vec2::ZERO);
+	let last = ((visible.max - origin) / cell_size).ceil().max(Vec2::ZERO);
+	let xs = first.x as usize..(last.x as usize).min(world.world_width());
+	let ys = first.y as usize..(last.y as usize).min(world.world_height());
+	for x in xs {
+		for y in ys.clone() {
 			let cell = &world.get_cell(x, y).unwrap();
-			let corner = bounds.min + (Vec2::from((x as f32, y as f32)) * GRID_SIZE);
-			let rect = Rect::from_min_size(corner, Vec2::splat(GRID_SIZE));
-			if cell.id() >= cells.len() {
-				continue;
+			let corner = origin + (Vec2::from((x as f32, y as f32)) * cell_size);
+			let rect = Rect::from_min_size(corner, Vec2::splat(cell_size));
+			match cells.get(cell.id()) {
+				Some(data) => {
+					render_cell(&painter, rect, data);
+					if labels {
+						let [r, g, b] = data.contrasting_text_color();
+						painter.text(
+							rect.center(),
+							Align2::CENTER_CENTER,
+							&data.name,
+							FontId::proportional(cell_size / 4.),
+							Color32::from_rgb(r, g, b),
+						);
+					}
+				}
+				None => draw_undefined(&painter, rect),
+			}
+			if world.is_frozen(x, y) {
+				painter.line_segment(
+					[rect.left_bottom(), rect.right_top()],
+					(1., Color32::from_white_alpha(60)),
+				);
 			}
-			let color = cells[cell.id()].color;
-			let color = Color32::from_rgb(color[0], color[1], color[2]);
 			if grid {
-				painter.rect(rect, 0., color, (1., Color32::GRAY));
-			} else {
-				painter.rect_filled(rect, 0., color);
+				painter.rect_stroke(rect, 0., (1., Color32::GRAY));
+			}
+		}
+	}
+}
+
+/// Hatch the margin around the world and outline its edge. With `reach`,
+/// tint that many cells of the margin where rules matching out of bounds can apply.
+fn paint_world_margin(painter: &Painter, world_rect: Rect, cell_size: f32, reach: Option<usize>) {
+	let outer = world_rect.expand(WORLD_MARGIN * cell_size);
+	for strip in ring_strips(world_rect, outer) {
+		let painter = painter.with_clip_rect(strip);
+		// lines of constant x + y, lined up across the strips
+		let base = outer.min.x + outer.min.y;
+		let first = ((strip.min.x + strip.min.y - base) / HATCH_SPACING).floor();
+		let mut c = base + first * HATCH_SPACING;
+		while c <= strip.max.x + strip.max.y {
+			painter.line_segment(
+				[
+					Pos2::new(c - strip.min.y, strip.min.y),
+					Pos2::new(c - strip.max.y, strip.max.y),
+				],
+				(1., Color32::from_gray(60)),
+			);
+			c += HATCH_SPACING;
+		}
+	}
+	if let Some(reach) = reach {
+		let reach = (reach as f32).min(WORLD_MARGIN);
+		let tinted = world_rect.expand(reach * cell_size);
+		for strip in ring_strips(world_rect, tinted) {
+			painter.rect_filled(strip, 0., Color32::from_rgba_unmultiplied(60, 140, 255, 60));
+		}
+	}
+	painter.rect_stroke(world_rect, 0., (1., Color32::GRAY));
+}
+
+/// the top, bottom, left and right parts of `outer` that are outside `inner`
+fn ring_strips(inner: Rect, outer: Rect) -> [Rect; 4] {
+	[
+		Rect::from_min_max(outer.min, Pos2::new(outer.max.x, inner.min.y)),
+		Rect::from_min_max(Pos2::new(outer.min.x, inner.max.y), outer.max),
+		Rect::from_min_max(
+			Pos2::new(outer.min.x, inner.min.y),
+			Pos2::new(inner.min.x, inner.max.y),
+		),
+		Rect::from_min_max(
+			Pos2::new(inner.max.x, inner.min.y),
+			Pos2::new(outer.max.x, inner.max.y),
+		),
+	]
+}
+
+/// nudge colliding colors apart in value and saturation, keeping the hue close to the original
+fn distinguish_colors(types: &mut [CellData], threshold: f32) {
+	const STEP: f32 = 0.08;
+	for _ in 0..32 {
+		let collisions = analyze::color_collisions(types, threshold);
+		if collisions.is_empty() {
+			break;
+		}
+		for (a, b) in collisions {
+			let fixed = Hsva::from_srgb(types[a].color);
+			let mut moved = Hsva::from_srgb(types[b].color);
+			let mut dv = if moved.v >= fixed.v { STEP } else { -STEP };
+			if !(0.0..=1.0).contains(&(moved.v + dv)) {
+				dv = -dv;
+			}
+			moved.v = (moved.v + dv).clamp(0., 1.);
+			moved.h = (moved.h + 0.01).fract();
+			types[b].color = moved.to_srgb();
+		}
+	}
+}
+
+/// renumber a group brush after group `removed` is deleted, false if it was that group
+fn brush_after_group_removal(brush: &mut Brush, removed: usize) -> bool {
+	if let Brush::Group(g) = brush {
+		if *g == removed {
+			return false;
+		} else if *g > removed {
+			*g -= 1;
+		}
+	}
+	true
+}
+
+/// a magenta and black checker for a world cell with no type, meant to stand out
+fn draw_undefined(painter: &Painter, rect: Rect) {
+	painter.rect_filled(rect, 0., UNDEFINED_COLOR);
+	let half = rect.size() / 2.;
+	painter.rect_filled(Rect::from_min_size(rect.min, half), 0., Color32::BLACK);
+	painter.rect_filled(
+		Rect::from_min_size(rect.min + half, half),
+		0.,
+		Color32::BLACK,
+	);
+}
+
+fn cell_color(cell: &CellData) -> Color32 {
+	let [r, g, b] = cell.color;
+	Color32::from_rgb(r, g, b)
+}
+
+/// Draw the pattern of a cell type over `rect`, which should already be filled with its color.
+/// Skipped when too small to make out, like `Dish::render_world_rgb`.
+fn paint_cell_pattern(painter: &Painter, rect: Rect, cell: &CellData) {
+	let size = rect.width();
+	if cell.pattern == CellPattern::None || size < MIN_PATTERN_SIZE {
+		return;
+	}
+	let painter = painter.with_clip_rect(rect);
+	let [r, g, b] = cell.pattern_color();
+	let stroke = Stroke::new((size / 8.).max(1.), Color32::from_rgb(r, g, b));
+	let at = |x: f32, y: f32| rect.min + Vec2::new(x, y) * size;
+	match cell.pattern {
+		CellPattern::None => (),
+		CellPattern::Stripes => {
+			for y in [0.25, 0.75] {
+				let band = Rect::from_min_max(at(0., y), at(1., y + 0.25));
+				painter.rect_filled(band, 0., stroke.color);
+			}
+		}
+		CellPattern::Diagonal => {
+			for c in [0.5, 1., 1.5] {
+				painter.line_segment([at(c, 0.), at(0., c)], stroke);
+			}
+		}
+		CellPattern::Dots => {
+			for (x, y) in [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)] {
+				painter.circle_filled(at(x, y), size / 8., stroke.color);
 			}
 		}
+		CellPattern::Cross => {
+			painter.line_segment([at(0., 0.), at(1., 1.)], stroke);
+			painter.line_segment([at(1., 0.), at(0., 1.)], stroke);
+		}
+	}
+}
+
+fn render_cell(painter: &Painter, rect: Rect, cell: &CellData) {
+	let color = cell_color(cell);
+	// the square inside the shape the pattern is drawn in
+	let pattern_rect = match cell.shape {
+		CellShape::Square => rect,
+		CellShape::Circle => rect.shrink(rect.width() * (1. - FRAC_1_SQRT_2) / 2.),
+		CellShape::Diamond => rect.shrink(rect.width() / 4.),
+		CellShape::Cross => Rect::from_center_size(rect.center(), rect.size() / 3.),
+	};
+	match cell.shape {
+		CellShape::Square => {
+			painter.rect_filled(rect, 0., color);
+		}
+		CellShape::Circle => {
+			painter.circle_filled(rect.center(), rect.width() / 2., color);
+		}
+		CellShape::Diamond => {
+			let points = vec![
+				rect.center_top(),
+				rect.right_center(),
+				rect.center_bottom(),
+				rect.left_center(),
+			];
+			painter.add(Shape::convex_polygon(points, color, Stroke::NONE));
+		}
+		CellShape::Cross => {
+			let thickness = rect.width() / 3.;
+			let horizontal =
+				Rect::from_center_size(rect.center(), Vec2::new(rect.width(), thickness));
+			let vertical =
+				Rect::from_center_size(rect.center(), Vec2::new(thickness, rect.height()));
+			painter.rect_filled(horizontal, 0., color);
+			painter.rect_filled(vertical, 0., color);
+		}
+	}
+	paint_cell_pattern(painter, pattern_rect, cell);
+}
+
+fn sparkline(ui: &mut Ui, values: &[f64]) {
+	let (_, rect) = ui.allocate_space(Vec2::new(ui.available_width(), 32.));
+	let painter = ui.painter_at(rect);
+	painter.rect_filled(rect, 0., Color32::from_gray(20));
+	let max = values.iter().copied().fold(f64::EPSILON, f64::max);
+	let step = rect.width() / values.len().max(2) as f32;
+	let points: Vec<Pos2> = values
+		.iter()
+		.enumerate()
+		.map(|(i, v)| {
+			let y = rect.max.y - (v / max) as f32 * rect.height();
+			Pos2::new(rect.min.x + i as f32 * step, y)
+		})
+		.collect();
+	for pair in points.windows(2) {
+		painter.line_segment([pair[0], pair[1]], (1., Color32::LIGHT_GREEN));
+	}
+}
+
+/// one labelled horizontal bar per rule
+fn bar_chart(ui: &mut Ui, rules: &[Rule], values: &[usize]) {
+	let max = values.iter().copied().max().unwrap_or(0).max(1);
+	for (rule, &value) in rules.iter().zip(values) {
+		ui.horizontal(|ui| {
+			let (_, rect) = ui.allocate_space(Vec2::new(80., 12.));
+			let painter = ui.painter_at(rect);
+			painter.rect_filled(rect, 0., Color32::from_gray(20));
+			let mut bar = rect;
+			bar.set_width(rect.width() * value as f32 / max as f32);
+			painter.rect_filled(bar, 0., Color32::LIGHT_BLUE);
+			ui.label(format!("{} ({value})", rule.name));
+		});
 	}
 }
 
 const CSIZE: f32 = 24.;
+/// smallest cell size in points cell patterns are drawn at
+const MIN_PATTERN_SIZE: f32 = 4.;
 const RESIZE_BUTTON_WIDTH: f32 = 8.;
 
 const OUTLINE: (f32, Color32) = (2., Color32::GRAY);
+/// world cells whose id has no type
+const UNDEFINED_COLOR: Color32 = Color32::from_rgb(255, 0, 255);
+
+/// number of recent simulation steps used for the rule activity badges
+const ACTIVITY_WINDOW: u64 = 10_000;
+
+/// number of brushes kept for the quick brush row
+const RECENT_BRUSHES: usize = 8;
+
+/// time spent on an unfinished cache rebuild each frame
+const REBUILD_FRAME_BUDGET: Duration = Duration::from_millis(10);
+/// rebuilds taking longer than this show a progress bar
+const REBUILD_PROGRESS_DELAY: Duration = Duration::from_millis(50);
+
+/// how long typing in a rule's DSL section has to pause before the text is parsed
+const DSL_PARSE_DELAY: Duration = Duration::from_millis(100);
+
+struct RuleStatus {
+	warnings: Vec<String>,
+	activity: RuleActivity,
+	step: u64,
+	name_errors: Vec<String>,
+	/// fraction of positions the rule matches at
+	coverage: f64,
+	world_area: f64,
+	/// the rule can never change a cell, see `analyze::no_op_rules`
+	no_op: bool,
+}
+
+/// sending rules to and receiving them from other instances
+#[derive(Debug)]
+struct Collab {
+	/// listening when Some
+	listener: Option<net::Listener>,
+	port: u16,
+	/// host:port rules are sent to
+	peer: String,
+	/// result of the last send or receive
+	status: Option<String>,
+	/// waiting to be accepted or rejected
+	received: Option<Rule>,
+	/// sends still in progress, with what is being sent where for the status
+	sending: Vec<(String, net::Sending)>,
+}
+
+impl Default for Collab {
+	fn default() -> Self {
+		Self {
+			listener: None,
+			port: net::DEFAULT_PORT,
+			peer: format!("127.0.0.1:{}", net::DEFAULT_PORT),
+			status: None,
+			received: None,
+			sending: Vec::new(),
+		}
+	}
+}
+
+/// settings and results of the "Detect oscillators" window
+#[derive(Debug)]
+struct OscillatorSearch {
+	period: u64,
+	steps: u64,
+	seed: u64,
+	/// runs on a copy of the dish
+	running: Option<JoinHandle<Vec<(usize, usize, u64)>>>,
+	/// (x, y, period) of the oscillating cells, highlighted in the world
+	found: Vec<(usize, usize, u64)>,
+}
+
+impl Default for OscillatorSearch {
+	fn default() -> Self {
+		Self {
+			period: 2,
+			steps: 200,
+			seed: 0,
+			running: None,
+			found: Vec::new(),
+		}
+	}
+}
+
+/// a change the bulk edit menu applies to each selected rule
+type RuleEdit = Box<dyn Fn(&mut Rule)>;
+/// one of the bool settings of a rule
+type RuleFlag = fn(&mut Rule) -> &mut bool;
+
+/// editor state of a rule that isn't saved with it
+#[derive(Debug, Default)]
+struct RuleUi {
+	/// text of the DSL section, reset from the rule whenever the section is expanded
+	dsl_text: String,
+	dsl_open: bool,
+	/// when the text was last edited, None once it has been parsed
+	dsl_edited: Option<Instant>,
+	dsl_parsed: Option<Result<Rule, ParseError>>,
+	/// part of the selection that bulk edits apply to
+	selected: bool,
+}
+
+fn unsatisfiable_text(reason: &Reason) -> String {
+	match reason {
+		Reason::EmptyGroup { x, y, group } => {
+			format!("never matches: group {group} at {x},{y} has no members")
+		}
+		Reason::MissingGroup { x, y, group } => {
+			format!("never matches: group {group} at {x},{y} doesn't exist")
+		}
+		Reason::MissingCell { x, y, cell } => {
+			format!("never matches: cell {} at {x},{y} doesn't exist", cell.id())
+		}
+	}
+}
+
+fn param_mismatch_text(mismatch: &ParamMismatch, dish: &Dish) -> String {
+	let mut parts = Vec::new();
+	if !mismatch.missing_in_dish.is_empty() {
+		parts.push(format!(
+			"no rule for: {}",
+			mismatch.missing_in_dish.join(", ")
+		));
+	}
+	if !mismatch.missing_in_set.is_empty() {
+		let names: Vec<&str> = mismatch
+			.missing_in_set
+			.iter()
+			.map(|&i| dish.rules[i].name.as_str())
+			.collect();
+		parts.push(format!("left unchanged: {}", names.join(", ")));
+	}
+	parts.join("\n")
+}
+
+fn warning_text(warning: &ValidationWarning, rule_names: &[String]) -> String {
+	match warning {
+		ValidationWarning::TrivialRule { .. } => "rule can never change anything".into(),
+		ValidationWarning::OutOfBoundsCopy { .. } => {
+			"a copy output refers to a cell outside the rule".into()
+		}
+		ValidationWarning::NeverWrites { .. } => {
+			"no output can ever write a cell, check for empty groups or copies from outside".into()
+		}
+		ValidationWarning::DuplicateRule { original, .. } => {
+			let name = rule_names.get(*original).map_or("?", |n| n.as_str());
+			format!("has the same pattern as '{name}'")
+		}
+	}
+}
+
+fn name_error_text(error: &RuleNameError, rule_names: &[String]) -> String {
+	match error {
+		RuleNameError::EmptyName { .. } => "the name is empty".into(),
+		RuleNameError::TooLong { len, .. } => {
+			format!("the name is {len} characters, at most {MAX_RULE_NAME_LEN} are allowed")
+		}
+		RuleNameError::DuplicateName { rule_a, .. } => {
+			let name = rule_names.get(*rule_a).map_or("?", |n| n.as_str());
+			format!("rule {rule_a} is also named '{name}'")
+		}
+		RuleNameError::InvalidChar { ch, .. } => format!("the name contains {ch:?}"),
+	}
+}
+
+fn schedule_editor(ui: &mut Ui, schedule: &mut Option<Schedule>, index: usize) -> bool {
+	let mut changed = false;
+	let selected = match schedule {
+		None => "always",
+		Some(Schedule::After(_)) => "after",
+		Some(Schedule::Until(_)) => "until",
+		Some(Schedule::Between(..)) => "between",
+		Some(Schedule::Periodic { .. }) => "periodic",
+	};
+	ComboBox::new(("schedule", index), "active")
+		.selected_text(selected)
+		.show_ui(ui, |ui| {
+			for (name, option) in [
+				("always", None),
+				("after", Some(Schedule::After(1000))),
+				("until", Some(Schedule::Until(1000))),
+				("between", Some(Schedule::Between(1000, 2000))),
+				(
+					"periodic",
+					Some(Schedule::Periodic {
+						period: 1000,
+						duty: 500,
+					}),
+				),
+			] {
+				if ui.selectable_label(selected == name, name).clicked() && selected != name {
+					*schedule = option;
+					changed = true;
+				}
+			}
+		});
+	let mut drag = |ui: &mut Ui, value: &mut u64, prefix: &str| {
+		if ui.add(DragValue::new(value).prefix(prefix)).changed() {
+			changed = true;
+		}
+	};
+	match schedule {
+		None => (),
+		Some(Schedule::After(start)) => drag(ui, start, "step "),
+		Some(Schedule::Until(end)) => drag(ui, end, "step "),
+		Some(Schedule::Between(start, end)) => {
+			drag(ui, start, "from ");
+			drag(ui, end, "to ");
+		}
+		Some(Schedule::Periodic { period, duty }) => {
+			drag(ui, duty, "on ");
+			drag(ui, period, "of every ");
+		}
+	}
+	changed
+}
+
+#[allow(clippy::too_many_arguments)]
 fn rule_editor(
 	ui: &mut Ui,
 	rule: &mut Rule,
 	index: usize,
 	cells: &[CellData],
 	groups: &[CellGroup],
+	status: &RuleStatus,
 	to_remove: &mut Option<usize>,
 	to_clone: &mut Option<usize>,
+	to_send: &mut Option<usize>,
+	to_select: &mut Option<usize>,
+	clipboard: &mut Option<SubRule>,
+	define_region: &mut Option<usize>,
+	rule_ui: &mut RuleUi,
+	edited: &mut bool,
 ) -> bool {
 	let mut changed = false;
 	let id = ui.make_persistent_id(format!("rule {index}"));
 	CollapsingState::load_with_default_open(ui.ctx(), id, true)
 		.show_header(ui, |ui| {
-			if ui.checkbox(&mut rule.enabled, &rule.name).changed() {
+			if ui
+				.selectable_label(rule_ui.selected, format!("{index}"))
+				.on_hover_text("click to select, ctrl-click to add, shift-click for a range")
+				.clicked()
+			{
+				*to_select = Some(index);
+			}
+			let checkbox = ui.checkbox(&mut rule.enabled, &rule.name);
+			let hover = match rule.disable_reason() {
+				Some(reason) if rule.description.is_empty() => format!("disabled: {reason}"),
+				Some(reason) => format!("{}\n\ndisabled: {reason}", rule.description),
+				None => rule.description.clone(),
+			};
+			let checkbox = if hover.is_empty() {
+				checkbox
+			} else {
+				checkbox.on_hover_text(hover)
+			};
+			if checkbox.changed() {
+				rule.enabled_reason = None;
 				changed = true;
 			}
+			if let Some(reason) = rule.disable_reason() {
+				ui.label(RichText::new(reason).italics().color(Color32::GRAY))
+					.on_hover_text("reason this rule was disabled");
+			}
+			if rule.enabled && !rule.is_active_at(status.step) {
+				ui.label(
+					RichText::new("scheduled off")
+						.italics()
+						.color(Color32::GRAY),
+				)
+				.on_hover_text("enabled, but outside its schedule at the current step");
+			}
+			let activity = &status.activity;
+			let (badge_color, badge_text) =
+				if activity.fired > 0 && activity.fired_empty == activity.fired {
+					(
+						Color32::RED,
+						format!("fired {} times but never wrote a cell", activity.fired),
+					)
+				} else if activity.fired > 0 {
+					let mut text = format!(
+						"fired {} times, lost {} times",
+						activity.fired, activity.lost_lottery
+					);
+					if activity.fired_empty > 0 {
+						text += &format!(", wrote nothing {} times", activity.fired_empty);
+					}
+					(Color32::GREEN, text)
+				} else if !status.activity.never_matched {
+					(Color32::YELLOW, "matched but never fired".to_owned())
+				} else {
+					(Color32::GRAY, "never matched".to_owned())
+				};
+			ui.colored_label(badge_color, "●").on_hover_text(badge_text);
+			if status.no_op {
+				let mut text = "no-op: every output keeps the cell it matched".to_owned();
+				if activity.fired > 0 && activity.fired_unchanged == activity.fired {
+					text += &format!(", fired {} times without changing a cell", activity.fired);
+				}
+				ui.colored_label(Color32::YELLOW, "=").on_hover_text(text);
+			}
+			for warning in &status.warnings {
+				ui.colored_label(Color32::YELLOW, "⚠")
+					.on_hover_text(warning);
+			}
 			if ui.button("delete").clicked() {
 				*to_remove = Some(index);
 			}
 			if ui.button("copy").clicked() {
 				*to_clone = Some(index);
 			}
+			if ui
+				.button("send")
+				.on_hover_text("send rule to the peer set under Collaboration")
+				.clicked()
+			{
+				*to_send = Some(index);
+			}
+			for (label, hover, transform) in [
+				("↔", "flip pattern horizontally", Transform::FlipX),
+				("↕", "flip pattern vertically", Transform::FlipY),
+				("⟳", "rotate pattern clockwise", Transform::Rotate90),
+			] {
+				if ui.small_button(label).on_hover_text(hover).clicked() {
+					rule.transform_base(transform);
+					changed = true;
+				}
+			}
+			if ui
+				.small_button("×2")
+				.on_hover_text("scale up ×2, each cell becomes a 2×2 block")
+				.clicked()
+			{
+				*rule = rule.scale(2);
+				changed = true;
+			}
 		})
 		.body(|ui| {
-			ui.text_edit_singleline(&mut rule.name);
+			*edited |= ui.text_edit_singleline(&mut rule.name).changed();
+			for error in &status.name_errors {
+				ui.colored_label(Color32::RED, error);
+			}
+			*edited |= ui
+				.add(
+					TextEdit::multiline(&mut rule.description)
+						.hint_text("description")
+						.desired_rows(2),
+				)
+				.changed();
+			ui.add(ProgressBar::new(status.coverage as f32).text(format!(
+				"matches at {:.1}% of positions",
+				status.coverage * 100.
+			)))
+			.on_hover_text(format!(
+				"about {:.1} applications per step",
+				status.coverage * status.world_area
+			));
 			ui.horizontal(|ui| {
 				if ui.checkbox(&mut rule.flip_x, "flip X").changed() {
 					changed = true;
@@ -303,12 +2700,99 @@ fn rule_editor(
 			});
 			ui.horizontal(|ui| {
 				ui.label("fail rate:");
-				ui.add(DragValue::new(&mut rule.failrate));
+				*edited |= ui.add(DragValue::new(&mut rule.failrate)).changed();
+				*edited |= ui
+					.checkbox(&mut rule.ignore_temperature, "ignore temperature")
+					.changed();
+				*edited |= ui
+					.add(DragValue::new(&mut rule.phase).prefix("phase: "))
+					.changed();
+			});
+			ui.horizontal(|ui| {
+				if schedule_editor(ui, &mut rule.schedule, index) {
+					changed = true;
+				}
+			});
+			ui.horizontal(|ui| {
+				let defining = *define_region == Some(index);
+				let mut restricted = rule.region_mask.is_some() || defining;
+				if ui.checkbox(&mut restricted, "restrict to region").changed() {
+					if restricted {
+						*define_region = Some(index);
+					} else {
+						rule.region_mask = None;
+						*define_region = None;
+						changed = true;
+					}
+				}
+				if defining {
+					ui.label("drag on the world to select");
+				} else if let Some(mask) = rule.region_mask {
+					ui.label(format!("{},{} {}x{}", mask.x, mask.y, mask.w, mask.h));
+					if ui.button("redefine").clicked() {
+						*define_region = Some(index);
+					}
+				}
 				ui.label(format!("variants: {}", rule.variant_count()));
 				if ui.button("debug").clicked() {
 					rule.dbg_variants();
 				}
 			});
+			if rule.variant_count() > 1 {
+				CollapsingHeader::new("variant priority")
+					.id_source(("variant priority", index))
+					.show(ui, |ui| {
+						let mut order = rule.variant_priority().to_vec();
+						let mut swap = None;
+						for (pos, variant) in order.iter().enumerate() {
+							ui.horizontal(|ui| {
+								ui.label(format!("{}. variant {variant}", pos + 1));
+								if ui.add_enabled(pos > 0, Button::new("⏶")).clicked() {
+									swap = Some(pos - 1);
+								}
+								if ui
+									.add_enabled(pos + 1 < order.len(), Button::new("⏷"))
+									.clicked()
+								{
+									swap = Some(pos);
+								}
+							});
+						}
+						if let Some(pos) = swap {
+							order.swap(pos, pos + 1);
+							rule.set_variant_priority(order).unwrap();
+							*edited = true;
+						}
+					});
+			}
+			ui.horizontal(|ui| {
+				if ui
+					.button("from→to")
+					.on_hover_text(
+						"set each output to its input cell, or to no change for groups and Any",
+					)
+					.clicked()
+				{
+					rule.copy_from_to_to();
+					changed = true;
+				}
+				if ui.button("clear to").clicked() {
+					rule.clear_to();
+					changed = true;
+				}
+				if ui.button("copy pattern").clicked() {
+					*clipboard = Some(rule.base().clone());
+				}
+				if ui
+					.add_enabled(clipboard.is_some(), Button::new("paste pattern"))
+					.clicked()
+				{
+					if let Some(pattern) = clipboard {
+						rule.paste_base(pattern.clone());
+						changed = true;
+					}
+				}
+			});
 			let cells_y = rule.height();
 			let cells_x = rule.width();
 			let patt_width = CSIZE * cells_x as f32;
@@ -426,18 +2910,149 @@ fn rule_editor(
 				}
 			}
 
-			for (a, b, marked) in overlay_lines {
-				let stroke = if marked {
-					(6., Color32::RED)
+			for (a, b, marked, (tx, ty)) in overlay_lines {
+				let width = if marked { 6. } else { 2. };
+				let valid = rule.base().is_in_bounds(tx as isize, ty as isize);
+				let color = if marked || !valid {
+					Color32::RED
 				} else {
-					(2., Color32::WHITE)
+					Color32::WHITE
 				};
+				let stroke = (width, color);
 				ui.painter().line_segment([a, b], stroke);
 			}
+
+			let dsl = CollapsingHeader::new("DSL")
+				.id_source(("dsl", index))
+				.default_open(false)
+				.show(ui, |ui| {
+					if !rule_ui.dsl_open {
+						rule_ui.dsl_text = dsl::rule_to_dsl(rule);
+						rule_ui.dsl_edited = None;
+						rule_ui.dsl_parsed = Some(dsl::parse_rule(&rule_ui.dsl_text));
+					}
+					let text = TextEdit::multiline(&mut rule_ui.dsl_text)
+						.code_editor()
+						.desired_rows(2);
+					if ui.add(text).changed() {
+						rule_ui.dsl_edited = Some(Instant::now());
+					}
+					if let Some(edited) = rule_ui.dsl_edited {
+						let wait = DSL_PARSE_DELAY.saturating_sub(edited.elapsed());
+						if wait.is_zero() {
+							rule_ui.dsl_parsed = Some(dsl::parse_rule(&rule_ui.dsl_text));
+							rule_ui.dsl_edited = None;
+						} else {
+							ui.ctx().request_repaint_after(wait);
+						}
+					}
+					match &rule_ui.dsl_parsed {
+						Some(Ok(parsed)) => match missing_reference(parsed, cells, groups) {
+							Some(error) => {
+								ui.colored_label(Color32::RED, error);
+							}
+							None => {
+								pattern_preview(ui, parsed, cells, groups);
+								if ui.button("Apply").clicked() {
+									rule.paste_base(parsed.base().clone());
+									changed = true;
+								}
+							}
+						},
+						Some(Err(e)) => {
+							ui.colored_label(Color32::RED, e.to_string());
+						}
+						None => (),
+					}
+				});
+			rule_ui.dsl_open = dsl.body_returned.is_some();
 		});
 	changed
 }
 
+/// a cell or group the pattern uses that doesn't exist
+fn missing_reference(rule: &Rule, cells: &[CellData], groups: &[CellGroup]) -> Option<String> {
+	for x in 0..rule.width() {
+		for y in 0..rule.height() {
+			let (from, to) = rule.get(x, y);
+			let (from_cell, from_group) = match from {
+				RuleCellFrom::One(c) => (Some(c), None),
+				RuleCellFrom::Group(g) => (None, Some(g)),
+				_ => (None, None),
+			};
+			let (to_cell, to_group) = match to {
+				RuleCellTo::One(c) => (Some(c), None),
+				RuleCellTo::GroupRandom(g) | RuleCellTo::GroupRandomOther(g) => (None, Some(g)),
+				_ => (None, None),
+			};
+			for cell in [from_cell, to_cell].into_iter().flatten() {
+				if !cell.is_boundary() && cell.id() >= cells.len() {
+					return Some(format!("there is no cell {}", cell.id()));
+				}
+			}
+			for group in [from_group, to_group].into_iter().flatten() {
+				if group >= groups.len() {
+					return Some(format!("there is no group {group}"));
+				}
+			}
+		}
+	}
+	None
+}
+
+/// the pattern drawn like in the rule editor, without editing
+/// cells of a rule footprint, row-major, with None for the parts outside the world
+fn footprint_preview(ui: &mut Ui, footprint: &[Option<Cell>], width: usize, types: &[CellData]) {
+	let height = footprint.len() / width.max(1);
+	let (_, bounds) = ui.allocate_space(Vec2::new(width as f32, height as f32) * CSIZE);
+	for (i, cell) in footprint.iter().enumerate() {
+		let corner = bounds.min + Vec2::new((i % width) as f32, (i / width) as f32) * CSIZE;
+		let rect = Rect::from_min_size(corner, Vec2::splat(CSIZE));
+		match cell.map(|cell| types.get(cell.id())) {
+			Some(Some(data)) => render_cell(ui.painter(), rect, data),
+			Some(None) => draw_undefined(ui.painter(), rect),
+			None => draw_boundary(ui, rect),
+		}
+	}
+}
+
+fn pattern_preview(ui: &mut Ui, rule: &Rule, cells: &[CellData], groups: &[CellGroup]) {
+	let mut rule = rule.clone();
+	let (cells_x, cells_y) = (rule.width(), rule.height());
+	let patt_width = CSIZE * cells_x as f32;
+	let patt_height = CSIZE * cells_y as f32;
+	let (_, bounds) = ui.allocate_space(Vec2::new(
+		patt_width * 2. + RESIZE_BUTTON_WIDTH * 2. + CSIZE,
+		patt_height,
+	));
+	let from_origin = bounds.min;
+	let to_origin = bounds.min + Vec2::X * (patt_width + RESIZE_BUTTON_WIDTH * 2. + CSIZE);
+	let mut overlay_lines = Vec::new();
+	ui.add_enabled_ui(false, |ui| {
+		for x in 0..cells_x {
+			for y in 0..cells_y {
+				let (left, right) = rule.get_mut(x, y);
+				rule_cell_edit_from(ui, from_origin, left, x, y, cells, groups);
+				rule_cell_edit_to(
+					ui,
+					to_origin,
+					right,
+					(x, y),
+					cells,
+					groups,
+					(cells_x, cells_y),
+					&mut overlay_lines,
+				);
+			}
+		}
+	});
+	for (a, b, _, (tx, ty)) in overlay_lines {
+		let valid = rule.base().is_in_bounds(tx as isize, ty as isize);
+		let color = if valid { Color32::WHITE } else { Color32::RED };
+		ui.painter().line_segment([a, b], (2., color));
+	}
+}
+
 fn rule_cell_edit_from(
 	ui: &mut Ui,
 	origin: Pos2,
@@ -459,31 +3074,32 @@ fn rule_cell_edit_from(
 	// draw
 	match rule {
 		RuleCellFrom::Any => (),
-		RuleCellFrom::One(cell) => {
-			let color = cells[cell.id()].color;
-			let color = Color32::from_rgb(color[0], color[1], color[2]);
-			ui.painter()
-				.rect(rect.shrink(OUTLINE.0 / 2.), 0., color, OUTLINE);
-		}
-		RuleCellFrom::Group(group_id) => {
-			let group = &groups[*group_id];
-			draw_group(ui, rect, group, cells);
-		}
+		RuleCellFrom::One(cell) if cell.is_boundary() => draw_boundary(ui, rect),
+		RuleCellFrom::One(cell) => match cells.get(cell.id()) {
+			Some(data) => rule_cell_swatch(ui, rect, data),
+			None => draw_missing(ui, rect),
+		},
+		RuleCellFrom::Group(group_id) => match groups.get(*group_id) {
+			Some(group) => draw_group(ui, rect, group, cells),
+			None => draw_missing(ui, rect),
+		},
+		RuleCellFrom::BoundaryVoid => draw_boundary(ui, rect),
 	}
 	// update
 	if cycle_colors {
 		match rule {
 			RuleCellFrom::Any => (),
 			RuleCellFrom::One(cell) => {
-				cell.0 += 1;
-				cell.0 %= cells.len() as u16;
+				// the boundary pseudo-cell wraps around to the first type
+				cell.0 = cell.0.wrapping_add(1) % cells.len().max(1) as u16;
 				changed = true;
 			}
 			RuleCellFrom::Group(group_id) => {
 				*group_id += 1;
-				*group_id %= groups.len();
+				*group_id %= groups.len().max(1);
 				changed = true;
 			}
+			RuleCellFrom::BoundaryVoid => (),
 		}
 	}
 	if switch_type {
@@ -496,6 +3112,9 @@ fn rule_cell_edit_from(
 				*rule = RuleCellFrom::Group(0);
 			}
 			RuleCellFrom::Group(_) => {
+				*rule = RuleCellFrom::BoundaryVoid;
+			}
+			RuleCellFrom::BoundaryVoid => {
 				*rule = RuleCellFrom::Any;
 			}
 		}
@@ -503,6 +3122,7 @@ fn rule_cell_edit_from(
 	changed
 }
 
+#[allow(clippy::too_many_arguments)]
 fn rule_cell_edit_to(
 	ui: &mut Ui,
 	origin: Pos2,
@@ -511,7 +3131,7 @@ fn rule_cell_edit_to(
 	cells: &[CellData],
 	groups: &[CellGroup],
 	(rule_width, rule_height): (usize, usize),
-	overlay_lines: &mut Vec<(Pos2, Pos2, bool)>,
+	overlay_lines: &mut Vec<(Pos2, Pos2, bool, (usize, usize))>,
 ) -> bool {
 	let mut changed = false;
 	let rect = Rect::from_min_size(
@@ -526,22 +3146,34 @@ fn rule_cell_edit_to(
 	// draw
 	match rule {
 		RuleCellTo::None => (),
-		RuleCellTo::One(cell) => {
-			let color = cells[cell.id()].color;
-			let color = Color32::from_rgb(color[0], color[1], color[2]);
-			ui.painter()
-				.rect(rect.shrink(OUTLINE.0 / 2.), 0., color, OUTLINE);
-		}
-		RuleCellTo::GroupRandom(group_id) => {
-			let group = &groups[*group_id];
-			draw_group(ui, rect, group, cells);
+		RuleCellTo::One(cell) if cell.is_boundary() => draw_boundary(ui, rect),
+		RuleCellTo::One(cell) => match cells.get(cell.id()) {
+			Some(data) => rule_cell_swatch(ui, rect, data),
+			None => draw_missing(ui, rect),
+		},
+		RuleCellTo::GroupRandom(group_id) => match groups.get(*group_id) {
+			Some(group) => draw_group(ui, rect, group, cells),
+			None => draw_missing(ui, rect),
+		},
+		RuleCellTo::GroupRandomOther(group_id) => {
+			match groups.get(*group_id) {
+				Some(group) => draw_group(ui, rect, group, cells),
+				None => draw_missing(ui, rect),
+			}
+			ui.painter_at(rect).text(
+				rect.center(),
+				Align2::CENTER_CENTER,
+				"≠",
+				FontId::proportional(CSIZE * 0.5),
+				Color32::WHITE,
+			);
 		}
 		RuleCellTo::Copy(x, y) => {
 			let this = rect.center();
 			let target = origin + Vec2::from((*x as f32, *y as f32)) * CSIZE
 				- Vec2::X * (CSIZE * (rule_width as f32 + 1.) + RESIZE_BUTTON_WIDTH * 2.)
 				+ Vec2::splat(CSIZE) * 0.5;
-			overlay_lines.push((this, target, hovered));
+			overlay_lines.push((this, target, hovered, (*x, *y)));
 		}
 	}
 
@@ -549,13 +3181,12 @@ fn rule_cell_edit_to(
 		match rule {
 			RuleCellTo::None => (),
 			RuleCellTo::One(cell) => {
-				cell.0 += 1;
-				cell.0 %= cells.len() as u16;
+				cell.0 = cell.0.wrapping_add(1) % cells.len().max(1) as u16;
 				changed = true;
 			}
-			RuleCellTo::GroupRandom(group_id) => {
+			RuleCellTo::GroupRandom(group_id) | RuleCellTo::GroupRandomOther(group_id) => {
 				*group_id += 1;
-				*group_id %= groups.len();
+				*group_id %= groups.len().max(1);
 				changed = true;
 			}
 			RuleCellTo::Copy(x, y) => {
@@ -581,7 +3212,10 @@ fn rule_cell_edit_to(
 			RuleCellTo::One(_) => {
 				*rule = RuleCellTo::GroupRandom(0);
 			}
-			RuleCellTo::GroupRandom(_) => {
+			RuleCellTo::GroupRandom(group_id) => {
+				*rule = RuleCellTo::GroupRandomOther(*group_id);
+			}
+			RuleCellTo::GroupRandomOther(_) => {
 				*rule = RuleCellTo::Copy(0, 0);
 			}
 			RuleCellTo::Copy(_, _) => {
@@ -592,17 +3226,51 @@ fn rule_cell_edit_to(
 	changed
 }
 
+/// a red cross for a cell or group that doesn't exist
+fn draw_missing(ui: &Ui, rect: Rect) {
+	let r = rect.shrink(OUTLINE.0 * 2.);
+	let stroke = (OUTLINE.0, Color32::RED);
+	ui.painter()
+		.line_segment([r.left_top(), r.right_bottom()], stroke);
+	ui.painter()
+		.line_segment([r.right_top(), r.left_bottom()], stroke);
+}
+
+fn draw_boundary(ui: &Ui, rect: Rect) {
+	let r = rect.shrink(OUTLINE.0);
+	let corners = [
+		r.left_top(),
+		r.right_top(),
+		r.right_bottom(),
+		r.left_bottom(),
+		r.left_top(),
+	];
+	ui.painter()
+		.extend(Shape::dashed_line(&corners, OUTLINE, 2., 2.));
+}
+
+/// a cell type in a rule pattern, outlined
+fn rule_cell_swatch(ui: &mut Ui, rect: Rect, data: &CellData) {
+	let rect = rect.shrink(OUTLINE.0 / 2.);
+	ui.painter().rect(rect, 0., cell_color(data), OUTLINE);
+	paint_cell_pattern(ui.painter(), rect.shrink(OUTLINE.0 / 2.), data);
+}
+
 fn draw_group(ui: &mut Ui, rect: Rect, group: &CellGroup, cells: &[CellData]) {
-	let group_size = group.cells.len();
+	let group_size = group.placeable_cells().count();
 	let radius_per_color = (CSIZE * 0.7) / (group_size as f32);
-	for (i, cell) in group.cells.iter().enumerate() {
-		let color = cells[cell.id()].color;
-		let color = Color32::from_rgb(color[0], color[1], color[2]);
+	for (i, cell) in group.placeable_cells().enumerate() {
+		let Some(data) = cells.get(cell.id()) else {
+			continue;
+		};
 		let radius = radius_per_color * ((group_size - i) as f32);
-		ui.painter_at(rect)
-			.circle_filled(rect.center(), radius, color);
+		let painter = ui.painter_at(rect);
+		painter.circle_filled(rect.center(), radius, cell_color(data));
+		// the square inside the circle, the next member covers its middle
+		let inside = Rect::from_center_size(rect.center(), Vec2::splat(radius * SQRT_2));
+		paint_cell_pattern(&painter, inside, data);
 	}
-	if group.void {
+	if group.contains(None) {
 		ui.painter_at(rect)
 			.line_segment([rect.min, rect.max], (1., Color32::WHITE));
 	}
@@ -0,0 +1,240 @@
+use std::{
+	io::{self, Read, Write},
+	net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::{self, Receiver, TryRecvError},
+		Arc,
+	},
+	thread::{self, JoinHandle},
+	time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+pub const DEFAULT_PORT: u16 = 47_100;
+/// a whole message has to be sent or received within this long
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// how often the background threads check whether they should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// larger messages are refused instead of allocating whatever the length prefix says
+const MAX_MESSAGE_LEN: u32 = 16 * 1024 * 1024;
+
+/// Accepts json messages from other instances on a localhost port.
+/// A message is a big-endian u32 length followed by that many bytes of json.
+/// Connections are accepted and read on a background thread, which stops when this is dropped.
+#[derive(Debug)]
+pub struct Listener {
+	received: Receiver<io::Result<Value>>,
+	stop: Arc<AtomicBool>,
+	thread: Option<JoinHandle<()>>,
+}
+
+impl Listener {
+	pub fn bind(port: u16) -> io::Result<Self> {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+		Self::spawn(listener)
+	}
+
+	fn spawn(listener: TcpListener) -> io::Result<Self> {
+		listener.set_nonblocking(true)?;
+		let (sender, received) = mpsc::channel();
+		let stop = Arc::new(AtomicBool::new(false));
+		let thread_stop = stop.clone();
+		let thread = thread::spawn(move || {
+			while !thread_stop.load(Ordering::Relaxed) {
+				let message = match listener.accept() {
+					Ok((stream, _)) => receive(stream, &thread_stop),
+					Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+						thread::sleep(POLL_INTERVAL);
+						continue;
+					}
+					Err(e) => Err(e),
+				};
+				if sender.send(message).is_err() {
+					break;
+				}
+			}
+		});
+		Ok(Self {
+			received,
+			stop,
+			thread: Some(thread),
+		})
+	}
+
+	/// a message that has been read completely, None if none has arrived since the last call
+	pub fn poll(&self) -> Option<io::Result<Value>> {
+		self.received.try_recv().ok()
+	}
+}
+
+impl Drop for Listener {
+	fn drop(&mut self) {
+		self.stop.store(true, Ordering::Relaxed);
+		// the thread checks the flag at least every POLL_INTERVAL, and the port is free once it returns
+		if let Some(thread) = self.thread.take() {
+			let _ = thread.join();
+		}
+	}
+}
+
+fn receive(mut stream: TcpStream, stop: &AtomicBool) -> io::Result<Value> {
+	let deadline = Instant::now() + TIMEOUT;
+	stream.set_nonblocking(false)?;
+	let mut len = [0; 4];
+	read_exact_by(&mut stream, &mut len, deadline, stop)?;
+	let len = u32::from_be_bytes(len);
+	if len > MAX_MESSAGE_LEN {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("message of {len} bytes is too large"),
+		));
+	}
+	let mut data = vec![0; len as usize];
+	read_exact_by(&mut stream, &mut data, deadline, stop)?;
+	Ok(serde_json::from_slice(&data)?)
+}
+
+/// like `read_exact`, but fails once `deadline` has passed or `stop` is set
+fn read_exact_by(
+	stream: &mut TcpStream,
+	mut buf: &mut [u8],
+	deadline: Instant,
+	stop: &AtomicBool,
+) -> io::Result<()> {
+	while !buf.is_empty() {
+		let left = time_left(deadline)?;
+		if stop.load(Ordering::Relaxed) {
+			return Err(io::Error::new(
+				io::ErrorKind::Interrupted,
+				"stopped listening",
+			));
+		}
+		stream.set_read_timeout(Some(left.min(POLL_INTERVAL)))?;
+		match stream.read(buf) {
+			Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+			Ok(n) => buf = &mut buf[n..],
+			Err(e)
+				if matches!(
+					e.kind(),
+					io::ErrorKind::WouldBlock
+						| io::ErrorKind::TimedOut
+						| io::ErrorKind::Interrupted
+				) => {}
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(())
+}
+
+/// time until `deadline`, or a timeout error if it has passed
+fn time_left(deadline: Instant) -> io::Result<Duration> {
+	let left = deadline.saturating_duration_since(Instant::now());
+	if left.is_zero() {
+		return Err(io::Error::new(
+			io::ErrorKind::TimedOut,
+			format!("no complete message within {TIMEOUT:?}"),
+		));
+	}
+	Ok(left)
+}
+
+/// A message being sent to a `Listener` on a background thread.
+#[derive(Debug)]
+pub struct Sending {
+	result: Receiver<io::Result<()>>,
+}
+
+impl Sending {
+	/// the result once sending has finished, None while it is still going
+	pub fn poll(&self) -> Option<io::Result<()>> {
+		match self.result.try_recv() {
+			Ok(result) => Some(result),
+			Err(TryRecvError::Empty) => None,
+			Err(TryRecvError::Disconnected) => {
+				Some(Err(io::Error::other("sending thread stopped")))
+			}
+		}
+	}
+}
+
+/// Start sending one message to a `Listener`, `addr` is a host:port pair such as "127.0.0.1:47100".
+/// Resolving, connecting and writing all have to finish within `TIMEOUT`.
+pub fn send(addr: String, message: Value) -> Sending {
+	let (sender, result) = mpsc::channel();
+	thread::spawn(move || {
+		let _ = sender.send(send_blocking(&addr, &message));
+	});
+	Sending { result }
+}
+
+fn send_blocking(addr: &str, message: &Value) -> io::Result<()> {
+	let deadline = Instant::now() + TIMEOUT;
+	let addr: SocketAddr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+		io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("no address for '{addr}'"),
+		)
+	})?;
+	let data = serde_json::to_vec(message)?;
+	let mut stream = TcpStream::connect_timeout(&addr, time_left(deadline)?)?;
+	for part in [&(data.len() as u32).to_be_bytes()[..], &data] {
+		let mut part = part;
+		while !part.is_empty() {
+			stream.set_write_timeout(Some(time_left(deadline)?))?;
+			match stream.write(part) {
+				Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+				Ok(n) => part = &part[n..],
+				Err(e)
+					if matches!(
+						e.kind(),
+						io::ErrorKind::WouldBlock
+							| io::ErrorKind::TimedOut
+							| io::ErrorKind::Interrupted
+					) => {}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+	stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// poll until something arrives, failing the test after a while
+	fn wait_for<T>(mut poll: impl FnMut() -> Option<T>) -> T {
+		let deadline = Instant::now() + TIMEOUT * 2;
+		loop {
+			if let Some(value) = poll() {
+				return value;
+			}
+			assert!(Instant::now() < deadline, "nothing arrived in time");
+			thread::sleep(POLL_INTERVAL);
+		}
+	}
+
+	#[test]
+	fn messages_arrive_without_blocking_either_side() {
+		let socket = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let addr = socket.local_addr().unwrap().to_string();
+		let listener = Listener::spawn(socket).unwrap();
+		assert!(listener.poll().is_none());
+
+		// a peer that connects and never writes only holds up the messages after it until the deadline
+		let stalled = TcpStream::connect(&addr).unwrap();
+		let message = serde_json::json!({ "name": "fall" });
+		let sending = send(addr, message.clone());
+		assert!(wait_for(|| sending.poll()).is_ok());
+		let first = wait_for(|| listener.poll());
+		assert_eq!(
+			first.unwrap_err().kind(),
+			io::ErrorKind::TimedOut,
+			"the stalled connection gives up at the deadline"
+		);
+		assert_eq!(wait_for(|| listener.poll()).unwrap(), message);
+		drop(stalled);
+	}
+}
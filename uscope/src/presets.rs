@@ -0,0 +1,82 @@
+use egui::Key;
+use petri::{Cell, StepMode};
+use serde::{Deserialize, Serialize};
+
+/// what gets painted into the world
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Brush {
+	Cell(Cell),
+	/// a random member of the group for each painted cell
+	Group(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BrushMode {
+	Paint,
+	FloodFill {
+		max_cells: usize,
+	},
+	/// exclude painted cells from simulation
+	Freeze,
+	Unfreeze,
+	/// drag out a rect to see statistics for it
+	Select,
+}
+
+/// the part of the UI state that presets capture
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+	pub speed: u32,
+	pub paused: bool,
+	pub show_grid: bool,
+	/// write the cell type names over the world when zoomed in enough
+	#[serde(default)]
+	pub show_labels: bool,
+	pub brush: Brush,
+	#[serde(default = "default_brush_mode")]
+	pub brush_mode: BrushMode,
+	#[serde(default)]
+	pub step_mode: StepMode,
+}
+
+fn default_brush_mode() -> BrushMode {
+	BrushMode::Paint
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			speed: 50,
+			paused: false,
+			show_grid: false,
+			show_labels: false,
+			brush: Brush::Cell(Cell(1)),
+			brush_mode: BrushMode::Paint,
+			step_mode: StepMode::Random,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+	pub name: String,
+	pub settings: Settings,
+}
+
+pub const STORAGE_KEY: &str = "presets";
+
+/// presets are bound to F1..F12 in list order
+pub const SHORTCUTS: [Key; 12] = [
+	Key::F1,
+	Key::F2,
+	Key::F3,
+	Key::F4,
+	Key::F5,
+	Key::F6,
+	Key::F7,
+	Key::F8,
+	Key::F9,
+	Key::F10,
+	Key::F11,
+	Key::F12,
+];
@@ -0,0 +1,141 @@
+use std::{mem, time::Duration};
+
+/// Keeps the last `capacity` items pushed, overwriting the oldest once full.
+/// Pushing never shifts or reallocates after the buffer has filled up.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T> {
+	items: Vec<T>,
+	capacity: usize,
+	/// index of the oldest item once the buffer is full
+	start: usize,
+}
+
+impl<T> RingBuffer<T> {
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			items: Vec::with_capacity(capacity),
+			capacity: capacity.max(1),
+			start: 0,
+		}
+	}
+
+	pub fn push(&mut self, item: T) {
+		if self.items.len() < self.capacity {
+			self.items.push(item);
+		} else {
+			self.items[self.start] = item;
+			self.start = (self.start + 1) % self.capacity;
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.items.len()
+	}
+
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	/// change the capacity, dropping the oldest items if there are too many
+	pub fn set_capacity(&mut self, capacity: usize) {
+		let capacity = capacity.max(1);
+		if capacity == self.capacity {
+			return;
+		}
+		self.items.rotate_left(self.start);
+		self.start = 0;
+		let excess = self.items.len().saturating_sub(capacity);
+		self.items.drain(..excess);
+		self.items.shrink_to(capacity);
+		self.capacity = capacity;
+	}
+
+	/// oldest first
+	pub fn iter(&self) -> impl Iterator<Item = &T> {
+		let (newer, older) = self.items.split_at(self.start);
+		older.iter().chain(newer)
+	}
+
+	pub fn last(&self) -> Option<&T> {
+		match self.start {
+			0 => self.items.last(),
+			start => self.items.get(start - 1),
+		}
+	}
+
+	/// bytes reserved for items, whether or not they are filled yet
+	pub fn memory_size(&self) -> usize {
+		self.capacity * mem::size_of::<T>()
+	}
+}
+
+impl RingBuffer<Duration> {
+	/// mean of the items pushed so far, zero if there are none
+	pub fn average(&self) -> Duration {
+		match self.len() {
+			0 => Duration::ZERO,
+			len => self.iter().sum::<Duration>() / len as u32,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn items(buffer: &RingBuffer<u32>) -> Vec<u32> {
+		buffer.iter().copied().collect()
+	}
+
+	#[test]
+	fn push_past_capacity_wraps() {
+		let mut buffer = RingBuffer::new(3);
+		assert_eq!(buffer.last(), None);
+		for i in 0..5 {
+			buffer.push(i);
+		}
+		assert_eq!(buffer.len(), 3);
+		assert_eq!(items(&buffer), [2, 3, 4]);
+		assert_eq!(buffer.last(), Some(&4));
+		buffer.push(5);
+		buffer.push(6);
+		assert_eq!(items(&buffer), [4, 5, 6]);
+		assert_eq!(buffer.last(), Some(&6));
+	}
+
+	#[test]
+	fn set_capacity_keeps_newest() {
+		let mut buffer = RingBuffer::new(4);
+		for i in 0..6 {
+			buffer.push(i);
+		}
+		buffer.set_capacity(2);
+		assert_eq!(items(&buffer), [4, 5]);
+		assert_eq!(buffer.last(), Some(&5));
+
+		buffer.set_capacity(4);
+		assert_eq!(buffer.capacity(), 4);
+		buffer.push(6);
+		buffer.push(7);
+		assert_eq!(items(&buffer), [4, 5, 6, 7]);
+		buffer.push(8);
+		assert_eq!(items(&buffer), [5, 6, 7, 8]);
+
+		buffer.set_capacity(0);
+		assert_eq!(buffer.capacity(), 1);
+		assert_eq!(items(&buffer), [8]);
+	}
+
+	#[test]
+	fn average_of_filled_items() {
+		let mut buffer = RingBuffer::new(4);
+		assert_eq!(buffer.average(), Duration::ZERO);
+		buffer.push(Duration::from_millis(10));
+		buffer.push(Duration::from_millis(20));
+		assert_eq!(buffer.average(), Duration::from_millis(15));
+		for ms in [30, 40, 50] {
+			buffer.push(Duration::from_millis(ms));
+		}
+		assert_eq!(buffer.average(), Duration::from_millis(35));
+	}
+}
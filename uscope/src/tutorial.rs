@@ -0,0 +1,157 @@
+use petri::{Cell, Dish, Rule};
+
+/// part of the UI the current step points at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Highlight {
+	Cells,
+	World,
+	Rule(&'static str),
+	Groups,
+}
+
+#[derive(Debug)]
+pub struct Step {
+	pub title: &'static str,
+	pub text: &'static str,
+	pub highlight: Highlight,
+	pub is_complete: fn(&Dish) -> bool,
+}
+
+pub const STEPS: &[Step] = &[
+	Step {
+		title: "Cells",
+		text: "Every cell in the world has a type. Click \"add cell\" to create a new cell type.",
+		highlight: Highlight::Cells,
+		is_complete: |dish| dish.types.len() > 2,
+	},
+	Step {
+		title: "Painting",
+		text: "Select your new cell with the radio button next to it, then paint it into the world with the mouse.",
+		highlight: Highlight::World,
+		is_complete: |dish| count_cells(dish, Cell(2)) > 0,
+	},
+	Step {
+		title: "Rules",
+		text: "Rules replace the left pattern with the right one wherever it matches. Uncheck the \"slide\" rule and watch the sand stack up in columns.",
+		highlight: Highlight::Rule("slide"),
+		is_complete: |dish| find_rule(dish, "slide").is_some_and(|rule| !rule.enabled),
+	},
+	Step {
+		title: "Groups",
+		text: "Groups let one rule cell match several cell types. Click \"add group\" and include some cells with its edit menu.",
+		highlight: Highlight::Groups,
+		is_complete: |dish| dish.groups.iter().skip(1).any(|g| !g.cells.is_empty()),
+	},
+	Step {
+		title: "Symmetry",
+		text: "Flip and rotate create mirrored and rotated copies of a rule. Enable \"rotate\" on the \"fall\" rule so cells fall in every direction.",
+		highlight: Highlight::Rule("fall"),
+		is_complete: |dish| find_rule(dish, "fall").is_some_and(|rule| rule.rotate),
+	},
+];
+
+fn find_rule<'a>(dish: &'a Dish, name: &str) -> Option<&'a Rule> {
+	dish.rules.iter().find(|r| r.name == name)
+}
+
+fn count_cells(dish: &Dish, cell: Cell) -> usize {
+	(0..dish.world_width())
+		.flat_map(|x| (0..dish.world_height()).map(move |y| (x, y)))
+		.filter(|&(x, y)| dish.get_cell(x, y) == Some(cell))
+		.count()
+}
+
+#[derive(Debug, Default)]
+pub struct Tutorial {
+	/// index into STEPS, None when the tutorial is not running
+	pub step: Option<usize>,
+}
+
+impl Tutorial {
+	/// loads the example dish the tutorial steps are written for
+	pub fn start(&mut self, dish: &mut Dish) {
+		*dish = Dish::new();
+		self.step = Some(0);
+	}
+
+	pub fn current(&self) -> Option<&'static Step> {
+		self.step.and_then(|i| STEPS.get(i))
+	}
+
+	pub fn highlight(&self) -> Option<Highlight> {
+		self.current().map(|s| s.highlight)
+	}
+
+	pub fn advance(&mut self) {
+		if let Some(step) = &mut self.step {
+			*step += 1;
+			if *step >= STEPS.len() {
+				self.step = None;
+			}
+		}
+	}
+
+	pub fn stop(&mut self) {
+		self.step = None;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use petri::{CellData, CellGroup};
+
+	/// what the user is asked to do in each step
+	fn do_step(index: usize, dish: &mut Dish) {
+		match index {
+			0 => {
+				dish.add_type(CellData::new("new", 0, 0, 255));
+			}
+			1 => dish.set_cell(2, 2, Cell(2)),
+			2 | 4 => {
+				let Highlight::Rule(name) = STEPS[index].highlight else {
+					panic!("step {index} should point at a rule");
+				};
+				let rule = dish.rules.iter_mut().find(|r| r.name == name).unwrap();
+				if index == 2 {
+					rule.enabled = false;
+				} else {
+					rule.rotate = true;
+				}
+			}
+			3 => {
+				dish.add_group(CellGroup {
+					cells: vec![Cell(1)],
+					..Default::default()
+				});
+			}
+			_ => panic!("step {index} has no action in this test"),
+		}
+	}
+
+	#[test]
+	fn steps_complete_in_order() {
+		let mut dish = Dish::new();
+		let mut tutorial = Tutorial::default();
+		tutorial.start(&mut dish);
+		for index in 0..STEPS.len() {
+			let step = tutorial.current().unwrap();
+			assert!(!(step.is_complete)(&dish), "{} done too early", step.title);
+			do_step(index, &mut dish);
+			assert!((step.is_complete)(&dish), "{} not done", step.title);
+			tutorial.advance();
+		}
+		assert!(tutorial.current().is_none());
+		assert!(tutorial.highlight().is_none());
+	}
+
+	#[test]
+	fn highlighted_rules_exist_in_the_example() {
+		let dish = Dish::new();
+		for step in STEPS {
+			if let Highlight::Rule(name) = step.highlight {
+				assert!(find_rule(&dish, name).is_some(), "{name}");
+			}
+		}
+	}
+}